@@ -3,13 +3,15 @@
 
   // --- LS discovery ---
 
+  // Returns every running LS instance, newest first.
   function discoverLs(ctx) {
-    return ctx.host.ls.discover({
+    var opts = {
       processName: "language_server_macos",
       markers: ["antigravity"],
       csrfFlag: "--csrf_token",
       portFlag: "--extension_server_port",
-    })
+    }
+    return ctx.host.ls.discoverAll(opts)
   }
 
   function probePort(ctx, scheme, port, csrf) {
@@ -52,6 +54,16 @@
     return null
   }
 
+  // With several windows open, use the newest instance that answers.
+  function findWorkingInstance(ctx, instances) {
+    for (var i = 0; i < instances.length; i++) {
+      var found = findWorkingPort(ctx, instances[i])
+      if (found) return { discovery: instances[i], port: found.port, scheme: found.scheme }
+      ctx.host.log.info("LS instance pid " + instances[i].pid + " not reachable")
+    }
+    return null
+  }
+
   function callLs(ctx, port, scheme, csrf, method, body) {
     var resp = ctx.host.http.request({
       method: "POST",
@@ -107,11 +119,12 @@
   // --- Probe ---
 
   function probe(ctx) {
-    var discovery = discoverLs(ctx)
-    if (!discovery) throw "Start Antigravity and try again."
+    var instances = discoverLs(ctx)
+    if (instances.length === 0) throw "Start Antigravity and try again."
 
-    var found = findWorkingPort(ctx, discovery)
+    var found = findWorkingInstance(ctx, instances)
     if (!found) throw "Start Antigravity and try again."
+    var discovery = found.discovery
 
     ctx.host.log.info("using LS at " + found.scheme + "://127.0.0.1:" + found.port)
 
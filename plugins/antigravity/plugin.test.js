@@ -74,7 +74,7 @@ function makeUserStatusResponse(overrides) {
 }
 
 function setupHttpMock(ctx, discovery, responseBody) {
-  ctx.host.ls.discover.mockReturnValue(discovery)
+  ctx.host.ls.discoverAll.mockReturnValue(discovery ? [discovery] : [])
   ctx.host.http.request.mockImplementation((opts) => {
     if (String(opts.url).includes("GetUnleashData")) {
       return { status: 200, bodyText: "{}" }
@@ -93,14 +93,14 @@ describe("antigravity plugin", () => {
 
   it("throws when LS not found", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(null)
+    ctx.host.ls.discoverAll.mockReturnValue([])
     const plugin = await loadPlugin()
     expect(() => plugin.probe(ctx)).toThrow("Start Antigravity and try again.")
   })
 
   it("throws when no working port found", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery())
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery()])
     ctx.host.http.request.mockImplementation(() => {
       throw new Error("connection refused")
     })
@@ -108,9 +108,28 @@ describe("antigravity plugin", () => {
     expect(() => plugin.probe(ctx)).toThrow("Start Antigravity and try again.")
   })
 
+  it("uses the newest reachable instance from discoverAll", async () => {
+    const ctx = makeCtx()
+    ctx.host.ls.discoverAll.mockImplementation(() => [
+      makeDiscovery({ pid: 2, csrf: "stale-csrf", ports: [43001] }),
+      makeDiscovery({ pid: 1, csrf: "live-csrf", ports: [43002] }),
+    ])
+    const csrfs = []
+    ctx.host.http.request.mockImplementation((opts) => {
+      if (String(opts.url).includes(":43001/")) throw new Error("connection refused")
+      if (String(opts.url).includes("GetUnleashData")) return { status: 200, bodyText: "{}" }
+      csrfs.push(opts.headers["x-codeium-csrf-token"])
+      return { status: 200, bodyText: JSON.stringify(makeUserStatusResponse()) }
+    })
+    const plugin = await loadPlugin()
+    const result = plugin.probe(ctx)
+    expect(result.lines.length).toBeGreaterThan(0)
+    expect(csrfs).toEqual(["live-csrf"])
+  })
+
   it("throws when both GetUserStatus and GetCommandModelConfigs fail", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery())
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery()])
     ctx.host.http.request.mockImplementation((opts) => {
       if (String(opts.url).includes("GetUnleashData")) {
         return { status: 200, bodyText: "{}" }
@@ -179,7 +198,7 @@ describe("antigravity plugin", () => {
 
   it("falls back to GetCommandModelConfigs when GetUserStatus fails", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery())
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery()])
     ctx.host.http.request.mockImplementation((opts) => {
       if (String(opts.url).includes("GetUnleashData")) {
         return { status: 200, bodyText: "{}" }
@@ -217,7 +236,7 @@ describe("antigravity plugin", () => {
 
   it("uses extension port as fallback when all ports fail probing", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery({ ports: [99999], extensionPort: 42010 }))
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery({ ports: [99999], extensionPort: 42010 })])
 
     let usedPort = null
     ctx.host.http.request.mockImplementation((opts) => {
@@ -309,7 +328,7 @@ describe("antigravity plugin", () => {
 
   it("probes ports with HTTPS first, then HTTP, picks first success", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery({ ports: [10001, 10002] }))
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery({ ports: [10001, 10002] })])
 
     const probed = []
     ctx.host.http.request.mockImplementation((opts) => {
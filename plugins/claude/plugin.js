@@ -121,11 +121,21 @@
     return null
   }
 
+  // Overridable in settings so added accounts can point at another Claude config dir.
+  function credFile(ctx) {
+    return (ctx.config && ctx.config.credentialsFile) || CRED_FILE
+  }
+
+  function keychainService(ctx) {
+    return (ctx.config && ctx.config.keychainService) || KEYCHAIN_SERVICE
+  }
+
   function loadCredentials(ctx) {
     // Try file first
-    if (ctx.host.fs.exists(CRED_FILE)) {
+    const file = credFile(ctx)
+    if (ctx.host.fs.exists(file)) {
       try {
-        const text = ctx.host.fs.readText(CRED_FILE)
+        const text = ctx.host.fs.readText(file)
         const parsed = tryParseCredentialJSON(ctx, text)
         if (parsed) {
           const oauth = parsed.claudeAiOauth
@@ -142,7 +152,7 @@
 
     // Try keychain fallback
     try {
-      const keychainValue = ctx.host.keychain.readGenericPassword(KEYCHAIN_SERVICE)
+      const keychainValue = ctx.host.keychain.readGenericPassword(keychainService(ctx))
       if (keychainValue) {
         const parsed = tryParseCredentialJSON(ctx, keychainValue)
         if (parsed) {
@@ -168,13 +178,13 @@
     const text = JSON.stringify(fullData)
     if (source === "file") {
       try {
-        ctx.host.fs.writeText(CRED_FILE, text)
+        ctx.host.fs.writeText(credFile(ctx), text, { lock: true, backup: true })
       } catch (e) {
         ctx.host.log.error("Failed to write Claude credentials file: " + String(e))
       }
     } else if (source === "keychain") {
       try {
-        ctx.host.keychain.writeGenericPassword(KEYCHAIN_SERVICE, text)
+        ctx.host.keychain.writeGenericPassword(keychainService(ctx), text)
       } catch (e) {
         ctx.host.log.error("Failed to write Claude credentials keychain: " + String(e))
       }
@@ -113,6 +113,20 @@ describe("claude plugin", () => {
     expect(result.lines.find((line) => line.label === "Extra usage")).toBeTruthy()
   })
 
+  it("reads credentials from the configured file and keychain item", async () => {
+    const ctx = makeCtx()
+    ctx.config = { credentialsFile: "~/.claude-work/.credentials.json", keychainService: "Claude Work" }
+    ctx.host.fs.exists = vi.fn(() => false)
+    ctx.host.keychain.readGenericPassword.mockReturnValue(
+      JSON.stringify({ claudeAiOauth: { accessToken: "token", subscriptionType: "pro" } })
+    )
+    ctx.host.http.request.mockReturnValue({ status: 200, bodyText: JSON.stringify({}) })
+    const plugin = await loadPlugin()
+    plugin.probe(ctx)
+    expect(ctx.host.fs.exists).toHaveBeenCalledWith("~/.claude-work/.credentials.json")
+    expect(ctx.host.keychain.readGenericPassword).toHaveBeenCalledWith("Claude Work")
+  })
+
   it("uses keychain credentials when value is hex-encoded JSON", async () => {
     const ctx = makeCtx()
     ctx.host.fs.exists = () => false
@@ -342,7 +356,11 @@ describe("claude plugin", () => {
     const plugin = await loadPlugin()
     const result = plugin.probe(ctx)
     expect(result.lines.find((line) => line.label === "Session")).toBeTruthy()
-    expect(ctx.host.fs.writeText).toHaveBeenCalled()
+    expect(ctx.host.fs.writeText).toHaveBeenCalledWith(
+      "~/.claude/.credentials.json",
+      expect.any(String),
+      { lock: true, backup: true }
+    )
   })
 
   it("refreshes keychain credentials and writes back to keychain", async () => {
@@ -134,7 +134,10 @@
       auth.last_refresh = new Date().toISOString()
 
       try {
-        ctx.host.fs.writeText(authPath, JSON.stringify(auth, null, 2))
+        ctx.host.fs.writeText(authPath, JSON.stringify(auth, null, 2), {
+          lock: true,
+          backup: true,
+        })
         ctx.host.log.info("refresh succeeded, auth file updated")
       } catch (e) {
         ctx.host.log.warn("refresh succeeded but failed to save auth: " + String(e))
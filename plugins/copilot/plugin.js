@@ -1,6 +1,7 @@
 (function () {
   const KEYCHAIN_SERVICE = "OpenUsage-copilot";
   const GH_KEYCHAIN_SERVICE = "gh:github.com";
+  const GH_TOKEN_COMMAND = ["gh", "auth", "token"];
   const USAGE_URL = "https://api.github.com/copilot_internal/user";
 
   function readJson(ctx, path) {
@@ -78,6 +79,27 @@
     } catch (e) {
       ctx.host.log.info("gh CLI keychain read failed: " + String(e));
     }
+    return loadTokenFromGhCommand(ctx);
+  }
+
+  // gh falls back to a plain config file when no keyring is available, so ask
+  // the CLI itself (declared under permissions.exec in plugin.json).
+  function loadTokenFromGhCommand(ctx) {
+    if (typeof ctx.host.exec !== "function") return null;
+    try {
+      const result = ctx.host.exec(GH_TOKEN_COMMAND);
+      const token =
+        result && result.exitCode === 0 ? String(result.stdout || "").trim() : "";
+      if (token) {
+        ctx.host.log.info("token loaded from gh auth token");
+        return { token: token, source: "gh-cli" };
+      }
+      ctx.host.log.info(
+        "gh auth token returned no token (exit " + (result && result.exitCode) + ")",
+      );
+    } catch (e) {
+      ctx.host.log.info("gh auth token failed: " + String(e));
+    }
     return null;
   }
 
@@ -113,6 +113,21 @@ describe("copilot plugin", () => {
     expect(call.headers.Authorization).toBe("token ghu_state");
   });
 
+  it("falls back to gh auth token when gh has no keychain item", async () => {
+    const ctx = makePluginTestContext();
+    ctx.host.keychain.readGenericPassword.mockImplementation(() => {
+      throw new Error("keychain item not found");
+    });
+    ctx.host.exec.mockReturnValue({ stdout: "gho_cmd\n", stderr: "", exitCode: 0, truncated: false });
+    mockUsageOk(ctx);
+    const plugin = await loadPlugin();
+    const result = plugin.probe(ctx);
+    expect(result.lines.find((l) => l.label === "Premium")).toBeTruthy();
+    expect(ctx.host.exec).toHaveBeenCalledWith(["gh", "auth", "token"]);
+    const call = ctx.host.http.request.mock.calls[0][0];
+    expect(call.headers.Authorization).toBe("token gho_cmd");
+  });
+
   it("prefers keychain over gh-cli", async () => {
     const ctx = makePluginTestContext();
     ctx.host.keychain.readGenericPassword.mockImplementation((service) => {
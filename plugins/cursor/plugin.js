@@ -1,10 +1,8 @@
 (function () {
-  const STATE_DB_MACOS =
-    "~/Library/Application Support/Cursor/User/globalStorage/state.vscdb"
-  const STATE_DB_WINDOWS =
-    "~/AppData/Roaming/Cursor/User/globalStorage/state.vscdb"
-  const STATE_DB_LINUX =
-    "~/.config/Cursor/User/globalStorage/state.vscdb"
+  const vscdb = require("openusage:vscdb")
+  const oauth = require("openusage:oauth")
+  const connect = require("openusage:connect")
+
   const BASE_URL = "https://api2.cursor.sh"
   const USAGE_URL = BASE_URL + "/aiserver.v1.DashboardService/GetCurrentPeriodUsage"
   const PLAN_URL = BASE_URL + "/aiserver.v1.DashboardService/GetPlanInfo"
@@ -13,66 +11,18 @@
   const REST_USAGE_URL = "https://cursor.com/api/usage"
   const CLIENT_ID = "KbZUR41cY7W6zRSdpSUJ7I7mLYBKOCmB"
   const REFRESH_BUFFER_MS = 5 * 60 * 1000 // refresh 5 minutes before expiration
-
-  function getStateDbPath(ctx) {
-    const platform = String(ctx && ctx.app && ctx.app.platform || "").toLowerCase()
-    if (platform === "windows") return STATE_DB_WINDOWS
-    if (platform === "linux") return STATE_DB_LINUX
-    return STATE_DB_MACOS
-  }
+  const PLAN_CACHE = { ttlMs: 15 * 60 * 1000 } // plan info rarely changes
 
   function readStateValue(ctx, key) {
-    try {
-      const stateDb = getStateDbPath(ctx)
-      const sql =
-        "SELECT value FROM ItemTable WHERE key = '" + key + "' LIMIT 1;"
-      const json = ctx.host.sqlite.query(stateDb, sql)
-      const rows = ctx.util.tryParseJson(json)
-      if (!Array.isArray(rows)) {
-        throw new Error("sqlite returned invalid json")
-      }
-      if (rows.length > 0 && rows[0].value) {
-        return rows[0].value
-      }
-    } catch (e) {
-      ctx.host.log.warn("sqlite read failed for " + key + ": " + String(e))
-    }
-    return null
+    return vscdb.read(ctx, vscdb.statePath(ctx, "Cursor"), key)
   }
 
   function writeStateValue(ctx, key, value) {
-    try {
-      const stateDb = getStateDbPath(ctx)
-      // Escape single quotes in value for SQL
-      const escaped = String(value).replace(/'/g, "''")
-      const sql =
-        "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('" +
-        key +
-        "', '" +
-        escaped +
-        "');"
-      ctx.host.sqlite.exec(stateDb, sql)
-      return true
-    } catch (e) {
-      ctx.host.log.warn("sqlite write failed for " + key + ": " + String(e))
-      return false
-    }
-  }
-
-  function getTokenExpiration(ctx, token) {
-    const payload = ctx.jwt.decodePayload(token)
-    if (!payload || typeof payload.exp !== "number") return null
-    return payload.exp * 1000 // Convert to milliseconds
+    return vscdb.write(ctx, vscdb.statePath(ctx, "Cursor"), key, value)
   }
 
   function needsRefresh(ctx, accessToken, nowMs) {
-    if (!accessToken) return true
-    const expiresAt = getTokenExpiration(ctx, accessToken)
-    return ctx.util.needsRefreshByExpiry({
-      nowMs,
-      expiresAtMs: expiresAt,
-      bufferMs: REFRESH_BUFFER_MS,
-    })
+    return oauth.needsRefresh(ctx, accessToken, { nowMs, bufferMs: REFRESH_BUFFER_MS })
   }
 
   function refreshToken(ctx, refreshTokenValue) {
@@ -143,18 +93,8 @@
     }
   }
 
-  function connectPost(ctx, url, token) {
-    return ctx.util.request({
-      method: "POST",
-      url: url,
-      headers: {
-        Authorization: "Bearer " + token,
-        "Content-Type": "application/json",
-        "Connect-Protocol-Version": "1",
-      },
-      bodyText: "{}",
-      timeoutMs: 10000,
-    })
+  function connectPost(ctx, url, token, cache) {
+    return connect.post(ctx, url, { token, cache })
   }
 
   function buildSessionToken(ctx, accessToken) {
@@ -314,7 +254,7 @@
     // Fetch plan info early (needed for Enterprise detection)
     let planName = ""
     try {
-      const planResp = connectPost(ctx, PLAN_URL, accessToken)
+      const planResp = connectPost(ctx, PLAN_URL, accessToken, PLAN_CACHE)
       if (planResp.status >= 200 && planResp.status < 300) {
         const plan = ctx.util.tryParseJson(planResp.bodyText)
         if (plan && plan.planInfo && plan.planInfo.planName) {
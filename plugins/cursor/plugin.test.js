@@ -431,11 +431,11 @@ describe("cursor plugin", () => {
       .replace(/=+$/g, "")
     const accessToken = `a.${expiredPayload}.c`
 
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (String(params).includes("cursorAuth/accessToken")) {
         return JSON.stringify([{ value: accessToken }])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (String(params).includes("cursorAuth/refreshToken")) {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -460,15 +460,18 @@ describe("cursor plugin", () => {
     const result = plugin.probe(ctx)
     expect(result.lines.find((line) => line.label === "Plan usage")).toBeTruthy()
     expect(ctx.host.sqlite.exec).toHaveBeenCalled()
+    const [, execSql, execParams] = ctx.host.sqlite.exec.mock.calls[0]
+    expect(execSql).not.toContain(newToken)
+    expect(execParams).toEqual(["cursorAuth/accessToken", newToken])
   })
 
   it("throws session expired when refresh requires logout and no access token exists", async () => {
     const ctx = makeCtx()
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (String(params).includes("cursorAuth/accessToken")) {
         return JSON.stringify([])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (String(params).includes("cursorAuth/refreshToken")) {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -492,11 +495,11 @@ describe("cursor plugin", () => {
       .replace(/=+$/g, "")
     const accessToken = `a.${payload}.c`
 
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (String(params).includes("cursorAuth/accessToken")) {
         return JSON.stringify([{ value: accessToken }])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (String(params).includes("cursorAuth/refreshToken")) {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -6,6 +6,8 @@
   const PROJECTS_URL = "https://cloudresourcemanager.googleapis.com/v1/projects"
   const TOKEN_URL = "https://oauth2.googleapis.com/token"
   const REFRESH_BUFFER_MS = 5 * 60 * 1000
+  // Tier and project rarely change; let the host cache them between refreshes.
+  const METADATA_CACHE = { ttlMs: 60 * 60 * 1000 }
   const IDE_METADATA = { ideType: "GEMINI_CLI", pluginType: "GEMINI" }
   const OAUTH2_JS_RELATIVE_PATHS = "node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|lib/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|libexec/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js".split("|")
   const OAUTH2_JS_CANDIDATES = "~/.bun/install/global/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/.bun/install/global/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/.npm-global/lib/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/.npm-global/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/AppData/Roaming/npm/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/AppData/Roaming/npm/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|/opt/homebrew/opt/gemini-cli/libexec/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|/usr/local/opt/gemini-cli/libexec/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|~/.linuxbrew/opt/gemini-cli/libexec/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js|/home/linuxbrew/.linuxbrew/opt/gemini-cli/libexec/lib/node_modules/@google/gemini-cli/node_modules/@google/gemini-cli-core/dist/src/code_assist/oauth2.js".split("|")
@@ -62,7 +64,10 @@
   }
   function saveOauthCreds(ctx, creds) {
     try {
-      ctx.host.fs.writeText(OAUTH_CREDS_PATH, JSON.stringify(creds, null, 2))
+      ctx.host.fs.writeText(OAUTH_CREDS_PATH, JSON.stringify(creds, null, 2), {
+        lock: true,
+        backup: true,
+      })
     } catch (e) {
       ctx.host.log.warn("failed to write oauth_creds.json: " + String(e))
     }
@@ -172,8 +177,8 @@
     saveOauthCreds(ctx, creds)
     return creds.access_token
   }
-  function postJson(ctx, url, accessToken, body) {
-    return ctx.util.request({
+  function postJson(ctx, url, accessToken, body, cache) {
+    const req = {
       method: "POST",
       url,
       headers: {
@@ -183,7 +188,9 @@
       },
       bodyText: JSON.stringify(body || {}),
       timeoutMs: 10000,
-    })
+    }
+    if (cache) req.cache = cache
+    return ctx.util.request(req)
   }
   function readFirstStringDeep(obj, keys) {
     if (!obj || typeof obj !== "object") return null
@@ -225,6 +232,7 @@
         url: PROJECTS_URL,
         headers: { Authorization: "Bearer " + accessToken, Accept: "application/json" },
         timeoutMs: 10000,
+        cache: METADATA_CACHE,
       })
     } catch (e) {
       ctx.host.log.warn("project discovery failed: " + String(e))
@@ -328,7 +336,7 @@
     let currentToken = accessToken
     const resp = ctx.util.retryOnceOnAuth({
       request: function (token) {
-        return postJson(ctx, LOAD_CODE_ASSIST_URL, token || currentToken, { metadata: IDE_METADATA })
+        return postJson(ctx, LOAD_CODE_ASSIST_URL, token || currentToken, { metadata: IDE_METADATA }, METADATA_CACHE)
       },
       refresh: function () {
         const refreshed = refreshToken(ctx, creds)
@@ -1,9 +1,5 @@
 (function () {
-  const CRED_PATH = "~/.kimi/credentials/kimi-code.json"
   const USAGE_URL = "https://api.kimi.com/coding/v1/usages"
-  const REFRESH_URL = "https://auth.kimi.com/api/oauth/token"
-  const CLIENT_ID = "17e5f671-d194-4dfb-9706-5516cb48c098"
-  const REFRESH_BUFFER_SEC = 5 * 60
 
   function readNumber(value) {
     const n = Number(value)
@@ -34,99 +30,30 @@
     return label || null
   }
 
-  function loadCredentials(ctx) {
-    if (!ctx.host.fs.exists(CRED_PATH)) {
-      ctx.host.log.warn("credentials file not found: " + CRED_PATH)
-      return null
-    }
-
-    try {
-      const text = ctx.host.fs.readText(CRED_PATH)
-      const parsed = ctx.util.tryParseJson(text)
-      if (!parsed || typeof parsed !== "object") {
-        ctx.host.log.warn("credentials file is not valid json")
-        return null
-      }
-      if (!parsed.access_token && !parsed.refresh_token) {
-        ctx.host.log.warn("credentials missing access_token and refresh_token")
-        return null
-      }
-      return parsed
-    } catch (e) {
-      ctx.host.log.warn("credentials read failed: " + String(e))
-      return null
-    }
+  function isRejected(e) {
+    return String(e).indexOf("oauth refresh rejected") !== -1
   }
 
-  function saveCredentials(ctx, creds) {
+  // The host refreshes near expiry and writes rotated tokens back to the
+  // credentials file declared under `oauth` in plugin.json.
+  function getAccessToken(ctx) {
     try {
-      ctx.host.fs.writeText(CRED_PATH, JSON.stringify(creds))
+      return ctx.host.oauth.accessToken()
     } catch (e) {
-      ctx.host.log.warn("failed to persist credentials: " + String(e))
-    }
-  }
-
-  function needsRefresh(creds, nowSec) {
-    if (!creds.access_token) return true
-    const expiresAt = readNumber(creds.expires_at)
-    if (expiresAt === null) return true
-    return nowSec + REFRESH_BUFFER_SEC >= expiresAt
-  }
-
-  function refreshToken(ctx, creds) {
-    if (!creds.refresh_token) {
-      ctx.host.log.warn("refresh skipped: no refresh token")
+      if (isRejected(e)) throw "Session expired. Run `kimi login` to authenticate."
+      ctx.host.log.warn("credentials unavailable: " + String(e))
       return null
     }
+  }
 
-    ctx.host.log.info("attempting token refresh")
-    let resp
+  function refreshToken(ctx, rejectedToken) {
     try {
-      resp = ctx.util.request({
-        method: "POST",
-        url: REFRESH_URL,
-        headers: {
-          "Content-Type": "application/x-www-form-urlencoded",
-          Accept: "application/json",
-        },
-        bodyText:
-          "client_id=" +
-          encodeURIComponent(CLIENT_ID) +
-          "&grant_type=refresh_token" +
-          "&refresh_token=" +
-          encodeURIComponent(creds.refresh_token),
-        timeoutMs: 15000,
-      })
+      return ctx.host.oauth.refresh(rejectedToken)
     } catch (e) {
-      ctx.host.log.error("refresh exception: " + String(e))
+      if (isRejected(e)) throw "Session expired. Run `kimi login` to authenticate."
+      ctx.host.log.warn("refresh failed: " + String(e))
       return null
     }
-
-    if (ctx.util.isAuthStatus(resp.status)) {
-      throw "Session expired. Run `kimi login` to authenticate."
-    }
-
-    if (resp.status < 200 || resp.status >= 300) {
-      ctx.host.log.warn("refresh returned unexpected status: " + resp.status)
-      return null
-    }
-
-    const body = ctx.util.tryParseJson(resp.bodyText)
-    if (!body || !body.access_token) {
-      ctx.host.log.warn("refresh response missing access_token")
-      return null
-    }
-
-    creds.access_token = body.access_token
-    if (body.refresh_token) creds.refresh_token = body.refresh_token
-    if (typeof body.expires_in === "number") {
-      creds.expires_at = Date.now() / 1000 + body.expires_in
-    }
-    if (typeof body.scope === "string") creds.scope = body.scope
-    if (typeof body.token_type === "string") creds.token_type = body.token_type
-
-    saveCredentials(ctx, creds)
-    return creds.access_token
   }
 
   function fetchUsage(ctx, accessToken) {
@@ -240,23 +167,11 @@
   }
 
   function probe(ctx) {
-    const creds = loadCredentials(ctx)
-    if (!creds) {
+    let accessToken = getAccessToken(ctx)
+    if (!accessToken) {
       throw "Not logged in. Run `kimi login` to authenticate."
     }
 
-    const nowSec = Date.now() / 1000
-    let accessToken = creds.access_token || ""
-
-    if (needsRefresh(creds, nowSec)) {
-      const refreshed = refreshToken(ctx, creds)
-      if (refreshed) {
-        accessToken = refreshed
-      } else if (!accessToken) {
-        throw "Not logged in. Run `kimi login` to authenticate."
-      }
-    }
-
     let didRefresh = false
     let resp
     try {
@@ -266,7 +181,7 @@
         },
         refresh: function () {
           didRefresh = true
-          const refreshed = refreshToken(ctx, creds)
+          const refreshed = refreshToken(ctx, accessToken)
           if (refreshed) accessToken = refreshed
           return refreshed
         },
@@ -1,7 +1,43 @@
-import { vi } from "vitest"
+import { createHash, createHmac, randomBytes, randomUUID } from "node:crypto"
+import { existsSync, readFileSync } from "node:fs"
+import { dirname, join } from "node:path"
+import { expect, vi } from "vitest"
+
+// The app injects `require` for `openusage:<name>` stdlib modules; plugin
+// tests load the major version pinned in the `stdlib` field of the
+// plugin.json next to the running test file, from the Rust sources.
+const STDLIB_DIR = new URL("../src-tauri/src/plugin_engine/stdlib/", import.meta.url)
+const stdlibCache = new Map()
+const pinnedMajor = (name) => {
+  const manifestPath = join(dirname(expect.getState().testPath), "plugin.json")
+  const pins = JSON.parse(readFileSync(manifestPath, "utf8")).stdlib || {}
+  if (!Object.hasOwn(pins, name)) {
+    throw new Error('require("openusage:' + name + '"): add "' + name + '" to "stdlib" in plugin.json')
+  }
+  return pins[name]
+}
+globalThis.require = (specifier) => {
+  const name = String(specifier).replace(/^openusage:/, "")
+  if (name === specifier) throw new Error("plugin tests only support openusage: modules")
+  const major = pinnedMajor(name)
+  const key = name + "@" + major
+  if (!stdlibCache.has(key)) {
+    // Only plain names, so a specifier can't reach outside STDLIB_DIR.
+    const file = /^[a-z0-9_-]+$/i.test(name) && new URL(name + ".v" + major + ".js", STDLIB_DIR)
+    if (!file || !existsSync(file)) {
+      throw new Error("unknown stdlib module " + specifier + " v" + major)
+    }
+    const source = readFileSync(file, "utf8")
+    const module = { exports: {} }
+    new Function("module", "exports", "require", source)(module, module.exports, globalThis.require)
+    stdlibCache.set(key, module.exports)
+  }
+  return stdlibCache.get(key)
+}
 
 export const makeCtx = () => {
   const files = new Map()
+  const stored = new Map()
 
   const ctx = {
     nowIso: "2026-02-02T00:00:00.000Z",
@@ -11,19 +47,45 @@ export const makeCtx = () => {
       appDataDir: "/tmp/openusage-test",
       pluginDataDir: "/tmp/openusage-test/plugin",
     },
+    config: {},
     host: {
       fs: {
         exists: (path) => files.has(path),
         readText: (path) => files.get(path),
         writeText: vi.fn((path, text) => files.set(path, text)),
+        listDir: vi.fn(() => []),
+        glob: vi.fn(() => []),
+        stat: vi.fn((path) => {
+          if (!files.has(path)) throw new Error("ENOENT: " + path)
+          return { size: files.get(path).length, mtimeMs: 0, mode: 0o600, isDir: false }
+        }),
+        tailJsonl: vi.fn(() => ({ records: [], skipped: 0, more: false })),
+        readBytes: vi.fn((path) => {
+          if (!files.has(path)) throw new Error("ENOENT: " + path)
+          return Buffer.from(files.get(path)).toString("base64")
+        }),
       },
       env: {
         get: vi.fn(() => null),
       },
+      store: {
+        get: vi.fn((key) => (stored.has(key) ? JSON.parse(stored.get(key)) : null)),
+        set: vi.fn((key, value) => {
+          stored.set(key, JSON.stringify(value))
+        }),
+        delete: vi.fn((key) => stored.delete(key)),
+        list: vi.fn((prefix = "") => [...stored.keys()].filter((key) => key.startsWith(prefix)).sort()),
+      },
+      oauth: {
+        accessToken: vi.fn(() => null),
+        refresh: vi.fn(() => null),
+        credentials: vi.fn(() => null),
+      },
       keychain: {
         readGenericPassword: vi.fn(),
         writeGenericPassword: vi.fn(),
         deleteGenericPassword: vi.fn(),
+        listGenericPasswords: vi.fn(() => []),
       },
       sqlite: {
         query: vi.fn(() => "[]"),
@@ -34,7 +96,9 @@ export const makeCtx = () => {
       },
       ls: {
         discover: vi.fn(() => null),
+        discoverAll: vi.fn(() => []),
       },
+      exec: vi.fn(() => ({ stdout: "", stderr: "", exitCode: 1, truncated: false })),
       log: {
         trace: vi.fn(),
         debug: vi.fn(),
@@ -93,46 +157,31 @@ export const makeCtx = () => {
     },
   }
 
-  const b64chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+  const encodeDigest = (hash, encoding = "hex") => hash.digest(encoding)
+  ctx.crypto = {
+    sha256: vi.fn((data, encoding) => encodeDigest(createHash("sha256").update(data, "utf8"), encoding)),
+    sha1: vi.fn((data, encoding) => encodeDigest(createHash("sha1").update(data, "utf8"), encoding)),
+    hmac: vi.fn((algorithm, key, data, encoding) =>
+      encodeDigest(createHmac(algorithm, key).update(data, "utf8"), encoding)
+    ),
+    randomBytes: vi.fn((len, encoding = "hex") => randomBytes(len).toString(encoding)),
+    uuid: vi.fn(() => randomUUID()),
+    pkce: vi.fn(() => {
+      const verifier = randomBytes(32).toString("base64url")
+      const challenge = createHash("sha256").update(verifier).digest("base64url")
+      return { verifier, challenge, method: "S256" }
+    }),
+  }
+
   ctx.base64 = {
-    decode: (str) => {
-      str = str.replace(/-/g, "+").replace(/_/g, "/")
-      while (str.length % 4) str += "="
-      str = str.replace(/=+$/, "")
-      let result = ""
-      const len = str.length
-      let i = 0
-      while (i < len) {
-        const remaining = len - i
-        const a = b64chars.indexOf(str.charAt(i++))
-        const b = b64chars.indexOf(str.charAt(i++))
-        const c = remaining > 2 ? b64chars.indexOf(str.charAt(i++)) : 0
-        const d = remaining > 3 ? b64chars.indexOf(str.charAt(i++)) : 0
-        const n = (a << 18) | (b << 12) | (c << 6) | d
-        result += String.fromCharCode((n >> 16) & 0xff)
-        if (remaining > 2) result += String.fromCharCode((n >> 8) & 0xff)
-        if (remaining > 3) result += String.fromCharCode(n & 0xff)
-      }
-      return result
-    },
-    encode: (str) => {
-      let result = ""
-      const len = str.length
-      let i = 0
-      while (i < len) {
-        const chunkStart = i
-        const a = str.charCodeAt(i++)
-        const b = i < len ? str.charCodeAt(i++) : 0
-        const c = i < len ? str.charCodeAt(i++) : 0
-        const bytesInChunk = i - chunkStart
-        const n = (a << 16) | (b << 8) | c
-        result += b64chars.charAt((n >> 18) & 63)
-        result += b64chars.charAt((n >> 12) & 63)
-        result += bytesInChunk < 2 ? "=" : b64chars.charAt((n >> 6) & 63)
-        result += bytesInChunk < 3 ? "=" : b64chars.charAt(n & 63)
-      }
-      return result
-    },
+    encode: (str) => Buffer.from(str, "utf8").toString("base64"),
+    encodeUrl: (str) => Buffer.from(str, "utf8").toString("base64url"),
+    decode: (str) => Buffer.from(str.replace(/-/g, "+").replace(/_/g, "/"), "base64").toString("utf8"),
+  }
+
+  ctx.hex = {
+    encode: (str) => Buffer.from(str, "utf8").toString("hex"),
+    decode: (str) => Buffer.from(str, "hex").toString("utf8"),
   }
 
   ctx.jwt = {
@@ -148,6 +197,12 @@ export const makeCtx = () => {
     },
   }
 
+  ctx.pricing = {
+    version: "test",
+    cost: vi.fn(() => null),
+    lookup: vi.fn(() => null),
+  }
+
   ctx.util = {
     tryParseJson: (text) => {
       if (text === null || text === undefined) return null
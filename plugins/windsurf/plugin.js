@@ -1,4 +1,6 @@
 (function () {
+  var vscdb = require("openusage:vscdb")
+
   var LS_SERVICE = "exa.language_server_pb.LanguageServerService"
 
   // Windsurf variants — tried in order (Windsurf first, then Windsurf Next).
@@ -18,31 +20,22 @@
 
   // --- LS discovery ---
 
+  // Returns every running LS instance for the variant, newest first.
   function discoverLs(ctx, variant) {
-    return ctx.host.ls.discover({
+    var opts = {
       processName: "language_server_macos",
       markers: [variant.marker],
       csrfFlag: "--csrf_token",
       portFlag: "--extension_server_port",
       extraFlags: ["--windsurf_version"],
-    })
+    }
+    return ctx.host.ls.discoverAll(opts)
   }
 
   function loadApiKey(ctx, variant) {
-    try {
-      var rows = ctx.host.sqlite.query(
-        variant.stateDb,
-        "SELECT value FROM ItemTable WHERE key = 'windsurfAuthStatus' LIMIT 1"
-      )
-      var parsed = ctx.util.tryParseJson(rows)
-      if (!parsed || !parsed.length || !parsed[0].value) return null
-      var auth = ctx.util.tryParseJson(parsed[0].value)
-      if (!auth || !auth.apiKey) return null
-      return auth.apiKey
-    } catch (e) {
-      ctx.host.log.warn("failed to read API key from " + variant.marker + ": " + String(e))
-      return null
-    }
+    var auth = vscdb.readJson(ctx, variant.stateDb, "windsurfAuthStatus")
+    if (!auth || !auth.apiKey) return null
+    return auth.apiKey
   }
 
   function probePort(ctx, scheme, port, csrf, ideName) {
@@ -83,6 +76,16 @@
     return null
   }
 
+  // With several windows open, use the newest instance that answers.
+  function findWorkingInstance(ctx, instances, ideName) {
+    for (var i = 0; i < instances.length; i++) {
+      var found = findWorkingPort(ctx, instances[i], ideName)
+      if (found) return { discovery: instances[i], port: found.port, scheme: found.scheme }
+      ctx.host.log.info("LS instance pid " + instances[i].pid + " not reachable")
+    }
+    return null
+  }
+
   function callLs(ctx, port, scheme, csrf, method, body) {
     var resp = ctx.host.http.request({
       method: "POST",
@@ -123,11 +126,9 @@
   // --- LS probe for a specific variant ---
 
   function probeVariant(ctx, variant) {
-    var discovery = discoverLs(ctx, variant)
-    if (!discovery) return null
-
-    var found = findWorkingPort(ctx, discovery, variant.ideName)
+    var found = findWorkingInstance(ctx, discoverLs(ctx, variant), variant.ideName)
     if (!found) return null
+    var discovery = found.discovery
 
     var apiKey = loadApiKey(ctx, variant)
     if (!apiKey) {
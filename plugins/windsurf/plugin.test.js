@@ -43,15 +43,15 @@ function makeLsResponse(overrides) {
 
 function setupLsMock(ctx, discovery, apiKey, responseBody, opts) {
   var stateDb = (opts && opts.stateDb) || "Windsurf"
-  ctx.host.ls.discover.mockImplementation((discoverOpts) => {
+  ctx.host.ls.discoverAll.mockImplementation((discoverOpts) => {
     // Match the right variant by marker
     var marker = discoverOpts.markers[0]
-    if (marker === "windsurf" && stateDb === "Windsurf") return discovery
-    if (marker === "windsurf-next" && stateDb === "Windsurf - Next") return discovery
-    return null
+    if (marker === "windsurf" && stateDb === "Windsurf") return discovery ? [discovery] : []
+    if (marker === "windsurf-next" && stateDb === "Windsurf - Next") return discovery ? [discovery] : []
+    return []
   })
-  ctx.host.sqlite.query.mockImplementation((db, sql) => {
-    if (String(sql).includes("windsurfAuthStatus") && String(db).includes(stateDb)) {
+  ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+    if (params && params[0] === "windsurfAuthStatus" && String(db).includes(stateDb)) {
       return makeAuthStatus(apiKey)
     }
     return "[]"
@@ -74,7 +74,7 @@ describe("windsurf plugin", () => {
 
   it("throws when LS not found and no cache", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(null)
+    ctx.host.ls.discoverAll.mockReturnValue([])
     ctx.host.sqlite.query.mockReturnValue("[]")
     const plugin = await loadPlugin()
     expect(() => plugin.probe(ctx)).toThrow("Start Windsurf and try again.")
@@ -146,7 +146,7 @@ describe("windsurf plugin", () => {
 
   it("returns null from LS when no API key", async () => {
     const ctx = makeCtx()
-    ctx.host.ls.discover.mockReturnValue(makeDiscovery())
+    ctx.host.ls.discoverAll.mockReturnValue([makeDiscovery()])
     ctx.host.sqlite.query.mockReturnValue("[]")
     ctx.host.http.request.mockImplementation((reqOpts) => {
       if (String(reqOpts.url).includes("GetUnleashData")) {
@@ -217,8 +217,8 @@ describe("windsurf plugin", () => {
     setupLsMock(ctx, makeDiscovery(), "sk-ws-01-next", makeLsResponse(), { stateDb: "Windsurf - Next" })
 
     let queriedDb = null
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("windsurfAuthStatus")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (params && params[0] === "windsurfAuthStatus") {
         queriedDb = db
         return makeAuthStatus("sk-ws-01-next")
       }
@@ -231,14 +231,39 @@ describe("windsurf plugin", () => {
     expect(queriedDb).toContain("Windsurf - Next")
   })
 
+  it("uses the newest reachable instance from discoverAll", async () => {
+    const ctx = makeCtx()
+    ctx.host.ls.discoverAll.mockImplementation((discoverOpts) => {
+      if (discoverOpts.markers[0] !== "windsurf") return []
+      return [
+        makeDiscovery({ pid: 2, csrf: "stale-csrf", ports: [43001] }),
+        makeDiscovery({ pid: 1, csrf: "live-csrf", ports: [43002] }),
+      ]
+    })
+    ctx.host.sqlite.query.mockReturnValue(makeAuthStatus("sk-ws-01-multi"))
+    const csrfs = []
+    ctx.host.http.request.mockImplementation((reqOpts) => {
+      if (String(reqOpts.url).includes(":43001/")) throw new Error("connection refused")
+      if (String(reqOpts.url).includes("GetUnleashData")) return { status: 200, bodyText: "{}" }
+      csrfs.push(reqOpts.headers["x-codeium-csrf-token"])
+      return { status: 200, bodyText: JSON.stringify(makeLsResponse()) }
+    })
+
+    const plugin = await loadPlugin()
+    const result = plugin.probe(ctx)
+
+    expect(result.plan).toBe("Teams")
+    expect(csrfs).toEqual(["live-csrf"])
+  })
+
   it("prefers Windsurf over Windsurf Next when both available", async () => {
     const ctx = makeCtx()
     // Both variants return valid discoveries
-    ctx.host.ls.discover.mockImplementation((discoverOpts) => {
-      return makeDiscovery()
+    ctx.host.ls.discoverAll.mockImplementation((discoverOpts) => {
+      return [makeDiscovery()]
     })
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("windsurfAuthStatus")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (params && params[0] === "windsurfAuthStatus") {
         return makeAuthStatus("sk-ws-01-both")
       }
       return "[]"
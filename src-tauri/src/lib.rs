@@ -40,6 +40,12 @@ pub struct PluginMeta {
     /// Ordered list of primary metric candidates (sorted by primaryOrder).
     /// Frontend picks the first one that exists in runtime data.
     pub primary_candidates: Vec<String>,
+    /// In-app login flow (`"pkce"` or `"deviceCode"`), if the plugin declares one.
+    pub login: Option<String>,
+    /// Settings the plugin declares; the UI renders a form from these.
+    pub settings: Vec<plugin_engine::plugin_settings::SettingField>,
+    /// Set on added accounts: the id of the plugin they copy.
+    pub instance_of: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -71,6 +77,13 @@ pub struct ProbeBatchComplete {
     pub batch_id: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginLoginPrompt {
+    pub plugin_id: String,
+    pub prompt: plugin_engine::oauth_login::LoginPrompt,
+}
+
 #[tauri::command]
 fn init_panel(app_handle: tauri::AppHandle) {
     panel::init(&app_handle).expect("Failed to initialize panel");
@@ -124,7 +137,12 @@ async fn start_probe_batch(
                 })
                 .collect()
         }
-        None => plugins,
+        // Native providers scan local files, so they only run once the user
+        // has enabled them and the UI asks for them by id.
+        None => plugins
+            .into_iter()
+            .filter(|plugin| plugin.native.is_none())
+            .collect(),
     };
 
     let response_plugin_ids: Vec<String> = selected_plugins
@@ -243,39 +261,216 @@ fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
     };
     log::debug!("list_plugins: {} plugins", plugins.len());
 
-    plugins
-        .into_iter()
-        .map(|plugin| {
-            // Extract primary candidates: progress lines with primary_order, sorted by order
-            let mut candidates: Vec<_> = plugin
-                .manifest
-                .lines
-                .iter()
-                .filter(|line| line.line_type == "progress" && line.primary_order.is_some())
-                .collect();
-            candidates.sort_by_key(|line| line.primary_order.unwrap());
-            let primary_candidates: Vec<String> =
-                candidates.iter().map(|line| line.label.clone()).collect();
-
-            PluginMeta {
-                id: plugin.manifest.id,
-                name: plugin.manifest.name,
-                icon_url: plugin.icon_data_url,
-                brand_color: plugin.manifest.brand_color,
-                lines: plugin
-                    .manifest
-                    .lines
-                    .iter()
-                    .map(|line| ManifestLineDto {
-                        line_type: line.line_type.clone(),
-                        label: line.label.clone(),
-                        scope: line.scope.clone(),
-                    })
-                    .collect(),
-                primary_candidates,
+    plugins.into_iter().map(plugin_meta).collect()
+}
+
+fn plugin_meta(plugin: plugin_engine::manifest::LoadedPlugin) -> PluginMeta {
+    // Extract primary candidates: progress lines with primary_order, sorted by order
+    let mut candidates: Vec<_> = plugin
+        .manifest
+        .lines
+        .iter()
+        .filter(|line| line.line_type == "progress" && line.primary_order.is_some())
+        .collect();
+    candidates.sort_by_key(|line| line.primary_order.unwrap());
+    let primary_candidates: Vec<String> =
+        candidates.iter().map(|line| line.label.clone()).collect();
+
+    PluginMeta {
+        id: plugin.manifest.id,
+        name: plugin.manifest.name,
+        icon_url: plugin.icon_data_url,
+        brand_color: plugin.manifest.brand_color,
+        lines: plugin
+            .manifest
+            .lines
+            .iter()
+            .map(|line| ManifestLineDto {
+                line_type: line.line_type.clone(),
+                label: line.label.clone(),
+                scope: line.scope.clone(),
+            })
+            .collect(),
+        primary_candidates,
+        login: plugin
+            .manifest
+            .oauth
+            .as_ref()
+            .and_then(|oauth| oauth.login.as_ref())
+            .map(|login| login.kind().to_string()),
+        settings: plugin.manifest.settings,
+        instance_of: plugin.instance_of,
+    }
+}
+
+/// Debug view of a plugin's `host.store` entries (values are JSON text).
+#[tauri::command]
+fn get_plugin_store(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+) -> Result<Vec<plugin_engine::kv_store::StoreEntry>, String> {
+    let app_data_dir = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        locked.app_data_dir.clone()
+    };
+    plugin_engine::kv_store::PluginStore::new(&app_data_dir, &plugin_id).entries()
+}
+
+fn plugin_settings_for(
+    state: &tauri::State<'_, Mutex<AppState>>,
+    plugin_id: &str,
+) -> Result<
+    (
+        plugin_engine::plugin_settings::PluginSettings,
+        Vec<plugin_engine::plugin_settings::SettingField>,
+    ),
+    String,
+> {
+    let locked = state.lock().map_err(|e| e.to_string())?;
+    let plugin = locked
+        .plugins
+        .iter()
+        .find(|plugin| plugin.manifest.id == plugin_id)
+        .ok_or_else(|| format!("unknown plugin: {}", plugin_id))?;
+    let settings = plugin_engine::plugin_settings::PluginSettings::new(
+        &locked.app_data_dir,
+        plugin_id,
+        plugin_engine::credential_store::detect(&locked.app_data_dir),
+    );
+    Ok((settings, plugin.manifest.settings.clone()))
+}
+
+/// Stored settings for the form; secrets are only reported as set.
+#[tauri::command]
+fn get_plugin_settings(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+) -> Result<plugin_engine::plugin_settings::SettingsView, String> {
+    let (settings, fields) = plugin_settings_for(&state, &plugin_id)?;
+    settings.view(&fields)
+}
+
+/// Saves the given settings; `null` or `""` clears a value.
+#[tauri::command]
+fn set_plugin_settings(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+    values: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    let (settings, fields) = plugin_settings_for(&state, &plugin_id)?;
+    settings.update(&fields, &values)
+}
+
+/// Adds another account of a plugin. The new instance starts without
+/// settings; the UI opens its detail page so the user can fill them in.
+#[tauri::command]
+fn create_plugin_instance(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+    name: String,
+) -> Result<PluginMeta, String> {
+    let mut locked = state.lock().map_err(|e| e.to_string())?;
+    let instance =
+        plugin_engine::instances::create(&locked.app_data_dir, &locked.plugins, &plugin_id, &name)?;
+    let plugins = std::mem::take(&mut locked.plugins);
+    locked.plugins = plugin_engine::instances::expand(plugins, std::slice::from_ref(&instance));
+    locked
+        .plugins
+        .iter()
+        .find(|plugin| plugin.manifest.id == instance.id)
+        .cloned()
+        .map(plugin_meta)
+        .ok_or_else(|| format!("failed to add {}", instance.id))
+}
+
+/// Removes an added account and everything it stored.
+#[tauri::command]
+fn remove_plugin_instance(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+) -> Result<(), String> {
+    let mut locked = state.lock().map_err(|e| e.to_string())?;
+    let index = locked
+        .plugins
+        .iter()
+        .position(|plugin| plugin.manifest.id == plugin_id)
+        .ok_or_else(|| format!("unknown plugin: {}", plugin_id))?;
+    let store = plugin_engine::credential_store::detect(&locked.app_data_dir);
+    plugin_engine::instances::remove(&locked.app_data_dir, &locked.plugins[index], store)?;
+    locked.plugins.remove(index);
+    Ok(())
+}
+
+/// Runs a plugin's declared OAuth login. Resolves once tokens are stored;
+/// `plugin-login:prompt` tells the UI what the user has to do meanwhile.
+#[tauri::command]
+async fn start_plugin_login(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+) -> Result<(), String> {
+    let (plugin, app_data_dir) = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        let plugin = locked
+            .plugins
+            .iter()
+            .find(|plugin| plugin.manifest.id == plugin_id)
+            .cloned()
+            .ok_or_else(|| format!("unknown plugin: {}", plugin_id))?;
+        (plugin, locked.app_data_dir.clone())
+    };
+    let config = plugin
+        .manifest
+        .oauth
+        .filter(|oauth| oauth.login.is_some())
+        .ok_or_else(|| format!("plugin {} has no login flow", plugin_id))?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        use plugin_engine::oauth_login::{self, LoginOptions, LoginPrompt};
+        use tauri_plugin_opener::OpenerExt;
+
+        let store = plugin_engine::credential_store::detect(&app_data_dir);
+        let http = plugin_engine::host_api::HttpHost::new(
+            &plugin_id,
+            &app_data_dir.join("plugins_data").join(&plugin_id),
+            None,
+            Vec::new(),
+        );
+        let transport = plugin_engine::oauth::http_transport(std::sync::Arc::new(http));
+        let prompt = |prompt: LoginPrompt| {
+            let url = match &prompt {
+                LoginPrompt::OpenBrowser { url } => url.clone(),
+                LoginPrompt::DeviceCode {
+                    verification_uri,
+                    verification_uri_complete,
+                    ..
+                } => verification_uri_complete
+                    .clone()
+                    .unwrap_or_else(|| verification_uri.clone()),
+            };
+            if let Err(err) = app_handle.opener().open_url(url, None::<&str>) {
+                log::warn!("[plugin:{}] failed to open browser: {}", plugin_id, err);
             }
-        })
-        .collect()
+            let _ = app_handle.emit(
+                "plugin-login:prompt",
+                PluginLoginPrompt {
+                    plugin_id: plugin_id.clone(),
+                    prompt,
+                },
+            );
+        };
+        oauth_login::login(
+            &plugin_id,
+            &config,
+            store.as_ref(),
+            &transport,
+            &prompt,
+            &LoginOptions::default(),
+        )
+        .inspect_err(|err| log::warn!("[plugin:{}] {}", plugin_id, err))
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -312,7 +507,13 @@ pub fn run() {
             hide_panel,
             start_probe_batch,
             list_plugins,
-            get_log_path
+            get_log_path,
+            get_plugin_store,
+            get_plugin_settings,
+            set_plugin_settings,
+            create_plugin_instance,
+            remove_plugin_instance,
+            start_plugin_login
         ])
         .setup(|app| {
             #[cfg(target_os = "macos")]
@@ -336,6 +537,17 @@ pub fn run() {
             log::debug!("app_data_dir: {:?}", app_data_dir);
 
             let (_, plugins) = plugin_engine::initialize_plugins(&app_data_dir, &resource_dir);
+            let plugins = plugin_engine::instances::expand(
+                plugins,
+                &plugin_engine::instances::load(&app_data_dir),
+            );
+            let installed: Vec<String> =
+                plugins.iter().map(|plugin| plugin.manifest.id.clone()).collect();
+            match plugin_engine::kv_store::prune_uninstalled(&app_data_dir, &installed) {
+                Ok(0) => {}
+                Ok(removed) => log::info!("removed {} store entries of uninstalled plugins", removed),
+                Err(err) => log::warn!("failed to prune plugin store: {}", err),
+            }
             app.manage(Mutex::new(AppState {
                 plugins,
                 app_data_dir,
@@ -0,0 +1,1157 @@
+//! Credential storage behind `host.keychain`.
+//!
+//! Plugins only ever see the `*GenericPassword` calls keyed by a service
+//! name. Where the secret actually lives depends on the platform:
+//!
+//! - macOS: the login keychain, through the `security` CLI.
+//! - Linux: the freedesktop Secret Service (GNOME Keyring, KWallet, KeePassXC)
+//!   over the session bus, when one answers.
+//! - Anywhere else, or Linux without a Secret Service: an encrypted vault file
+//!   in the app data dir.
+//!
+//! Errors are plain strings; the host API wraps them into JS exceptions.
+
+use base64::Engine;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Why a credential store call failed. Displays as the message plugins see.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CredentialError {
+    /// There is no item for the service; holds the backend's detail.
+    NotFound(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(detail) => write!(f, "keychain item not found: {}", detail),
+            Self::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for CredentialError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<CredentialError> for String {
+    fn from(err: CredentialError) -> Self {
+        err.to_string()
+    }
+}
+
+fn failed(op: &str, err: impl std::fmt::Display) -> CredentialError {
+    CredentialError::Failed(format!("keychain {} failed: {}", op, err))
+}
+
+pub trait CredentialStore: Send + Sync {
+    /// Short backend name, for logs.
+    fn name(&self) -> &'static str;
+    fn read(&self, service: &str) -> Result<String, CredentialError>;
+    fn write(&self, service: &str, value: &str) -> Result<(), CredentialError>;
+    fn delete(&self, service: &str) -> Result<(), CredentialError>;
+    /// Service names starting with `prefix`, sorted.
+    fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError>;
+}
+
+/// The best available store for this machine.
+pub fn detect(app_data_dir: &Path) -> Arc<dyn CredentialStore> {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = app_data_dir;
+        Arc::new(MacKeychain)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        match SecretService::session() {
+            Some(store) => Arc::new(store),
+            None => Arc::new(FileVault::new(app_data_dir)),
+        }
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        Arc::new(FileVault::new(app_data_dir))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn first_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+/// `security` exits with 44 (errSecItemNotFound) when there is no such item.
+#[cfg(target_os = "macos")]
+fn mac_error(op: &str, output: &std::process::Output) -> CredentialError {
+    let detail = first_line(&output.stderr);
+    match output.status.code() {
+        Some(44) => CredentialError::NotFound(detail),
+        _ => failed(op, detail),
+    }
+}
+
+/// macOS login keychain via `/usr/bin/security`.
+#[cfg(target_os = "macos")]
+pub struct MacKeychain;
+
+#[cfg(target_os = "macos")]
+impl CredentialStore for MacKeychain {
+    fn name(&self) -> &'static str {
+        "macos-keychain"
+    }
+
+    fn read(&self, service: &str) -> Result<String, CredentialError> {
+        let output = std::process::Command::new("security")
+            .args(["find-generic-password", "-s", service, "-w"])
+            .output()
+            .map_err(|e| failed("read", e))?;
+
+        if !output.status.success() {
+            return Err(mac_error("read", &output));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn write(&self, service: &str, value: &str) -> Result<(), CredentialError> {
+        let existing = mac_find(service).ok().flatten();
+
+        let mut args = vec!["add-generic-password", "-s", service];
+        // Keep the account of an existing entry so `-U` updates it in place
+        // instead of adding a second item for the same service.
+        if let Some(account) = existing.as_ref().and_then(|item| item.account.as_deref()) {
+            args.extend(["-a", account]);
+        }
+        // New items and our older ones get our creator code; an existing item
+        // of another app (say, a CLI's credentials) keeps belonging to it.
+        if existing.as_ref().is_none_or(MacItem::is_ours) {
+            args.extend(["-c", MAC_CREATOR]);
+        }
+        args.extend(["-w", value, "-U"]);
+        let output = std::process::Command::new("security")
+            .args(&args)
+            .output()
+            .map_err(|e| failed("write", e))?;
+
+        if !output.status.success() {
+            return Err(failed("write", first_line(&output.stderr)));
+        }
+
+        Ok(())
+    }
+
+    /// Only deletes items OpenUsage created (see `MacItem::is_ours`); another
+    /// app's item under the same service reads as not found.
+    fn delete(&self, service: &str) -> Result<(), CredentialError> {
+        let delete = |extra: &[&str]| {
+            std::process::Command::new("security")
+                .args(["delete-generic-password", "-s", service])
+                .args(extra)
+                .output()
+                .map_err(|e| failed("delete", e))
+        };
+
+        let output = delete(&["-c", MAC_CREATOR])?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let err = mac_error("delete", &output);
+        if !matches!(err, CredentialError::NotFound(_)) {
+            return Err(err);
+        }
+
+        // Items from before creator codes have no account, which is how
+        // they are told apart from other apps' items.
+        match mac_find(service)? {
+            Some(item) if item.creator.is_none() && item.is_ours() => {
+                let output = delete(&[])?;
+                if !output.status.success() {
+                    return Err(mac_error("delete", &output));
+                }
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Only items OpenUsage created are listed (see `MacItem::is_ours`), like
+    /// on the Secret Service; other apps' service names stay invisible.
+    fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError> {
+        // Without -d, dump-keychain prints attributes only and never prompts.
+        let output = std::process::Command::new("security")
+            .args(["dump-keychain"])
+            .output()
+            .map_err(|e| failed("list", e))?;
+
+        if !output.status.success() {
+            return Err(failed("list", first_line(&output.stderr)));
+        }
+
+        Ok(mac_dump_services(&String::from_utf8_lossy(&output.stdout), prefix))
+    }
+}
+
+/// The first generic password for `service`, or `None` if there is none.
+#[cfg(target_os = "macos")]
+fn mac_find(service: &str) -> Result<Option<MacItem>, CredentialError> {
+    let output = std::process::Command::new("security")
+        .args(["find-generic-password", "-s", service])
+        .output()
+        .map_err(|e| failed("read", e))?;
+    if !output.status.success() {
+        return match mac_error("read", &output) {
+            CredentialError::NotFound(_) => Ok(None),
+            err => Err(err),
+        };
+    }
+    Ok(mac_items(&String::from_utf8_lossy(&output.stdout)).into_iter().next())
+}
+
+/// Creator code set on every keychain item we create, so ours can be told
+/// apart from other apps' items.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const MAC_CREATOR: &str = "opnu";
+
+/// The attributes of a keychain item that `security` prints.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+#[derive(Debug, Default, PartialEq)]
+struct MacItem {
+    generic: bool,
+    service: Option<String>,
+    account: Option<String>,
+    creator: Option<String>,
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+impl MacItem {
+    /// Items we create carry `MAC_CREATOR`. Ones written before that have
+    /// neither a creator code nor an account, while CLIs and other apps
+    /// always name an account.
+    fn is_ours(&self) -> bool {
+        match self.creator.as_deref() {
+            Some(creator) => creator == MAC_CREATOR,
+            None => self.account.as_deref().is_none_or(str::is_empty),
+        }
+    }
+}
+
+/// Items in `security find-generic-password` or `dump-keychain` output, where
+/// each item starts with a `keychain:` line followed by its attributes.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn mac_items(output: &str) -> Vec<MacItem> {
+    let mut items: Vec<MacItem> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("keychain: ") {
+            items.push(MacItem::default());
+            continue;
+        }
+        let Some(item) = items.last_mut() else {
+            continue;
+        };
+        if let Some(class) = line.strip_prefix("class: ") {
+            item.generic = class == "\"genp\"";
+        } else if let Some(value) = line.strip_prefix("\"crtr\"<uint32>=") {
+            item.creator = mac_attribute(value);
+        } else if let Some(value) = line.strip_prefix("\"acct\"<blob>=") {
+            item.account = mac_attribute(value);
+        } else if let Some(value) = line.strip_prefix("\"svce\"<blob>=") {
+            item.service = mac_attribute(value);
+        }
+    }
+    items
+}
+
+/// `"text"` or, for blobs with non-printable bytes, `0x<hex>  "<escaped>"`.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn mac_attribute(raw: &str) -> Option<String> {
+    if raw == "<NULL>" {
+        return None;
+    }
+    let text = raw.find('"').map_or(raw, |start| &raw[start..]);
+    let text = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text);
+    Some(text.to_string())
+}
+
+/// Service names of our generic passwords in `security dump-keychain` output.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn mac_dump_services(dump: &str, prefix: &str) -> Vec<String> {
+    let services: std::collections::BTreeSet<String> = mac_items(dump)
+        .into_iter()
+        .filter(|item| item.generic && item.is_ours())
+        .filter_map(|item| item.service)
+        .filter(|service| service.starts_with(prefix))
+        .collect();
+    services.into_iter().collect()
+}
+
+#[cfg(target_os = "linux")]
+pub use secret_service::SecretService;
+
+#[cfg(target_os = "linux")]
+mod secret_service {
+    use super::{CredentialError, CredentialStore, failed};
+    use std::collections::HashMap;
+    use std::sync::OnceLock;
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::proxy::CacheProperties;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+    pub(crate) const DEST: &str = "org.freedesktop.secrets";
+    pub(crate) const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+    pub(crate) const SERVICE_IFACE: &str = "org.freedesktop.Secret.Service";
+    pub(crate) const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+    pub(crate) const COLLECTION_IFACE: &str = "org.freedesktop.Secret.Collection";
+    pub(crate) const ITEM_IFACE: &str = "org.freedesktop.Secret.Item";
+    /// Attribute set on every item we create, so ours can be told apart.
+    pub(crate) const APPLICATION: &str = "openusage";
+
+    /// `(session, parameters, value, content_type)`, the spec's Secret struct.
+    pub(crate) type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+    /// freedesktop Secret Service, looked up by a `service` attribute.
+    ///
+    /// Reads match any item with that attribute, so plugins can use secrets
+    /// other tools stored (gh's token, say). Items written here also carry
+    /// `application=openusage` (see `APPLICATION`), and only those are listed
+    /// or deleted.
+    ///
+    /// Uses the unencrypted "plain" session: the bus is local to the user, and
+    /// libsecret-based tools (`secret-tool`, `gh`) do the same by default.
+    pub struct SecretService {
+        conn: Connection,
+    }
+
+    impl SecretService {
+        pub fn new(conn: Connection) -> Self {
+            Self { conn }
+        }
+
+        /// Store on the user's session bus, if a Secret Service answers there.
+        /// Probed once per process; D-Bus activation can take a while to fail.
+        pub fn session() -> Option<Self> {
+            static SESSION: OnceLock<Option<Connection>> = OnceLock::new();
+            SESSION
+                .get_or_init(|| {
+                    let conn = Connection::session().ok()?;
+                    match SecretService::new(conn.clone()).open_session() {
+                        Ok(_) => Some(conn),
+                        Err(e) => {
+                            log::info!("secret service unavailable: {}", e);
+                            None
+                        }
+                    }
+                })
+                .clone()
+                .map(Self::new)
+        }
+
+        fn proxy(&self, path: &'static str, iface: &'static str) -> Result<Proxy<'_>, String> {
+            Proxy::new(&self.conn, DEST, path, iface).map_err(|e| e.to_string())
+        }
+
+        fn item_proxy(&self, item: &OwnedObjectPath) -> Result<Proxy<'_>, String> {
+            let build = || {
+                zbus::blocking::proxy::Builder::<Proxy<'_>>::new(&self.conn)
+                    .destination(DEST)?
+                    .path(item.clone())?
+                    .interface(ITEM_IFACE)?
+                    .cache_properties(CacheProperties::No)
+                    .build()
+            };
+            build().map_err(|e| e.to_string())
+        }
+
+        fn open_session(&self) -> Result<OwnedObjectPath, String> {
+            let (_, session): (OwnedValue, OwnedObjectPath) = self
+                .proxy(SERVICE_PATH, SERVICE_IFACE)?
+                .call("OpenSession", &("plain", Value::from("")))
+                .map_err(|e| e.to_string())?;
+            Ok(session)
+        }
+
+        /// First item whose `service` attribute matches, unlocking it if needed.
+        /// With `ours`, only items OpenUsage created (see `APPLICATION`).
+        fn find(&self, service: &str, ours: bool) -> Result<Option<OwnedObjectPath>, String> {
+            let proxy = self.proxy(SERVICE_PATH, SERVICE_IFACE)?;
+            let mut attributes = HashMap::from([("service", service)]);
+            if ours {
+                attributes.insert("application", APPLICATION);
+            }
+            let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = proxy
+                .call("SearchItems", &(attributes,))
+                .map_err(|e| e.to_string())?;
+            if let Some(item) = unlocked.into_iter().next() {
+                return Ok(Some(item));
+            }
+            if locked.is_empty() {
+                return Ok(None);
+            }
+
+            let (unlocked, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) = proxy
+                .call("Unlock", &(locked,))
+                .map_err(|e| e.to_string())?;
+            match unlocked.into_iter().next() {
+                Some(item) => Ok(Some(item)),
+                // Unlocking needs user interaction; a background probe can't
+                // answer a prompt, so report it instead of blocking.
+                None if prompt.as_str() != "/" => Err("keychain is locked".to_string()),
+                None => Ok(None),
+            }
+        }
+    }
+
+    impl CredentialStore for SecretService {
+        fn name(&self) -> &'static str {
+            "secret-service"
+        }
+
+        fn read(&self, service: &str) -> Result<String, CredentialError> {
+            let session = self
+                .open_session()
+                .map_err(|e| failed("read", e))?;
+            let item = self
+                .find(service, false)
+                .map_err(|e| failed("read", e))?
+                .ok_or_else(|| CredentialError::NotFound(service.to_string()))?;
+            let secrets: HashMap<OwnedObjectPath, Secret> = self
+                .proxy(SERVICE_PATH, SERVICE_IFACE)
+                .and_then(|proxy| {
+                    proxy
+                        .call("GetSecrets", &(vec![item.clone()], session))
+                        .map_err(|e| e.to_string())
+                })
+                .map_err(|e| failed("read", e))?;
+            let (_, _, value, _) = secrets
+                .get(&item)
+                .ok_or_else(|| CredentialError::NotFound(service.to_string()))?;
+            String::from_utf8(value.clone())
+                .map(|value| value.trim().to_string())
+                .map_err(|_| failed("read", format!("{} is not UTF-8", service)))
+        }
+
+        fn write(&self, service: &str, value: &str) -> Result<(), CredentialError> {
+            let session = self
+                .open_session()
+                .map_err(|e| failed("write", e))?;
+            let attributes = HashMap::from([("service", service), ("application", APPLICATION)]);
+            let properties = HashMap::from([
+                (
+                    "org.freedesktop.Secret.Item.Label",
+                    Value::from(format!("OpenUsage: {}", service)),
+                ),
+                ("org.freedesktop.Secret.Item.Attributes", Value::from(attributes)),
+            ]);
+            let secret: Secret = (
+                session,
+                Vec::new(),
+                value.as_bytes().to_vec(),
+                "text/plain".to_string(),
+            );
+            let (item, prompt): (OwnedObjectPath, OwnedObjectPath) = self
+                .proxy(DEFAULT_COLLECTION, COLLECTION_IFACE)
+                .and_then(|proxy| {
+                    proxy
+                        .call("CreateItem", &(properties, secret, true))
+                        .map_err(|e| e.to_string())
+                })
+                .map_err(|e| failed("write", e))?;
+            if item.as_str() == "/" && prompt.as_str() != "/" {
+                return Err(failed("write", "keychain is locked"));
+            }
+            Ok(())
+        }
+
+        /// Only deletes items OpenUsage created, so a plugin can't remove
+        /// another app's secret that happens to share the service name.
+        fn delete(&self, service: &str) -> Result<(), CredentialError> {
+            let item = self
+                .find(service, true)
+                .map_err(|e| failed("delete", e))?
+                .ok_or_else(|| CredentialError::NotFound(service.to_string()))?;
+            let prompt: OwnedObjectPath = self
+                .item_proxy(&item)
+                .and_then(|proxy| proxy.call("Delete", &()).map_err(|e| e.to_string()))
+                .map_err(|e| failed("delete", e))?;
+            if prompt.as_str() != "/" {
+                return Err(failed("delete", "keychain is locked"));
+            }
+            Ok(())
+        }
+
+        /// Only items OpenUsage created are listed (see `APPLICATION`); other
+        /// apps' secrets stay invisible even when their service names match.
+        fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError> {
+            let list_failed = |e: String| failed("list", e);
+            let attributes = HashMap::from([("application", APPLICATION)]);
+            let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = self
+                .proxy(SERVICE_PATH, SERVICE_IFACE)
+                .and_then(|proxy| {
+                    proxy
+                        .call("SearchItems", &(attributes,))
+                        .map_err(|e| e.to_string())
+                })
+                .map_err(list_failed)?;
+            // Attributes are readable on locked items too, so no unlock here.
+            let mut services = std::collections::BTreeSet::new();
+            for item in unlocked.iter().chain(locked.iter()) {
+                let attributes: HashMap<String, String> = self
+                    .item_proxy(item)
+                    .and_then(|proxy| {
+                        proxy
+                            .get_property("Attributes")
+                            .map_err(|e| e.to_string())
+                    })
+                    .map_err(list_failed)?;
+                if let Some(service) = attributes.get("service")
+                    && service.starts_with(prefix)
+                {
+                    services.insert(service.clone());
+                }
+            }
+            Ok(services.into_iter().collect())
+        }
+    }
+}
+
+const VAULT_FILE: &str = "credentials.vault";
+const VAULT_KEY_FILE: &str = "credentials.key";
+
+/// Serializes vault read-modify-write cycles across concurrent probes.
+static VAULT_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VaultContents {
+    /// service -> base64(nonce || ciphertext || tag)
+    entries: BTreeMap<String, String>,
+}
+
+/// Encrypted file fallback: ChaCha20-Poly1305 with a random key kept next to
+/// the vault (both 0600). This protects against casual reads and backups of
+/// the vault alone, not against someone who can read the whole app data dir.
+pub struct FileVault {
+    dir: PathBuf,
+}
+
+impl FileVault {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn key(&self, create: bool) -> Result<Option<LessSafeKey>, String> {
+        let path = self.dir.join(VAULT_KEY_FILE);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+                let mut bytes = vec![0u8; CHACHA20_POLY1305.key_len()];
+                SystemRandom::new()
+                    .fill(&mut bytes)
+                    .map_err(|_| "failed to generate vault key".to_string())?;
+                write_private(&path, &bytes)?;
+                bytes
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes)
+            .map_err(|_| "vault key is corrupt".to_string())?;
+        Ok(Some(LessSafeKey::new(key)))
+    }
+
+    fn load(&self) -> Result<VaultContents, String> {
+        match std::fs::read(self.dir.join(VAULT_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(VaultContents::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn save(&self, contents: &VaultContents) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(contents).map_err(|e| e.to_string())?;
+        let path = self.dir.join(VAULT_FILE);
+        let tmp = self.dir.join(format!("{}.tmp", VAULT_FILE));
+        write_private(&tmp, &bytes)?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+}
+
+impl CredentialStore for FileVault {
+    fn name(&self) -> &'static str {
+        "file-vault"
+    }
+
+    fn read(&self, service: &str) -> Result<String, CredentialError> {
+        let _guard = VAULT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let not_found = || CredentialError::NotFound(service.to_string());
+        let contents = self
+            .load()
+            .map_err(|e| failed("read", e))?;
+        let entry = contents.entries.get(service).ok_or_else(not_found)?;
+        let key = self
+            .key(false)
+            .map_err(|e| failed("read", e))?
+            .ok_or_else(not_found)?;
+        open_entry(&key, service, entry).map_err(|e| failed("read", e))
+    }
+
+    fn write(&self, service: &str, value: &str) -> Result<(), CredentialError> {
+        let _guard = VAULT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let result = (|| {
+            std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+            let key = self.key(true)?.ok_or("vault key missing")?;
+            let entry = seal_entry(&key, service, value)?;
+            let mut contents = self.load()?;
+            contents.entries.insert(service.to_string(), entry);
+            self.save(&contents)
+        })();
+        result.map_err(|e| failed("write", e))
+    }
+
+    fn delete(&self, service: &str) -> Result<(), CredentialError> {
+        let _guard = VAULT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut contents = self
+            .load()
+            .map_err(|e| failed("delete", e))?;
+        if contents.entries.remove(service).is_none() {
+            return Err(CredentialError::NotFound(service.to_string()));
+        }
+        self.save(&contents)
+            .map_err(|e| failed("delete", e))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError> {
+        let _guard = VAULT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let contents = self
+            .load()
+            .map_err(|e| failed("list", e))?;
+        Ok(contents
+            .entries
+            .into_keys()
+            .filter(|service| service.starts_with(prefix))
+            .collect())
+    }
+}
+
+/// The service name is bound as associated data, so an entry copied under a
+/// different service fails to decrypt.
+fn seal_entry(key: &LessSafeKey, service: &str, value: &str) -> Result<String, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "failed to generate nonce".to_string())?;
+    let mut in_out = value.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(service.as_bytes()),
+        &mut in_out,
+    )
+    .map_err(|_| "encryption failed".to_string())?;
+    let mut blob = nonce.to_vec();
+    blob.extend_from_slice(&in_out);
+    Ok(base64::engine::general_purpose::STANDARD.encode(blob))
+}
+
+fn open_entry(key: &LessSafeKey, service: &str, entry: &str) -> Result<String, String> {
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(entry)
+        .map_err(|e| e.to_string())?;
+    if blob.len() < NONCE_LEN {
+        return Err("vault entry is truncated".to_string());
+    }
+    let (nonce, sealed) = blob.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "bad nonce".to_string())?;
+    let mut in_out = sealed.to_vec();
+    let plain = key
+        .open_in_place(nonce, Aad::from(service.as_bytes()), &mut in_out)
+        .map_err(|_| "vault entry failed authentication".to_string())?;
+    String::from_utf8(plain.to_vec()).map_err(|e| e.to_string())
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| e.to_string())?;
+    file.write_all(bytes).map_err(|e| e.to_string())
+}
+
+/// Prefix of the app's own entries: secret settings and in-app OAuth logins.
+pub const RESERVED_PREFIX: &str = "openusage:";
+
+/// The view of a store that plugin code gets through `host.keychain`. Entries
+/// under `RESERVED_PREFIX` belong to the app and are scoped per plugin, so
+/// they can't be read, written, deleted or listed here.
+pub struct PluginKeychain(pub Arc<dyn CredentialStore>);
+
+impl PluginKeychain {
+    fn check(service: &str) -> Result<(), CredentialError> {
+        if service.starts_with(RESERVED_PREFIX) {
+            return Err(CredentialError::Failed(format!(
+                "keychain service names starting with \"{}\" are reserved",
+                RESERVED_PREFIX
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl CredentialStore for PluginKeychain {
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn read(&self, service: &str) -> Result<String, CredentialError> {
+        Self::check(service)?;
+        self.0.read(service)
+    }
+
+    fn write(&self, service: &str, value: &str) -> Result<(), CredentialError> {
+        Self::check(service)?;
+        self.0.write(service, value)
+    }
+
+    fn delete(&self, service: &str) -> Result<(), CredentialError> {
+        Self::check(service)?;
+        self.0.delete(service)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError> {
+        Ok(self
+            .0
+            .list(prefix)?
+            .into_iter()
+            .filter(|service| !service.starts_with(RESERVED_PREFIX))
+            .collect())
+    }
+}
+
+/// Map-backed store, for tests that must not touch the real keychain.
+pub struct MemoryStore {
+    items: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    pub fn new(items: HashMap<String, String>) -> Self {
+        Self {
+            items: Mutex::new(items),
+        }
+    }
+}
+
+impl CredentialStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn read(&self, service: &str) -> Result<String, CredentialError> {
+        self.items
+            .lock()
+            .ok()
+            .and_then(|items| items.get(service).cloned())
+            .ok_or_else(|| CredentialError::NotFound(service.to_string()))
+    }
+
+    fn write(&self, service: &str, value: &str) -> Result<(), CredentialError> {
+        if let Ok(mut items) = self.items.lock() {
+            items.insert(service.to_string(), value.to_string());
+        }
+        Ok(())
+    }
+
+    fn delete(&self, service: &str) -> Result<(), CredentialError> {
+        self.items
+            .lock()
+            .ok()
+            .and_then(|mut items| items.remove(service))
+            .map(|_| ())
+            .ok_or_else(|| CredentialError::NotFound(service.to_string()))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, CredentialError> {
+        let mut services: Vec<String> = self
+            .items
+            .lock()
+            .map(|items| items.keys().filter(|s| s.starts_with(prefix)).cloned().collect())
+            .unwrap_or_default();
+        services.sort();
+        Ok(services)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn file_vault_roundtrips_and_overwrites() {
+        let dir = TempDir::new("credentials-roundtrip");
+        let vault = FileVault::new(&dir);
+        let err = vault.read("Claude Code-credentials").unwrap_err();
+        assert!(matches!(err, CredentialError::NotFound(_)), "{}", err);
+
+        vault.write("Claude Code-credentials", "{\"a\":1}").expect("write");
+        vault.write("Claude Code-credentials", "{\"a\":2}").expect("overwrite");
+        vault.write("other", "secret").expect("write other");
+        assert_eq!(vault.read("Claude Code-credentials").unwrap(), "{\"a\":2}");
+        assert_eq!(FileVault::new(&dir).read("other").unwrap(), "secret");
+        assert_eq!(vault.list("Claude").unwrap(), vec!["Claude Code-credentials".to_string()]);
+
+        let raw = std::fs::read_to_string(dir.join(VAULT_FILE)).expect("vault file");
+        assert!(!raw.contains("secret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(VAULT_KEY_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        vault.delete("other").expect("delete");
+        assert!(matches!(vault.read("other"), Err(CredentialError::NotFound(_))));
+        assert!(matches!(vault.delete("other"), Err(CredentialError::NotFound(_))));
+        assert_eq!(vault.list("").unwrap(), vec!["Claude Code-credentials".to_string()]);
+    }
+
+    #[test]
+    fn file_vault_rejects_entries_moved_between_services() {
+        let dir = TempDir::new("credentials-tamper");
+        let vault = FileVault::new(&dir);
+        vault.write("a", "alpha").expect("write");
+        let mut contents = vault.load().expect("load");
+        let entry = contents.entries["a"].clone();
+        contents.entries.insert("b".to_string(), entry);
+        vault.save(&contents).expect("save");
+
+        let err = vault.read("b").unwrap_err();
+        assert!(err.to_string().contains("failed authentication"), "{}", err);
+        assert_eq!(vault.read("a").unwrap(), "alpha");
+    }
+
+    #[test]
+    fn mac_dump_lists_only_our_generic_passwords() {
+        let dump = r#"keychain: "/Users/me/Library/Keychains/login.keychain-db"
+version: 512
+class: "genp"
+attributes:
+    "acct"<blob>="me"
+    "crtr"<uint32>="opnu"
+    "svce"<blob>="OpenUsage-copilot"
+keychain: "/Users/me/Library/Keychains/login.keychain-db"
+version: 512
+class: "genp"
+attributes:
+    "acct"<blob>="me"
+    "crtr"<uint32>=<NULL>
+    "svce"<blob>="Claude Code-credentials"
+keychain: "/Users/me/Library/Keychains/login.keychain-db"
+version: 512
+class: "inet"
+attributes:
+    "crtr"<uint32>="opnu"
+    "svce"<blob>="OpenUsage-web"
+keychain: "/Users/me/Library/Keychains/login.keychain-db"
+version: 512
+class: "genp"
+attributes:
+    "crtr"<uint32>="opnu"
+    "svce"<blob>="gh:github.com"
+keychain: "/Users/me/Library/Keychains/login.keychain-db"
+version: 512
+class: "genp"
+attributes:
+    "acct"<blob>=<NULL>
+    "crtr"<uint32>=<NULL>
+    "svce"<blob>="OpenUsage-legacy"
+"#;
+        assert_eq!(
+            mac_dump_services(dump, ""),
+            vec![
+                "OpenUsage-copilot".to_string(),
+                "OpenUsage-legacy".to_string(),
+                "gh:github.com".to_string(),
+            ]
+        );
+        assert_eq!(
+            mac_dump_services(dump, "OpenUsage-"),
+            vec!["OpenUsage-copilot".to_string(), "OpenUsage-legacy".to_string()]
+        );
+    }
+
+    #[test]
+    fn mac_items_tell_older_items_of_ours_from_other_apps() {
+        let find = |account: &str, creator: &str| {
+            let output = format!(
+                "keychain: \"/Users/me/Library/Keychains/login.keychain-db\"\n\
+                 version: 512\n\
+                 class: \"genp\"\n\
+                 attributes:\n    \"acct\"<blob>={}\n    \"crtr\"<uint32>={}\n    \
+                 \"svce\"<blob>=\"OpenUsage-copilot\"\n",
+                account, creator
+            );
+            mac_items(&output).pop().expect("item")
+        };
+
+        let legacy = find("<NULL>", "<NULL>");
+        assert_eq!(legacy.service.as_deref(), Some("OpenUsage-copilot"));
+        assert!(legacy.is_ours());
+        assert!(find("\"\"", "<NULL>").is_ours());
+        assert!(find("\"me\"", "\"opnu\"").is_ours());
+
+        let cli = find("\"me\"", "<NULL>");
+        assert_eq!(cli.account.as_deref(), Some("me"));
+        assert!(!cli.is_ours());
+        assert!(!find("\"me\"", "\"aapl\"").is_ours());
+        let hex = find("0x6D65FF  \"me\\377\"", "<NULL>");
+        assert_eq!(hex.account.as_deref(), Some("me\\377"));
+        assert!(!hex.is_ours());
+    }
+
+    #[test]
+    fn memory_store_supports_every_operation() {
+        let store = MemoryStore::new(HashMap::from([("a".to_string(), "1".to_string())]));
+        assert_eq!(store.read("a").unwrap(), "1");
+        store.write("b", "2").unwrap();
+        assert_eq!(store.read("b").unwrap(), "2");
+        assert!(store.read("c").is_err());
+        assert_eq!(store.list("").unwrap(), vec!["a".to_string(), "b".to_string()]);
+        store.delete("a").unwrap();
+        assert_eq!(store.delete("a").unwrap_err(), CredentialError::NotFound("a".to_string()));
+    }
+
+    /// Minimal Secret Service stand-in served over a socket pair.
+    #[cfg(target_os = "linux")]
+    mod fake_secret_service {
+        use super::super::secret_service::*;
+        use std::collections::HashMap;
+        use std::sync::{Arc, Mutex};
+        use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+        /// Item slots by index; deleted items leave a `None` behind.
+        pub type Items = Arc<Mutex<Vec<Option<(HashMap<String, String>, Vec<u8>)>>>>;
+
+        /// Item objects are registered up front, one per slot.
+        const SLOTS: usize = 8;
+
+        fn item_path(index: usize) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(format!("/org/freedesktop/secrets/collection/login/{}", index))
+                .expect("item path")
+        }
+
+        fn item_index(path: &OwnedObjectPath) -> Option<usize> {
+            path.as_str().rsplit('/').next()?.parse().ok()
+        }
+
+        pub struct Service {
+            pub items: Items,
+        }
+
+        #[zbus::interface(name = "org.freedesktop.Secret.Service")]
+        impl Service {
+            fn open_session(&self, _algorithm: &str, _input: OwnedValue) -> (OwnedValue, OwnedObjectPath) {
+                let output = OwnedValue::try_from(Value::from("")).expect("value");
+                let session = OwnedObjectPath::try_from("/org/freedesktop/secrets/session/1")
+                    .expect("session path");
+                (output, session)
+            }
+
+            fn search_items(
+                &self,
+                attributes: HashMap<String, String>,
+            ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+                let items = self.items.lock().unwrap();
+                let found = items
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, item)| Some((index, item.as_ref()?)))
+                    .filter(|(_, (attrs, _))| {
+                        attributes.iter().all(|(k, v)| attrs.get(k) == Some(v))
+                    })
+                    .map(|(index, _)| item_path(index))
+                    .collect();
+                (found, Vec::new())
+            }
+
+            fn get_secrets(
+                &self,
+                items: Vec<OwnedObjectPath>,
+                session: OwnedObjectPath,
+            ) -> HashMap<OwnedObjectPath, Secret> {
+                let stored = self.items.lock().unwrap();
+                items
+                    .into_iter()
+                    .filter_map(|path| {
+                        let (_, value) = stored.get(item_index(&path)?)?.as_ref()?;
+                        let secret = (session.clone(), Vec::new(), value.clone(), "text/plain".into());
+                        Some((path, secret))
+                    })
+                    .collect()
+            }
+        }
+
+        pub struct Collection {
+            pub items: Items,
+        }
+
+        #[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+        impl Collection {
+            fn create_item(
+                &self,
+                properties: HashMap<String, OwnedValue>,
+                secret: Secret,
+                replace: bool,
+            ) -> (OwnedObjectPath, OwnedObjectPath) {
+                let attributes: HashMap<String, String> = properties
+                    .get("org.freedesktop.Secret.Item.Attributes")
+                    .and_then(|value| value.try_clone().ok())
+                    .and_then(|value| HashMap::try_from(value).ok())
+                    .unwrap_or_default();
+                let mut items = self.items.lock().unwrap();
+                let existing = items
+                    .iter()
+                    .position(|item| item.as_ref().is_some_and(|(attrs, _)| *attrs == attributes));
+                let index = match existing {
+                    Some(index) if replace => index,
+                    _ => {
+                        items.push(None);
+                        items.len() - 1
+                    }
+                };
+                assert!(index < SLOTS, "fake secret service is full");
+                items[index] = Some((attributes, secret.2));
+                (item_path(index), OwnedObjectPath::try_from("/").expect("root"))
+            }
+        }
+
+        pub struct Item {
+            pub items: Items,
+            pub index: usize,
+        }
+
+        #[zbus::interface(name = "org.freedesktop.Secret.Item")]
+        impl Item {
+            fn delete(&self) -> OwnedObjectPath {
+                if let Some(item) = self.items.lock().unwrap().get_mut(self.index) {
+                    *item = None;
+                }
+                OwnedObjectPath::try_from("/").expect("root")
+            }
+
+            #[zbus(property)]
+            fn attributes(&self) -> HashMap<String, String> {
+                let items = self.items.lock().unwrap();
+                items
+                    .get(self.index)
+                    .and_then(|item| item.as_ref())
+                    .map(|(attrs, _)| attrs.clone())
+                    .unwrap_or_default()
+            }
+        }
+
+        /// Returns the client store plus the server connection, which must be
+        /// kept alive for the duration of the test.
+        pub fn connect(items: Items) -> (SecretService, zbus::blocking::Connection) {
+            let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().expect("pair");
+            let guid = zbus::Guid::generate();
+            let server_items = Arc::clone(&items);
+            let server = std::thread::spawn(move || {
+                let mut builder = zbus::blocking::connection::Builder::unix_stream(server_sock)
+                    .server(guid)
+                    .expect("server")
+                    .p2p()
+                    .serve_at(SERVICE_PATH, Service { items: Arc::clone(&server_items) })
+                    .expect("serve service")
+                    .serve_at(DEFAULT_COLLECTION, Collection { items: Arc::clone(&server_items) })
+                    .expect("serve collection");
+                for index in 0..SLOTS {
+                    let item = Item { items: Arc::clone(&server_items), index };
+                    builder = builder.serve_at(item_path(index), item).expect("serve item");
+                }
+                builder.build().expect("server connection")
+            });
+            let client = zbus::blocking::connection::Builder::unix_stream(client_sock)
+                .p2p()
+                .build()
+                .expect("client connection");
+            let server = server.join().expect("server thread");
+            (SecretService::new(client), server)
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn secret_service_reads_and_writes_through_dbus() {
+        let items: fake_secret_service::Items = Arc::new(Mutex::new(vec![Some((
+            HashMap::from([
+                ("service".to_string(), "gh:github.com".to_string()),
+                ("username".to_string(), "octocat".to_string()),
+            ]),
+            b"gho_token\n".to_vec(),
+        ))]));
+        let (store, _server) = fake_secret_service::connect(Arc::clone(&items));
+
+        assert_eq!(store.read("gh:github.com").unwrap(), "gho_token");
+        let err = store.read("Claude Code-credentials").unwrap_err();
+        assert_eq!(err.to_string(), "keychain item not found: Claude Code-credentials");
+
+        store.write("Claude Code-credentials", "{\"v\":1}").expect("write");
+        store.write("Claude Code-credentials", "{\"v\":2}").expect("replace");
+        assert_eq!(store.read("Claude Code-credentials").unwrap(), "{\"v\":2}");
+
+        {
+            let items = items.lock().unwrap();
+            assert_eq!(items.len(), 2);
+            let (attrs, _) = items[1].as_ref().expect("created item");
+            assert_eq!(attrs.get("application").map(String::as_str), Some("openusage"));
+        }
+
+        // Only items OpenUsage created are listed; gh's item is left alone.
+        store.write("OpenUsage-copilot", "{}").expect("write copilot");
+        assert_eq!(
+            store.list("").unwrap(),
+            vec!["Claude Code-credentials".to_string(), "OpenUsage-copilot".to_string()]
+        );
+        assert_eq!(store.list("OpenUsage-").unwrap(), vec!["OpenUsage-copilot".to_string()]);
+
+        store.delete("OpenUsage-copilot").expect("delete");
+        let err = store.delete("OpenUsage-copilot").unwrap_err();
+        assert_eq!(err.to_string(), "keychain item not found: OpenUsage-copilot");
+        assert!(store.read("OpenUsage-copilot").is_err());
+        assert_eq!(store.read("gh:github.com").unwrap(), "gho_token");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn secret_service_only_deletes_items_we_created() {
+        let items: fake_secret_service::Items = Arc::new(Mutex::new(vec![Some((
+            HashMap::from([
+                ("service".to_string(), "gh:github.com".to_string()),
+                ("username".to_string(), "octocat".to_string()),
+            ]),
+            b"gho_token\n".to_vec(),
+        ))]));
+        let (store, _server) = fake_secret_service::connect(Arc::clone(&items));
+
+        let err = store.delete("gh:github.com").unwrap_err();
+        assert_eq!(err, CredentialError::NotFound("gh:github.com".to_string()));
+        assert_eq!(store.read("gh:github.com").unwrap(), "gho_token");
+        assert!(items.lock().unwrap()[0].is_some());
+    }
+}
@@ -0,0 +1,172 @@
+//! Hashing, randomness and encodings behind `ctx.crypto`, `ctx.base64` and
+//! `ctx.hex`. Strings cross the JS boundary as UTF-8; binary results are
+//! returned in the encoding the plugin asks for (hex by default).
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use ring::rand::SecureRandom;
+
+pub const MAX_RANDOM_BYTES: usize = 1024;
+/// 32 random bytes give a 43-character verifier, within RFC 7636's 43-128.
+const PKCE_VERIFIER_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+    Base64Url,
+}
+
+impl Encoding {
+    pub fn parse(name: Option<&str>) -> Result<Self, String> {
+        match name.unwrap_or("hex") {
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            "base64url" => Ok(Self::Base64Url),
+            other => Err(format!("unsupported encoding: {}", other)),
+        }
+    }
+
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Hex => hex_encode(bytes),
+            Self::Base64 => STANDARD.encode(bytes),
+            Self::Base64Url => URL_SAFE_NO_PAD.encode(bytes),
+        }
+    }
+}
+
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    ring::digest::digest(&ring::digest::SHA256, data).as_ref().to_vec()
+}
+
+/// For legacy protocols only (e.g. APIs that sign with SHA-1).
+pub fn sha1(data: &[u8]) -> Vec<u8> {
+    ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, data)
+        .as_ref()
+        .to_vec()
+}
+
+pub fn hmac(algorithm: &str, key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let algorithm = match algorithm {
+        "sha256" => ring::hmac::HMAC_SHA256,
+        "sha1" => ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+        other => return Err(format!("unsupported hmac algorithm: {}", other)),
+    };
+    let key = ring::hmac::Key::new(algorithm, key);
+    Ok(ring::hmac::sign(&key, data).as_ref().to_vec())
+}
+
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, String> {
+    if len > MAX_RANDOM_BYTES {
+        return Err(format!("randomBytes is limited to {} bytes", MAX_RANDOM_BYTES));
+    }
+    let mut buf = vec![0u8; len];
+    ring::rand::SystemRandom::new()
+        .fill(&mut buf)
+        .map_err(|_| "system random number generator failed".to_string())?;
+    Ok(buf)
+}
+
+pub fn uuid_v4() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// `(verifier, challenge)` for the S256 method.
+pub fn pkce_pair() -> Result<(String, String), String> {
+    let verifier = URL_SAFE_NO_PAD.encode(random_bytes(PKCE_VERIFIER_BYTES)?);
+    let challenge = URL_SAFE_NO_PAD.encode(sha256(verifier.as_bytes()));
+    Ok((verifier, challenge))
+}
+
+/// Accepts the standard and URL-safe alphabets, with or without padding,
+/// since JWTs and most OAuth payloads use unpadded base64url.
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
+    let normalized: String = input
+        .trim()
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    STANDARD_NO_PAD
+        .decode(normalized)
+        .map_err(|e| format!("invalid base64: {}", e))
+}
+
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn hex_decode(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    if !input.len().is_multiple_of(2) || !input.is_ascii() {
+        return Err("invalid hex: odd length or non-ASCII".to_string());
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&input[i..i + 2], 16).map_err(|_| format!("invalid hex at {}", i))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_and_hmac_match_known_vectors() {
+        assert_eq!(
+            hex_encode(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hex_encode(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // RFC 4231 test case 2.
+        assert_eq!(
+            hex_encode(&hmac("sha256", b"Jefe", b"what do ya want for nothing?").unwrap()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert!(hmac("md5", b"k", b"d").is_err());
+    }
+
+    #[test]
+    fn base64_handles_utf8_and_url_alphabet() {
+        let encoded = STANDARD.encode("José ✓".as_bytes());
+        assert_eq!(encoded, "Sm9zw6kg4pyT");
+        assert_eq!(base64_decode(&encoded).unwrap(), "José ✓".as_bytes());
+
+        // Unpadded base64url, as in JWT segments.
+        assert_eq!(base64_decode("-_8").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(base64_decode("+/8=").unwrap(), vec![0xfb, 0xff]);
+        assert!(base64_decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn hex_roundtrips_and_rejects_garbage() {
+        assert_eq!(hex_decode("00ff10").unwrap(), vec![0, 255, 16]);
+        assert_eq!(hex_encode(&[0, 255, 16]), "00ff10");
+        assert!(hex_decode("abc").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+
+    #[test]
+    fn random_values_and_pkce() {
+        assert_eq!(random_bytes(16).unwrap().len(), 16);
+        assert_ne!(random_bytes(16).unwrap(), random_bytes(16).unwrap());
+        assert!(random_bytes(MAX_RANDOM_BYTES + 1).is_err());
+
+        let id = uuid_v4();
+        assert_eq!(id.len(), 36);
+        assert_eq!(id.as_bytes()[14], b'4');
+
+        let (verifier, challenge) = pkce_pair().unwrap();
+        assert_eq!(verifier.len(), 43);
+        assert_eq!(challenge, URL_SAFE_NO_PAD.encode(sha256(verifier.as_bytes())));
+        assert_eq!(Encoding::parse(Some("base64url")).unwrap().encode(&[0xfb, 0xff]), "-_8");
+        assert!(Encoding::parse(Some("latin1")).is_err());
+    }
+}
@@ -0,0 +1,570 @@
+//! Manifest-only plugins: HTTP requests plus JSONPath mappings to lines,
+//! evaluated without a JS runtime.
+//!
+//! Strings in a spec are templates. `{{config.key}}` inserts a setting,
+//! `{{env.NAME}}` an environment variable declared in `permissions.env`, and
+//! `{{$.request.path}}` a value from an earlier response. A mapping that is a
+//! bare path (`"$.usage.spent"`) keeps the JSON type, so numbers stay numbers.
+//! Response values inserted into a request URL are percent-encoded.
+
+use crate::plugin_engine::host_api::HttpHost;
+use crate::plugin_engine::oauth;
+use crate::plugin_engine::runtime::{MetricLine, ProgressFormat};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeclarativeSpec {
+    /// Run in order; each response is available as `$.<name>` afterwards.
+    pub requests: Vec<RequestSpec>,
+    #[serde(default)]
+    pub plan: Option<String>,
+    pub lines: Vec<LineMapping>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestSpec {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LineMapping {
+    #[serde(rename_all = "camelCase")]
+    Progress {
+        label: String,
+        used: Value,
+        limit: Value,
+        #[serde(default = "default_format")]
+        format: ProgressFormat,
+        /// Multiplies `used` and `limit`, e.g. `100` for 0–1 ratios.
+        #[serde(default)]
+        scale: Option<f64>,
+        #[serde(default)]
+        resets_at: Option<String>,
+        #[serde(default)]
+        period_duration_ms: Option<u64>,
+        #[serde(default)]
+        color: Option<String>,
+    },
+    Text {
+        label: String,
+        value: Value,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        subtitle: Option<String>,
+    },
+    Badge {
+        label: String,
+        text: Value,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        subtitle: Option<String>,
+    },
+}
+
+fn default_format() -> ProgressFormat {
+    ProgressFormat::Percent
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+/// Sends a request and returns `(status, body)`.
+pub type Fetch<'a> = &'a dyn Fn(&HttpRequest) -> Result<(u16, String), String>;
+
+/// Sends requests through the plugin's HTTP host, so they get the same URL
+/// overrides, VCR recording and logging as `host.http`.
+pub(crate) fn http_fetch(http: HttpHost) -> impl Fn(&HttpRequest) -> Result<(u16, String), String> {
+    move |req: &HttpRequest| {
+        let headers: Map<String, Value> = req
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+            .collect();
+        let req_json = serde_json::json!({
+            "method": req.method,
+            "url": req.url,
+            "headers": headers,
+            "bodyText": req.body,
+            "timeoutMs": 15_000,
+        });
+        let resp: Value = serde_json::from_str(&http.request(&req_json.to_string())?)
+            .map_err(|e| format!("invalid response: {}", e))?;
+        let status = resp.get("status").and_then(Value::as_u64).unwrap_or(0) as u16;
+        let body = resp.get("bodyText").and_then(Value::as_str).unwrap_or("").to_string();
+        Ok((status, body))
+    }
+}
+
+/// Checks a spec when the plugin is loaded. `env_allowed` is `permissions.env`.
+pub fn validate(spec: &DeclarativeSpec, env_allowed: &[String]) -> Result<(), String> {
+    if spec.requests.is_empty() {
+        return Err("declarative plugin needs at least one request".to_string());
+    }
+    let mut names = HashSet::new();
+    for request in &spec.requests {
+        let valid_name = !request.name.is_empty()
+            && request
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name || !names.insert(request.name.as_str()) {
+            return Err(format!(
+                "declarative request name '{}' must be unique and alphanumeric",
+                request.name
+            ));
+        }
+        if !request.url.starts_with("https://") && !request.url.starts_with("http://") {
+            return Err(format!("declarative request '{}' needs an http(s) URL", request.name));
+        }
+        if reqwest::Method::from_bytes(request.method.as_bytes()).is_err() {
+            return Err(format!("declarative request '{}' has an invalid method", request.name));
+        }
+    }
+
+    let mut templates: Vec<&str> = Vec::new();
+    for request in &spec.requests {
+        templates.push(&request.url);
+        templates.extend(request.headers.values().map(String::as_str));
+        templates.extend(request.body.as_deref());
+    }
+    templates.extend(spec.plan.as_deref());
+    for line in &spec.lines {
+        match line {
+            LineMapping::Progress { used, limit, resets_at, .. } => {
+                templates.extend(used.as_str());
+                templates.extend(limit.as_str());
+                templates.extend(resets_at.as_deref());
+            }
+            LineMapping::Text { value: text, .. } | LineMapping::Badge { text, .. } => {
+                templates.extend(text.as_str());
+            }
+        }
+    }
+    for template in templates {
+        for expr in placeholders(template) {
+            if let Some(name) = expr.strip_prefix("env.")
+                && !env_allowed.iter().any(|allowed| allowed == name)
+            {
+                return Err(format!("env var {} must be listed in permissions.env", name));
+            }
+            if expr.starts_with('$') {
+                parse_path(expr)?;
+            }
+        }
+        if template.starts_with('$') && !template.contains("{{") {
+            parse_path(template)?;
+        }
+    }
+    Ok(())
+}
+
+/// What templates can refer to besides responses.
+pub struct Inputs<'a> {
+    pub config: &'a Map<String, Value>,
+    pub env: &'a dyn Fn(&str) -> Option<String>,
+}
+
+/// Result of evaluating a spec: the plan label and lines, or a user-facing error.
+pub type Evaluated = Result<(Option<String>, Vec<MetricLine>), String>;
+
+pub fn evaluate(plugin_id: &str, spec: &DeclarativeSpec, inputs: &Inputs, fetch: Fetch) -> Evaluated {
+    let mut data = Value::Object(Map::new());
+
+    for request in &spec.requests {
+        let scope = Scope { inputs, data: &data };
+        let render_with = |template: &str, encode: bool| {
+            scope.render_with(template, encode).ok_or_else(|| {
+                format!(
+                    "Request '{}' is missing a value. Check the plugin's settings.",
+                    request.name
+                )
+            })
+        };
+        let render = |template: &str| render_with(template, false);
+        let http = HttpRequest {
+            method: request.method.to_uppercase(),
+            // Response values may hold `/`, `?` or spaces; config values are
+            // left alone so a setting can supply a whole base URL.
+            url: render_with(&request.url, true)?,
+            headers: request
+                .headers
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), render(value)?)))
+                .collect::<Result<_, String>>()?,
+            body: request.body.as_deref().map(render).transpose()?,
+        };
+
+        let (status, body) = fetch(&http).map_err(|e| {
+            log::warn!("[plugin:{}] request '{}' failed: {}", plugin_id, request.name, e);
+            format!("Request '{}' failed. Check your connection.", request.name)
+        })?;
+        log::info!("[plugin:{}] request '{}' -> HTTP {}", plugin_id, request.name, status);
+        if status == 401 || status == 403 {
+            return Err(format!(
+                "Authentication failed (HTTP {}). Check the plugin's settings.",
+                status
+            ));
+        }
+        if !(200..300).contains(&status) {
+            return Err(format!("Request '{}' failed (HTTP {}).", request.name, status));
+        }
+        let json: Value = serde_json::from_str(&body)
+            .map_err(|_| format!("Request '{}' returned invalid JSON.", request.name))?;
+        if let Value::Object(map) = &mut data {
+            map.insert(request.name.clone(), json);
+        }
+    }
+
+    let scope = Scope { inputs, data: &data };
+    let plan = spec.plan.as_deref().and_then(|template| scope.text(template));
+    let mut lines = Vec::new();
+    for mapping in &spec.lines {
+        // Lines whose values are missing from the response are left out.
+        let line = match mapping {
+            LineMapping::Progress {
+                label,
+                used,
+                limit,
+                format,
+                scale,
+                resets_at,
+                period_duration_ms,
+                color,
+            } => {
+                let scale = scale.unwrap_or(1.0);
+                match (scope.number(used), scope.number(limit)) {
+                    (Some(used), Some(limit)) => Some(MetricLine::Progress {
+                        label: label.clone(),
+                        used: used * scale,
+                        limit: limit * scale,
+                        format: format.clone(),
+                        resets_at: resets_at.as_deref().and_then(|template| scope.timestamp(template)),
+                        period_duration_ms: *period_duration_ms,
+                        color: color.clone(),
+                    }),
+                    _ => None,
+                }
+            }
+            LineMapping::Text { label, value, color, subtitle } => {
+                scope.text_value(value).map(|value| MetricLine::Text {
+                    label: label.clone(),
+                    value,
+                    color: color.clone(),
+                    subtitle: subtitle.as_deref().and_then(|template| scope.text(template)),
+                })
+            }
+            LineMapping::Badge { label, text, color, subtitle } => {
+                scope.text_value(text).map(|text| MetricLine::Badge {
+                    label: label.clone(),
+                    text,
+                    color: color.clone(),
+                    subtitle: subtitle.as_deref().and_then(|template| scope.text(template)),
+                })
+            }
+        };
+        match line {
+            Some(line) => lines.push(line),
+            None => log::debug!("[plugin:{}] skipped line with missing data", plugin_id),
+        }
+    }
+    if lines.is_empty() {
+        lines.push(MetricLine::Badge {
+            label: "Status".to_string(),
+            text: "No usage data".to_string(),
+            color: Some("#a3a3a3".to_string()),
+            subtitle: None,
+        });
+    }
+    Ok((plan, lines))
+}
+
+struct Scope<'a> {
+    inputs: &'a Inputs<'a>,
+    data: &'a Value,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, expr: &str) -> Option<Value> {
+        let expr = expr.trim();
+        if let Some(key) = expr.strip_prefix("config.") {
+            return self.inputs.config.get(key).filter(|value| !value.is_null()).cloned();
+        }
+        if let Some(name) = expr.strip_prefix("env.") {
+            return (self.inputs.env)(name).map(Value::String);
+        }
+        let path = parse_path(expr).ok()?;
+        select(self.data, &path).filter(|value| !value.is_null()).cloned()
+    }
+
+    /// A bare path keeps its JSON type; anything else renders as text.
+    fn value(&self, mapping: &Value) -> Option<Value> {
+        match mapping {
+            Value::String(s) if s.starts_with('$') && !s.contains("{{") => self.lookup(s),
+            Value::String(s) => self.render(s).map(Value::String),
+            Value::Null => None,
+            other => Some(other.clone()),
+        }
+    }
+
+    fn number(&self, mapping: &Value) -> Option<f64> {
+        match self.value(mapping)? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn text_value(&self, mapping: &Value) -> Option<String> {
+        self.value(mapping).map(|value| display(&value))
+    }
+
+    fn text(&self, template: &str) -> Option<String> {
+        self.text_value(&Value::String(template.to_string()))
+    }
+
+    /// ISO strings pass through; numbers are unix seconds or milliseconds.
+    fn timestamp(&self, template: &str) -> Option<String> {
+        match self.value(&Value::String(template.to_string()))? {
+            Value::String(s) => Some(s),
+            Value::Number(n) => {
+                let n = n.as_f64()?;
+                let millis = if n.abs() < 1e12 { n * 1000.0 } else { n };
+                let nanos = (millis as i128) * 1_000_000;
+                time::OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                    .ok()?
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Fills every `{{expr}}`; `None` if any of them has no value.
+    fn render(&self, template: &str) -> Option<String> {
+        self.render_with(template, false)
+    }
+
+    /// Like `render`, but with `encode` set values from earlier responses
+    /// (`$.` paths) are percent-encoded, for use in URLs.
+    fn render_with(&self, template: &str, encode: bool) -> Option<String> {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..].find("}}")? + start;
+            let expr = &rest[start + 2..end];
+            let value = display(&self.lookup(expr)?);
+            out.push_str(&rest[..start]);
+            if encode && expr.trim().starts_with('$') {
+                out.push_str(&oauth::form_encode(&value));
+            } else {
+                out.push_str(&value);
+            }
+            rest = &rest[end + 2..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else { break };
+        found.push(rest[start + 2..start + end].trim());
+        rest = &rest[start + end + 2..];
+    }
+    found
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+/// Parses the JSONPath subset `$`, `.key`, `['key']` and `[n]` (negative `n`
+/// counts from the end).
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let invalid = || format!("invalid JSONPath: {}", path);
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(Segment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            match quoted {
+                Some(key) => segments.push(Segment::Key(key.to_string())),
+                None => segments.push(Segment::Index(inner.parse().map_err(|_| invalid())?)),
+            }
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+fn select<'a>(value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |current, segment| match segment {
+        Segment::Key(key) => current.get(key),
+        Segment::Index(index) => {
+            let items = current.as_array()?;
+            let index = if *index < 0 {
+                items.len().checked_sub(index.unsigned_abs() as usize)?
+            } else {
+                *index as usize
+            };
+            items.get(index)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn spec(json: &str) -> DeclarativeSpec {
+        serde_json::from_str(json).expect("spec")
+    }
+
+    const GATEWAY: &str = r#"{
+        "requests": [
+            {
+                "name": "usage",
+                "url": "https://llm.internal/v1/teams/{{config.team}}/usage",
+                "headers": { "Authorization": "Bearer {{env.GATEWAY_TOKEN}}" }
+            },
+            { "name": "limits", "url": "https://llm.internal/v1/limits/{{$.usage.tier}}" }
+        ],
+        "plan": "{{$.usage.tier}} tier",
+        "lines": [
+            { "type": "progress", "label": "Budget", "used": "$.usage.spend.total", "limit": "$.limits.monthly",
+              "format": { "kind": "dollars" }, "resetsAt": "$.usage.period_end" },
+            { "type": "progress", "label": "Requests", "used": "$.usage.ratio", "limit": 1, "scale": 100 },
+            { "type": "text", "label": "Top model", "value": "$.usage.models[0]['name']" },
+            { "type": "badge", "label": "Region", "text": "{{$.usage.region}}" },
+            { "type": "text", "label": "Missing", "value": "$.usage.nope" }
+        ]
+    }"#;
+
+    #[test]
+    fn evaluates_requests_and_maps_lines() {
+        let spec = spec(GATEWAY);
+        validate(&spec, &["GATEWAY_TOKEN".to_string()]).expect("valid");
+        assert!(validate(&spec, &[]).unwrap_err().contains("GATEWAY_TOKEN"));
+
+        let sent = RefCell::new(Vec::new());
+        let fetch = |req: &HttpRequest| {
+            sent.borrow_mut().push(req.clone());
+            let body = if req.url.ends_with("/usage") {
+                r#"{"tier": "gold/eu plan", "spend": {"total": 12.5}, "ratio": 0.25, "period_end": 1767225600,
+                    "models": [{"name": "big-model"}], "region": "eu"}"#
+            } else {
+                r#"{"monthly": 50}"#
+            };
+            Ok((200, body.to_string()))
+        };
+        let config = serde_json::from_str(r#"{"team": "platform"}"#).unwrap();
+        let env = |name: &str| (name == "GATEWAY_TOKEN").then(|| "tok".to_string());
+        let inputs = Inputs { config: &config, env: &env };
+
+        let (plan, lines) = evaluate("gateway", &spec, &inputs, &fetch).expect("evaluate");
+        let sent = sent.into_inner();
+        assert_eq!(sent[0].url, "https://llm.internal/v1/teams/platform/usage");
+        assert_eq!(sent[0].headers, vec![("Authorization".to_string(), "Bearer tok".to_string())]);
+        assert_eq!(sent[1].url, "https://llm.internal/v1/limits/gold%2Feu%20plan");
+        assert_eq!(plan.as_deref(), Some("gold/eu plan tier"));
+
+        assert_eq!(lines.len(), 4);
+        match &lines[0] {
+            MetricLine::Progress { used, limit, resets_at, format, .. } => {
+                assert_eq!((*used, *limit), (12.5, 50.0));
+                assert!(matches!(format, ProgressFormat::Dollars));
+                assert_eq!(resets_at.as_deref(), Some("2026-01-01T00:00:00Z"));
+            }
+            other => panic!("expected progress, got {:?}", other),
+        }
+        assert!(matches!(&lines[1], MetricLine::Progress { used, limit, .. } if *used == 25.0 && *limit == 100.0));
+        assert!(matches!(&lines[2], MetricLine::Text { value, .. } if value == "big-model"));
+        assert!(matches!(&lines[3], MetricLine::Badge { text, .. } if text == "eu"));
+    }
+
+    #[test]
+    fn reports_http_and_config_errors() {
+        let spec = spec(GATEWAY);
+        let config = Map::new();
+        let env = |_: &str| Some("tok".to_string());
+        let inputs = Inputs { config: &config, env: &env };
+        let unreachable = |_: &HttpRequest| -> Result<(u16, String), String> { panic!("no request expected") };
+        assert_eq!(
+            evaluate("gateway", &spec, &inputs, &unreachable).unwrap_err(),
+            "Request 'usage' is missing a value. Check the plugin's settings."
+        );
+
+        let config = serde_json::from_str(r#"{"team": "platform"}"#).unwrap();
+        let inputs = Inputs { config: &config, env: &env };
+        for (status, body, error) in [
+            (401, "", "Authentication failed (HTTP 401). Check the plugin's settings."),
+            (500, "", "Request 'usage' failed (HTTP 500)."),
+            (200, "<html>", "Request 'usage' returned invalid JSON."),
+        ] {
+            let fetch = |_: &HttpRequest| Ok((status, body.to_string()));
+            assert_eq!(evaluate("gateway", &spec, &inputs, &fetch).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn parses_the_jsonpath_subset() {
+        let data: Value = serde_json::from_str(r#"{"a": {"b c": [1, 2, {"d": true}]}}"#).unwrap();
+        let at = |path: &str| select(&data, &parse_path(path).unwrap()).cloned();
+        assert_eq!(at("$.a['b c'][1]"), Some(Value::from(2)));
+        assert_eq!(at("$.a[\"b c\"][-1].d"), Some(Value::Bool(true)));
+        assert_eq!(at("$.a['b c'][9]"), None);
+        assert!(parse_path("a.b").is_err());
+        assert!(parse_path("$..a").is_err());
+        assert!(parse_path("$.a[x]").is_err());
+    }
+}
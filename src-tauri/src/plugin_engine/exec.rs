@@ -0,0 +1,174 @@
+//! Allowlisted command execution for `host.exec`.
+//!
+//! A plugin may only run argv lists it declared under `permissions.exec` in
+//! its manifest, matched exactly. Commands are spawned directly (never through
+//! a shell), with stdin closed, a timeout, and capped stdout/stderr.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+pub const MAX_TIMEOUT_MS: u64 = 60_000;
+/// Per-stream cap; anything beyond is read and discarded so the child never
+/// blocks on a full pipe.
+pub const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecOpts {
+    pub timeout_ms: Option<u64>,
+}
+
+impl ExecOpts {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(
+            self.timeout_ms
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+                .clamp(1, MAX_TIMEOUT_MS),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the process was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// True if either stream hit `MAX_OUTPUT_BYTES`.
+    pub truncated: bool,
+}
+
+/// Exact argv match against the manifest's `permissions.exec` entries.
+pub fn is_allowed(allowlist: &[Vec<String>], argv: &[String]) -> bool {
+    !argv.is_empty() && allowlist.iter().any(|allowed| allowed.as_slice() == argv)
+}
+
+pub fn run(argv: &[String], timeout: Duration) -> Result<ExecOutput, String> {
+    let (program, args) = argv.split_first().ok_or("empty command")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start {}: {}", program, e))?;
+
+    let stdout = child.stdout.take().map(read_capped);
+    let stderr = child.stderr.take().map(read_capped);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                // Reader threads are not joined: a grandchild may still hold
+                // the pipes open, and they exit on their own once it closes.
+                return Err(format!("command timed out after {}ms", timeout.as_millis()));
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("failed to wait for {}: {}", program, e)),
+        }
+    };
+
+    let (stdout, stdout_truncated) = join_reader(stdout);
+    let (stderr, stderr_truncated) = join_reader(stderr);
+    Ok(ExecOutput {
+        stdout,
+        stderr,
+        exit_code: status.code(),
+        truncated: stdout_truncated || stderr_truncated,
+    })
+}
+
+fn read_capped(mut pipe: impl Read + Send + 'static) -> JoinHandle<(Vec<u8>, bool)> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 8192];
+        let mut truncated = false;
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let room = MAX_OUTPUT_BYTES - buf.len();
+                    if n > room {
+                        truncated = true;
+                    }
+                    buf.extend_from_slice(&chunk[..n.min(room)]);
+                }
+            }
+        }
+        (buf, truncated)
+    })
+}
+
+fn join_reader(reader: Option<JoinHandle<(Vec<u8>, bool)>>) -> (String, bool) {
+    let (bytes, truncated) = reader
+        .and_then(|handle| handle.join().ok())
+        .unwrap_or_default();
+    (String::from_utf8_lossy(&bytes).to_string(), truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    #[test]
+    fn allowlist_requires_exact_argv() {
+        let allowlist = vec![argv(&["gh", "auth", "token"])];
+        assert!(is_allowed(&allowlist, &argv(&["gh", "auth", "token"])));
+        assert!(!is_allowed(&allowlist, &argv(&["gh", "auth"])));
+        assert!(!is_allowed(&allowlist, &argv(&["gh", "auth", "token", "--hostname", "x"])));
+        assert!(!is_allowed(&allowlist, &argv(&["gh auth token"])));
+        assert!(!is_allowed(&allowlist, &[]));
+    }
+
+    #[test]
+    fn timeout_is_clamped() {
+        assert_eq!(ExecOpts::default().timeout(), Duration::from_millis(DEFAULT_TIMEOUT_MS));
+        let opts = ExecOpts { timeout_ms: Some(10 * MAX_TIMEOUT_MS) };
+        assert_eq!(opts.timeout(), Duration::from_millis(MAX_TIMEOUT_MS));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_captures_output_and_exit_code_without_a_shell() {
+        let output = run(&argv(&["sh", "-c", "echo out; echo err >&2; exit 3"]), Duration::from_secs(5))
+            .expect("run");
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+        assert_eq!(output.exit_code, Some(3));
+        assert!(!output.truncated);
+
+        // Arguments reach the program verbatim; nothing is expanded.
+        let output = run(&argv(&["echo", "$HOME", "a;b"]), Duration::from_secs(5)).expect("echo");
+        assert_eq!(output.stdout, "$HOME a;b\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_enforces_timeout_and_output_cap() {
+        let err = run(&argv(&["sleep", "5"]), Duration::from_millis(100)).unwrap_err();
+        assert_eq!(err, "command timed out after 100ms");
+
+        let output = run(&argv(&["head", "-c", "2000000", "/dev/zero"]), Duration::from_secs(5))
+            .expect("head");
+        assert_eq!(output.stdout.len(), MAX_OUTPUT_BYTES);
+        assert!(output.truncated);
+        assert_eq!(output.exit_code, Some(0));
+
+        assert!(run(&argv(&["definitely-not-a-command-openusage"]), Duration::from_secs(1)).is_err());
+    }
+}
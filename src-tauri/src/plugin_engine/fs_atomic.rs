@@ -0,0 +1,274 @@
+//! Crash-safe file replacement for `host.fs.writeText`.
+//!
+//! Plugins rewrite live CLI credential files (`~/.claude/.credentials.json`,
+//! `~/.codex/auth.json`), so a torn write logs the user out. Every write goes
+//! to a temp file in the same directory, is fsynced, and is renamed over the
+//! target, keeping the target's permissions. Optionally:
+//!
+//! - `lock`: serializes writers of the same path across probes and plugins.
+//!   The lock lives in memory, so nothing is left next to files other tools
+//!   own.
+//! - `backup`: the previous contents are copied to the plugin data dir first,
+//!   keeping the newest `MAX_BACKUPS` per path.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+pub const MAX_BACKUPS: usize = 5;
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Paths some writer currently holds, and a signal for when one is released.
+static WRITE_LOCKS: LazyLock<(Mutex<HashSet<PathBuf>>, Condvar)> =
+    LazyLock::new(|| (Mutex::new(HashSet::new()), Condvar::new()));
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteOpts {
+    #[serde(default)]
+    pub lock: bool,
+    #[serde(default)]
+    pub backup: bool,
+}
+
+/// Replace `path` with `content`. `data_dir` holds backup files.
+pub fn write(path: &Path, content: &[u8], opts: WriteOpts, data_dir: &Path) -> Result<(), String> {
+    // Write through symlinks (dotfile managers) instead of replacing the link.
+    let target = match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => path.canonicalize().map_err(|e| e.to_string())?,
+        _ => path.to_path_buf(),
+    };
+    let key = path_key(&target);
+
+    let _lock = if opts.lock {
+        Some(acquire_lock(&target, LOCK_TIMEOUT)?)
+    } else {
+        None
+    };
+
+    if opts.backup && target.is_file() {
+        backup(&target, &data_dir.join("backups"), &key)?;
+    }
+
+    replace(&target, content)
+}
+
+fn replace(target: &Path, content: &[u8]) -> Result<(), String> {
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = target
+        .file_name()
+        .ok_or_else(|| format!("not a file path: {}", target.display()))?
+        .to_string_lossy();
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let temp = dir.join(format!(".{}.{}-{}.tmp", name, std::process::id(), nanos));
+    let existing = std::fs::metadata(target).ok().map(|meta| meta.permissions());
+
+    let result = (|| {
+        let mut file = create_private(&temp)?;
+        file.write_all(content)?;
+        if let Some(permissions) = existing {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp, target)
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.to_string());
+    }
+
+    // Persist the rename itself. Directories can't be opened for sync on
+    // Windows, where rename durability is the filesystem's job.
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// New files are owner-only; existing files keep their mode (set by caller).
+fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Releases the path when dropped.
+#[derive(Debug)]
+struct WriteLock(PathBuf);
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        let (held, released) = &*WRITE_LOCKS;
+        held.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.0);
+        released.notify_all();
+    }
+}
+
+fn acquire_lock(path: &Path, timeout: Duration) -> Result<WriteLock, String> {
+    let (held, released) = &*WRITE_LOCKS;
+    let deadline = Instant::now() + timeout;
+    let mut held = held.lock().unwrap_or_else(|e| e.into_inner());
+    while held.contains(path) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("file is locked by another writer".to_string());
+        }
+        held = released
+            .wait_timeout(held, remaining)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    held.insert(path.to_path_buf());
+    Ok(WriteLock(path.to_path_buf()))
+}
+
+fn backup(target: &Path, backup_dir: &Path, key: &str) -> Result<(), String> {
+    std::fs::create_dir_all(backup_dir).map_err(|e| e.to_string())?;
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let prefix = format!("{}-{}.", name, key);
+    let previous = std::fs::read(target).map_err(|e| e.to_string())?;
+    let backup_path = backup_dir.join(format!("{}{}.bak", prefix, backup_timestamp()));
+    let mut file = create_private(&backup_path).map_err(|e| e.to_string())?;
+    file.write_all(&previous).map_err(|e| e.to_string())?;
+
+    // Timestamps sort lexically, so the oldest backups come first.
+    let mut existing: Vec<PathBuf> = std::fs::read_dir(backup_dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".bak"))
+        })
+        .collect();
+    existing.sort();
+    let excess = existing.len().saturating_sub(MAX_BACKUPS);
+    for old in existing.into_iter().take(excess) {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(())
+}
+
+/// `20260102T030405123456Z`: UTC with microseconds, so names sort by time.
+fn backup_timestamp() -> String {
+    let now = time::OffsetDateTime::now_utc();
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}{:06}Z",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second(),
+        now.microsecond()
+    )
+}
+
+/// Short stable id for a path, so files with the same name in different
+/// directories get separate backups.
+fn path_key(path: &Path) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, path.to_string_lossy().as_bytes());
+    digest.as_ref()[..6]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn write_replaces_contents_and_keeps_bounded_backups() {
+        let root = TempDir::new("fs-atomic-backup");
+        let data_dir = root.join("plugin_data");
+        let path = root.join(".credentials.json");
+        let opts = WriteOpts { lock: true, backup: true };
+
+        write(&path, b"v0", opts, &data_dir).expect("first write");
+        assert!(!data_dir.join("backups").exists());
+        for version in 1..=(MAX_BACKUPS + 2) {
+            write(&path, format!("v{}", version).as_bytes(), opts, &data_dir).expect("write");
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), format!("v{}", MAX_BACKUPS + 2));
+
+        let mut backups: Vec<String> = std::fs::read_dir(data_dir.join("backups"))
+            .unwrap()
+            .flatten()
+            .map(|entry| std::fs::read_to_string(entry.path()).unwrap())
+            .collect();
+        backups.sort();
+        let expected: Vec<String> = (2..=(MAX_BACKUPS + 1)).map(|v| format!("v{}", v)).collect();
+        assert_eq!(backups, expected);
+
+        // No temp files are left next to the target.
+        let siblings: Vec<_> = std::fs::read_dir(&root)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(siblings.is_empty());
+        assert!(!root.join(".credentials.json.lock").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_preserves_mode_and_follows_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = TempDir::new("fs-atomic-mode");
+        let real = root.join("dotfiles-auth.json");
+        let link = root.join("auth.json");
+        std::fs::write(&real, "old").unwrap();
+        std::fs::set_permissions(&real, std::fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        write(&link, b"new", WriteOpts::default(), &root.join("data")).expect("write");
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&real).unwrap(), "new");
+        let mode = std::fs::metadata(&real).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        let fresh = root.join("fresh.json");
+        write(&fresh, b"{}", WriteOpts::default(), &root.join("data")).expect("write fresh");
+        let mode = std::fs::metadata(&fresh).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn lock_times_out_while_another_writer_holds_it() {
+        let root = TempDir::new("fs-atomic-lock");
+        let path = root.join("auth.json");
+        let timeout = Duration::from_millis(100);
+        let held = acquire_lock(&path, timeout).expect("first lock");
+        let started = Instant::now();
+        let err = acquire_lock(&path, timeout).unwrap_err();
+        assert_eq!(err, "file is locked by another writer");
+        assert!(started.elapsed() >= timeout);
+        acquire_lock(&root.join("other.json"), timeout).expect("other path");
+        drop(held);
+        acquire_lock(&path, timeout).expect("lock after release");
+    }
+}
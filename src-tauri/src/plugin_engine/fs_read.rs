@@ -0,0 +1,256 @@
+//! Read-only filesystem queries for `host.fs`: `listDir`, `stat`, `glob` and
+//! `readBytes`. Paths arrive already `~`-expanded. Every call is bounded so a
+//! plugin pointed at a huge directory or file fails fast instead of stalling
+//! the probe.
+
+use base64::Engine;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+pub const MAX_LIST_ENTRIES: usize = 5_000;
+pub const MAX_GLOB_MATCHES: usize = 5_000;
+pub const MAX_GLOB_VISITED: usize = 100_000;
+pub const MAX_READ_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStat {
+    pub size: u64,
+    /// Milliseconds since the Unix epoch; `None` if the platform can't say.
+    pub mtime_ms: Option<f64>,
+    /// Permission bits. On Windows only the read-only flag is reflected.
+    pub mode: u32,
+    pub is_dir: bool,
+}
+
+/// Entry names (not paths) in `dir`, sorted.
+pub fn list_dir(dir: &Path) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if names.len() == MAX_LIST_ENTRIES {
+            return Err(format!(
+                "directory has more than {} entries: {}",
+                MAX_LIST_ENTRIES,
+                dir.display()
+            ));
+        }
+        names.push(entry.file_name().to_string_lossy().to_string());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Follows symlinks, like `exists` and `readText`.
+pub fn stat(path: &Path) -> Result<FileStat, String> {
+    let meta = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let mtime_ms = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_millis() as f64);
+    Ok(FileStat {
+        size: meta.len(),
+        mtime_ms,
+        mode: mode_bits(&meta),
+        is_dir: meta.is_dir(),
+    })
+}
+
+#[cfg(unix)]
+fn mode_bits(meta: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode_bits(meta: &std::fs::Metadata) -> u32 {
+    if meta.permissions().readonly() { 0o444 } else { 0o666 }
+}
+
+/// Matching paths, sorted. Unreadable entries are skipped. Fails once more
+/// than `MAX_GLOB_VISITED` directory entries have been looked at, so a `**`
+/// over a large tree is bounded even when little of it matches.
+pub fn glob(pattern: &str) -> Result<Vec<String>, String> {
+    glob_within(pattern, MAX_GLOB_VISITED)
+}
+
+fn glob_within(pattern: &str, max_visited: usize) -> Result<Vec<String>, String> {
+    glob::Pattern::new(pattern).map_err(|e| format!("invalid glob pattern: {}", e))?;
+    let parts: Vec<&str> = pattern.split(std::path::is_separator).collect();
+    // Literal leading components are joined as-is rather than listed.
+    let literal = parts.iter().take_while(|part| !is_wild(part)).count();
+    let base = parts[..literal].join(std::path::MAIN_SEPARATOR_STR);
+    let mut walk = GlobWalk {
+        pattern,
+        visited: 0,
+        max_visited,
+        matches: Vec::new(),
+    };
+    if literal == parts.len() {
+        if Path::new(pattern).exists() {
+            walk.matches.push(pattern.to_string());
+        }
+    } else {
+        walk.walk(Path::new(&base), &parts[literal..])?;
+    }
+    walk.matches.sort();
+    walk.matches.dedup();
+    Ok(walk.matches)
+}
+
+fn is_wild(part: &str) -> bool {
+    part.contains(['*', '?', '['])
+}
+
+struct GlobWalk<'a> {
+    pattern: &'a str,
+    visited: usize,
+    max_visited: usize,
+    matches: Vec<String>,
+}
+
+impl GlobWalk<'_> {
+    fn walk(&mut self, dir: &Path, parts: &[&str]) -> Result<(), String> {
+        let Some((part, rest)) = parts.split_first() else {
+            return self.push(dir);
+        };
+        if *part == "**" {
+            // Zero directories, then each subdirectory. Symlinked directories
+            // are not descended into, so link cycles can't loop.
+            self.walk(dir, rest)?;
+            for (path, is_dir) in self.read_dir(dir)? {
+                if is_dir {
+                    self.walk(&path, parts)?;
+                }
+            }
+            return Ok(());
+        }
+        if !is_wild(part) {
+            let path = dir.join(part);
+            if rest.is_empty() && path.exists() {
+                return self.push(&path);
+            }
+            if !rest.is_empty() && path.is_dir() {
+                return self.walk(&path, rest);
+            }
+            return Ok(());
+        }
+        let pattern = glob::Pattern::new(part).map_err(|e| format!("invalid glob pattern: {}", e))?;
+        for (path, _) in self.read_dir(dir)? {
+            let matched = path
+                .file_name()
+                .is_some_and(|name| pattern.matches(&name.to_string_lossy()));
+            if !matched {
+                continue;
+            }
+            if rest.is_empty() {
+                self.push(&path)?;
+            } else if path.is_dir() {
+                self.walk(&path, rest)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries of `dir` with whether each is a real (non-symlink) directory.
+    /// An unreadable directory has none.
+    fn read_dir(&mut self, dir: &Path) -> Result<Vec<(std::path::PathBuf, bool)>, String> {
+        let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let Ok(entries) = std::fs::read_dir(listed) else {
+            return Ok(Vec::new());
+        };
+        let mut out = Vec::new();
+        for entry in entries.flatten() {
+            self.visited += 1;
+            if self.visited > self.max_visited {
+                return Err(format!(
+                    "glob visited more than {} entries: {}",
+                    self.max_visited, self.pattern
+                ));
+            }
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            out.push((dir.join(entry.file_name()), is_dir));
+        }
+        Ok(out)
+    }
+
+    fn push(&mut self, path: &Path) -> Result<(), String> {
+        if self.matches.len() == MAX_GLOB_MATCHES {
+            return Err(format!(
+                "glob matched more than {} paths: {}",
+                MAX_GLOB_MATCHES, self.pattern
+            ));
+        }
+        self.matches.push(path.to_string_lossy().to_string());
+        Ok(())
+    }
+}
+
+/// File contents as standard base64.
+pub fn read_bytes(path: &Path) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > MAX_READ_BYTES {
+        return Err(format!(
+            "file is larger than {} bytes: {}",
+            MAX_READ_BYTES,
+            path.display()
+        ));
+    }
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn list_stat_glob_and_read_bytes() {
+        let root = TempDir::new("fs-read-basic");
+        std::fs::create_dir_all(root.join("sessions/2026")).unwrap();
+        std::fs::write(root.join("sessions/2026/b.jsonl"), "{}\n").unwrap();
+        std::fs::write(root.join("sessions/2026/a.jsonl"), "{}\n{}\n").unwrap();
+        std::fs::write(root.join("sessions/notes.txt"), "x").unwrap();
+        std::fs::write(root.join("blob.bin"), [0u8, 159, 146, 150]).unwrap();
+
+        assert_eq!(list_dir(&root.join("sessions")).unwrap(), vec!["2026", "notes.txt"]);
+        assert!(list_dir(&root.join("missing")).is_err());
+
+        let file = stat(&root.join("sessions/2026/a.jsonl")).unwrap();
+        assert_eq!(file.size, 6);
+        assert!(!file.is_dir);
+        assert!(file.mtime_ms.unwrap() > 0.0);
+        assert!(stat(&root.join("sessions")).unwrap().is_dir);
+        assert!(stat(&root.join("missing")).is_err());
+
+        let pattern = format!("{}/sessions/**/*.jsonl", root.display());
+        let matches = glob(&pattern).unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                root.join("sessions/2026/a.jsonl").to_string_lossy().to_string(),
+                root.join("sessions/2026/b.jsonl").to_string_lossy().to_string(),
+            ]
+        );
+        assert!(glob("[").is_err());
+        assert_eq!(glob(&format!("{}/sessions/*", root.display())).unwrap().len(), 2);
+        assert_eq!(glob(&format!("{}/blob.bin", root.display())).unwrap().len(), 1);
+
+        // Entries that don't match still count against the budget.
+        let err = glob_within(&format!("{}/**/*.nothing", root.display()), 3).unwrap_err();
+        assert!(err.starts_with("glob visited more than 3 entries"), "{}", err);
+
+        assert_eq!(read_bytes(&root.join("blob.bin")).unwrap(), "AJ+Slg==");
+    }
+
+    #[test]
+    fn read_bytes_rejects_oversized_files() {
+        let root = TempDir::new("fs-read-large");
+        let path = root.join("big.bin");
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(MAX_READ_BYTES + 1).unwrap();
+        let err = read_bytes(&path).unwrap_err();
+        assert!(err.starts_with("file is larger than"));
+    }
+}
@@ -1,11 +1,63 @@
+use crate::plugin_engine::credential_store::{self, CredentialStore, MemoryStore, PluginKeychain};
+use crate::plugin_engine::crypto::{self, Encoding};
+use crate::plugin_engine::exec::{self, ExecOpts};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::fs_read;
+use crate::plugin_engine::http_cache::{self, HttpCache};
+use crate::plugin_engine::jsonl_tail;
+use crate::plugin_engine::kv_store::PluginStore;
+use crate::plugin_engine::ls_procfs::{self, LsProcess};
+use crate::plugin_engine::manifest::PluginPermissions;
+use crate::plugin_engine::oauth::{self, CredentialSource, OAuthConfig, OAuthManager, SourceLocation};
+use crate::plugin_engine::pricing::{self, PriceTable};
+use crate::plugin_engine::vcr::{HttpVcr, VcrMode, VcrRequest, VcrResponse};
 use base64::Engine;
 use rquickjs::{Ctx, Exception, Function, Object};
-use rusqlite::{Connection, OpenFlags};
-use rusqlite::types::ValueRef;
-use std::path::PathBuf;
+use rquickjs::function::Opt;
+use rusqlite::{Connection, OpenFlags, TransactionBehavior};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 const WHITELISTED_ENV_VARS: [&str; 1] = ["CODEX_HOME"];
 
+/// Per-probe overrides for the host API. `HostOptions::from_env()` is what the
+/// app uses; tests build their own to run plugins hermetically against fake
+/// servers and a throwaway home directory.
+#[derive(Debug, Clone, Default)]
+pub struct HostOptions {
+    pub vcr: Option<VcrMode>,
+    /// `(from, to)` URL prefix rewrites applied before a request is sent.
+    pub url_overrides: Vec<(String, String)>,
+    /// Replaces the user's home directory when expanding `~` paths.
+    pub home_dir: Option<PathBuf>,
+    /// Replaces the process environment for `host.env.get`.
+    pub env: Option<HashMap<String, String>>,
+    /// In-memory keychain contents used instead of the detected credential store.
+    pub keychain: Option<HashMap<String, String>>,
+    /// Replaces the manifest's `permissions.exec` allowlist.
+    pub exec: Option<Vec<Vec<String>>>,
+}
+
+impl HostOptions {
+    pub fn from_env() -> Self {
+        Self {
+            #[cfg(any(test, debug_assertions))]
+            vcr: VcrMode::from_env(),
+            ..Self::default()
+        }
+    }
+
+    /// The keychain override if set, otherwise the platform credential store.
+    pub fn credential_store(&self, app_data_dir: &Path) -> Arc<dyn CredentialStore> {
+        match &self.keychain {
+            Some(items) => Arc::new(MemoryStore::new(items.clone())),
+            None => credential_store::detect(app_data_dir),
+        }
+    }
+}
+
 /// Redact sensitive value to first4...last4 format (UTF-8 safe)
 fn redact_value(value: &str) -> String {
     let chars: Vec<char> = value.chars().collect();
@@ -19,7 +71,7 @@ fn redact_value(value: &str) -> String {
 }
 
 /// Redact sensitive query parameters in URL
-fn redact_url(url: &str) -> String {
+pub(crate) fn redact_url(url: &str) -> String {
     let sensitive_params = [
         "key", "api_key", "apikey", "token", "access_token", "secret",
         "password", "auth", "authorization", "bearer", "credential",
@@ -51,7 +103,7 @@ fn redact_url(url: &str) -> String {
 }
 
 /// Redact sensitive patterns in response body for logging
-fn redact_body(body: &str) -> String {
+pub(crate) fn redact_body(body: &str) -> String {
     let mut result = body.to_string();
     
     // Redact JWTs (eyJ... pattern with dots)
@@ -89,7 +141,7 @@ fn redact_body(body: &str) -> String {
 }
 
 /// Lightweight redaction for plugin log messages (JWT + API key patterns only).
-fn redact_log_message(msg: &str) -> String {
+pub(crate) fn redact_log_message(msg: &str) -> String {
     let mut result = msg.to_string();
     if let Ok(jwt_re) = regex_lite::Regex::new(r"eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+\.[A-Za-z0-9_-]+") {
         result = jwt_re.replace_all(&result, |caps: &regex_lite::Captures| redact_value(&caps[0])).to_string();
@@ -105,6 +157,9 @@ pub fn inject_host_api<'js>(
     plugin_id: &str,
     app_data_dir: &PathBuf,
     app_version: &str,
+    permissions: &PluginPermissions,
+    oauth: Option<&OAuthConfig>,
+    options: &HostOptions,
 ) -> rquickjs::Result<()> {
     let globals = ctx.globals();
     let probe_ctx = Object::new(ctx.clone())?;
@@ -128,15 +183,48 @@ pub fn inject_host_api<'js>(
         plugin_data_dir.to_string_lossy().to_string(),
     )?;
     probe_ctx.set("app", app_obj)?;
+    inject_pricing(ctx, &probe_ctx, plugin_id, app_data_dir)?;
+    inject_crypto(ctx, &probe_ctx)?;
 
     let host = Object::new(ctx.clone())?;
+    let home = options.home_dir.clone();
+    let vcr = options.vcr.as_ref().map(|mode| HttpVcr::new(mode, plugin_id));
     inject_log(ctx, &host, plugin_id)?;
-    inject_fs(ctx, &host)?;
-    inject_env(ctx, &host)?;
-    inject_http(ctx, &host, plugin_id)?;
-    inject_keychain(ctx, &host)?;
-    inject_sqlite(ctx, &host)?;
+    inject_fs(ctx, &host, home.clone(), &plugin_data_dir)?;
+    inject_env(ctx, &host, permissions.env.clone(), options.env.clone())?;
+    let http = Arc::new(HttpHost::new(
+        plugin_id,
+        &plugin_data_dir,
+        vcr,
+        options.url_overrides.clone(),
+    ));
+    inject_http(ctx, &host, Arc::clone(&http))?;
+    let credentials = options.credential_store(app_data_dir);
+    inject_keychain(ctx, &host, plugin_id, Arc::clone(&credentials))?;
+    if let Some(config) = oauth {
+        let location = match &config.source {
+            CredentialSource::File { path } => {
+                SourceLocation::File(PathBuf::from(expand_path(path, home.as_deref())))
+            }
+            CredentialSource::Keychain { service } => SourceLocation::Keychain(service.clone()),
+        };
+        let manager = OAuthManager::new(
+            plugin_id,
+            config.clone(),
+            location,
+            plugin_data_dir.clone(),
+            credentials,
+        );
+        inject_oauth(ctx, &host, manager, http)?;
+    }
+    inject_sqlite(ctx, &host, home, permissions.sqlite_write.clone())?;
     inject_ls(ctx, &host, plugin_id)?;
+    inject_store(ctx, &host, app_data_dir, plugin_id)?;
+    let exec_allowlist = options
+        .exec
+        .clone()
+        .unwrap_or_else(|| permissions.exec.clone());
+    inject_exec(ctx, &host, plugin_id, exec_allowlist)?;
 
     probe_ctx.set("host", host)?;
     globals.set("__openusage_ctx", probe_ctx)?;
@@ -144,6 +232,202 @@ pub fn inject_host_api<'js>(
     Ok(())
 }
 
+/// Sets `ctx.config` to the plugin's resolved settings. Runs after
+/// `inject_host_api`, which creates the probe context.
+pub fn inject_config(ctx: &Ctx<'_>, config: &serde_json::Map<String, serde_json::Value>) -> rquickjs::Result<()> {
+    let probe_ctx: Object = ctx.globals().get("__openusage_ctx")?;
+    let json = serde_json::to_string(config).unwrap_or_else(|_| "{}".to_string());
+    let value = ctx.json_parse(json)?;
+    probe_ctx.set("config", value)
+}
+
+fn inject_pricing<'js>(
+    ctx: &Ctx<'js>,
+    probe_ctx: &Object<'js>,
+    plugin_id: &str,
+    app_data_dir: &Path,
+) -> rquickjs::Result<()> {
+    let (table, warning) = PriceTable::load(app_data_dir);
+    if let Some(warning) = warning {
+        log::warn!("[plugin:{}] ignoring price overrides: {}", plugin_id, warning);
+    }
+    let table = Arc::new(table);
+    let pricing_obj = Object::new(ctx.clone())?;
+    pricing_obj.set("version", table.version.clone())?;
+
+    let prices = Arc::clone(&table);
+    pricing_obj.set(
+        "cost",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>, usage: rquickjs::Value<'js>| -> rquickjs::Result<Option<f64>> {
+                let json = match ctx_inner.json_stringify(usage)? {
+                    Some(json) => json.to_string()?,
+                    None => String::new(),
+                };
+                let usage: pricing::Usage = serde_json::from_str(&json).map_err(|e| {
+                    Exception::throw_message(
+                        &ctx_inner,
+                        &format!("invalid pricing usage: {}", e),
+                    )
+                })?;
+                Ok(prices.cost(&usage))
+            },
+        )?,
+    )?;
+
+    let prices = Arc::clone(&table);
+    pricing_obj.set(
+        "lookup",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>, model: String| -> rquickjs::Result<Option<Object<'js>>> {
+                let Some((id, price)) = prices.lookup(&model) else {
+                    return Ok(None);
+                };
+                let obj = Object::new(ctx_inner.clone())?;
+                obj.set("model", id)?;
+                obj.set("input", price.input)?;
+                obj.set("output", price.output)?;
+                obj.set("cacheRead", price.cache_read.unwrap_or(price.input))?;
+                obj.set("cacheWrite", price.cache_write.unwrap_or(price.input))?;
+                Ok(Some(obj))
+            },
+        )?,
+    )?;
+
+    probe_ctx.set("pricing", pricing_obj)?;
+    Ok(())
+}
+
+fn encoding_arg(ctx: &Ctx<'_>, encoding: Opt<String>) -> rquickjs::Result<Encoding> {
+    Encoding::parse(encoding.0.as_deref()).map_err(|e| Exception::throw_message(ctx, &e))
+}
+
+/// `ctx.crypto`, `ctx.base64` and `ctx.hex`, backed by `crypto.rs`.
+fn inject_crypto<'js>(ctx: &Ctx<'js>, probe_ctx: &Object<'js>) -> rquickjs::Result<()> {
+    let crypto_obj = Object::new(ctx.clone())?;
+
+    crypto_obj.set(
+        "sha256",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, data: String, encoding: Opt<String>| -> rquickjs::Result<String> {
+                let encoding = encoding_arg(&ctx_inner, encoding)?;
+                Ok(encoding.encode(&crypto::sha256(data.as_bytes())))
+            },
+        )?,
+    )?;
+
+    crypto_obj.set(
+        "sha1",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, data: String, encoding: Opt<String>| -> rquickjs::Result<String> {
+                let encoding = encoding_arg(&ctx_inner, encoding)?;
+                Ok(encoding.encode(&crypto::sha1(data.as_bytes())))
+            },
+        )?,
+    )?;
+
+    crypto_obj.set(
+        "hmac",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>,
+                  algorithm: String,
+                  key: String,
+                  data: String,
+                  encoding: Opt<String>|
+                  -> rquickjs::Result<String> {
+                let encoding = encoding_arg(&ctx_inner, encoding)?;
+                let mac = crypto::hmac(&algorithm, key.as_bytes(), data.as_bytes())
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                Ok(encoding.encode(&mac))
+            },
+        )?,
+    )?;
+
+    crypto_obj.set(
+        "randomBytes",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, len: usize, encoding: Opt<String>| -> rquickjs::Result<String> {
+                let encoding = encoding_arg(&ctx_inner, encoding)?;
+                let bytes = crypto::random_bytes(len)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                Ok(encoding.encode(&bytes))
+            },
+        )?,
+    )?;
+
+    crypto_obj.set("uuid", Function::new(ctx.clone(), crypto::uuid_v4)?)?;
+
+    crypto_obj.set(
+        "pkce",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>| -> rquickjs::Result<Object<'js>> {
+                let (verifier, challenge) = crypto::pkce_pair()
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                let obj = Object::new(ctx_inner.clone())?;
+                obj.set("verifier", verifier)?;
+                obj.set("challenge", challenge)?;
+                obj.set("method", "S256")?;
+                Ok(obj)
+            },
+        )?,
+    )?;
+    probe_ctx.set("crypto", crypto_obj)?;
+
+    let base64_obj = Object::new(ctx.clone())?;
+    base64_obj.set(
+        "encode",
+        Function::new(ctx.clone(), |text: String| -> String {
+            Encoding::Base64.encode(text.as_bytes())
+        })?,
+    )?;
+    base64_obj.set(
+        "encodeUrl",
+        Function::new(ctx.clone(), |text: String| -> String {
+            Encoding::Base64Url.encode(text.as_bytes())
+        })?,
+    )?;
+    base64_obj.set(
+        "decode",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, encoded: String| -> rquickjs::Result<String> {
+                let bytes = crypto::base64_decode(&encoded)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                Ok(String::from_utf8_lossy(&bytes).to_string())
+            },
+        )?,
+    )?;
+    probe_ctx.set("base64", base64_obj)?;
+
+    let hex_obj = Object::new(ctx.clone())?;
+    hex_obj.set(
+        "encode",
+        Function::new(ctx.clone(), |text: String| -> String {
+            crypto::hex_encode(text.as_bytes())
+        })?,
+    )?;
+    hex_obj.set(
+        "decode",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, encoded: String| -> rquickjs::Result<String> {
+                let bytes = crypto::hex_decode(&encoded)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                Ok(String::from_utf8_lossy(&bytes).to_string())
+            },
+        )?,
+    )?;
+    probe_ctx.set("hex", hex_obj)?;
+    Ok(())
+}
+
 fn inject_log<'js>(
     ctx: &Ctx<'js>,
     host: &Object<'js>,
@@ -179,23 +463,30 @@ fn inject_log<'js>(
     Ok(())
 }
 
-fn inject_fs<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
+fn inject_fs<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    home: Option<PathBuf>,
+    plugin_data_dir: &Path,
+) -> rquickjs::Result<()> {
     let fs_obj = Object::new(ctx.clone())?;
 
+    let home_dir = home.clone();
     fs_obj.set(
         "exists",
         Function::new(ctx.clone(), move |path: String| -> bool {
-            let expanded = expand_path(&path);
+            let expanded = expand_path(&path, home_dir.as_deref());
             std::path::Path::new(&expanded).exists()
         })?,
     )?;
 
+    let home_dir = home.clone();
     fs_obj.set(
         "readText",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<String> {
-                let expanded = expand_path(&path);
+                let expanded = expand_path(&path, home_dir.as_deref());
                 std::fs::read_to_string(&expanded).map_err(|e| {
                     Exception::throw_message(&ctx_inner, &e.to_string())
                 })
@@ -203,148 +494,166 @@ fn inject_fs<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
         )?,
     )?;
 
+    let home_dir = home.clone();
     fs_obj.set(
-        "writeText",
+        "readBytes",
         Function::new(
             ctx.clone(),
-            move |ctx_inner: Ctx<'_>, path: String, content: String| -> rquickjs::Result<()> {
-                let expanded = expand_path(&path);
-                std::fs::write(&expanded, &content).map_err(|e| {
-                    Exception::throw_message(&ctx_inner, &e.to_string())
+            move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<String> {
+                let expanded = expand_path(&path, home_dir.as_deref());
+                fs_read::read_bytes(Path::new(&expanded))
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
+
+    let home_dir = home.clone();
+    fs_obj.set(
+        "listDir",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, path: String| -> rquickjs::Result<Vec<String>> {
+                let expanded = expand_path(&path, home_dir.as_deref());
+                fs_read::list_dir(Path::new(&expanded))
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
+
+    let home_dir = home.clone();
+    fs_obj.set(
+        "stat",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>, path: String| -> rquickjs::Result<Object<'js>> {
+                let expanded = expand_path(&path, home_dir.as_deref());
+                let stat = fs_read::stat(Path::new(&expanded))
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                let obj = Object::new(ctx_inner.clone())?;
+                obj.set("size", stat.size as f64)?;
+                obj.set("mtimeMs", stat.mtime_ms)?;
+                obj.set("mode", stat.mode)?;
+                obj.set("isDir", stat.is_dir)?;
+                Ok(obj)
+            },
+        )?,
+    )?;
+
+    let home_dir = home.clone();
+    fs_obj.set(
+        "glob",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, pattern: String| -> rquickjs::Result<Vec<String>> {
+                let expanded = expand_path(&pattern, home_dir.as_deref());
+                fs_read::glob(&expanded).map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
+
+    let home_dir = home.clone();
+    let data_dir = plugin_data_dir.to_path_buf();
+    fs_obj.set(
+        "_tailJsonlRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, pattern: String, checkpoint_key: String| -> rquickjs::Result<String> {
+                let expanded = expand_path(&pattern, home_dir.as_deref());
+                let result = jsonl_tail::tail(&expanded, &checkpoint_key, &data_dir)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                serde_json::to_string(&result).map_err(|e| {
+                    Exception::throw_message(
+                        &ctx_inner,
+                        &format!("serialize failed: {}", e),
+                    )
                 })
             },
         )?,
     )?;
 
+    let data_dir = plugin_data_dir.to_path_buf();
+    fs_obj.set(
+        "writeText",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>,
+                  path: String,
+                  content: String,
+                  opts: Opt<rquickjs::Value<'js>>|
+                  -> rquickjs::Result<()> {
+                let opts: WriteOpts = match opts.0 {
+                    Some(value) if !value.is_undefined() && !value.is_null() => {
+                        let json = match ctx_inner.json_stringify(value)? {
+                            Some(json) => json.to_string()?,
+                            None => String::new(),
+                        };
+                        serde_json::from_str(&json).map_err(|e| {
+                            Exception::throw_message(
+                                &ctx_inner,
+                                &format!("invalid writeText opts: {}", e),
+                            )
+                        })?
+                    }
+                    _ => WriteOpts::default(),
+                };
+                let expanded = expand_path(&path, home.as_deref());
+                fs_atomic::write(Path::new(&expanded), content.as_bytes(), opts, &data_dir)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
+
     host.set("fs", fs_obj)?;
     Ok(())
 }
 
-fn inject_env<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
+/// Reads `name` if it is always allowed or declared in `permissions.env`.
+/// `overrides` replaces the process environment (tests).
+pub(crate) fn read_env(
+    name: &str,
+    declared: &[String],
+    overrides: Option<&HashMap<String, String>>,
+) -> Option<String> {
+    if !WHITELISTED_ENV_VARS.contains(&name) && !declared.iter().any(|entry| entry == name) {
+        return None;
+    }
+    match overrides {
+        Some(env) => env.get(name).cloned(),
+        None => std::env::var(name).ok(),
+    }
+}
+
+fn inject_env<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    declared: Vec<String>,
+    env: Option<HashMap<String, String>>,
+) -> rquickjs::Result<()> {
     let env_obj = Object::new(ctx.clone())?;
     env_obj.set(
         "get",
         Function::new(ctx.clone(), move |name: String| -> Option<String> {
-            if WHITELISTED_ENV_VARS.contains(&name.as_str()) {
-                std::env::var(&name).ok()
-            } else {
-                None
-            }
+            read_env(&name, &declared, env.as_ref())
         })?,
     )?;
     host.set("env", env_obj)?;
     Ok(())
 }
 
-fn inject_http<'js>(ctx: &Ctx<'js>, host: &Object<'js>, plugin_id: &str) -> rquickjs::Result<()> {
+fn inject_http<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    http: Arc<HttpHost>,
+) -> rquickjs::Result<()> {
     let http_obj = Object::new(ctx.clone())?;
-    let pid = plugin_id.to_string();
 
     http_obj.set(
         "_requestRaw",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, req_json: String| -> rquickjs::Result<String> {
-                let req: HttpReqParams = serde_json::from_str(&req_json).map_err(|e| {
-                    Exception::throw_message(&ctx_inner, &format!("invalid request: {}", e))
-                })?;
-
-                let method_str = req.method.as_deref().unwrap_or("GET");
-                let redacted_url = redact_url(&req.url);
-                log::info!("[plugin:{}] HTTP {} {}", pid, method_str, redacted_url);
-
-                let mut header_map = reqwest::header::HeaderMap::new();
-                if let Some(headers) = &req.headers {
-                    for (key, val) in headers {
-                        let name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
-                            .map_err(|e| {
-                                Exception::throw_message(
-                                    &ctx_inner,
-                                    &format!("invalid header name '{}': {}", key, e),
-                                )
-                            })?;
-                        let value = reqwest::header::HeaderValue::from_str(val).map_err(|e| {
-                            Exception::throw_message(
-                                &ctx_inner,
-                                &format!("invalid header value for '{}': {}", key, e),
-                            )
-                        })?;
-                        header_map.insert(name, value);
-                    }
-                }
-
-                let timeout_ms = req.timeout_ms.unwrap_or(10_000);
-                let mut builder = reqwest::blocking::Client::builder()
-                    .timeout(std::time::Duration::from_millis(timeout_ms))
-                    .redirect(reqwest::redirect::Policy::none());
-                if req.dangerously_ignore_tls.unwrap_or(false) {
-                    builder = builder.danger_accept_invalid_certs(true);
-                }
-                let client = builder
-                    .build()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                let method = req.method.as_deref().unwrap_or("GET");
-                let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("invalid http method '{}': {}", method, e),
-                    )
-                })?;
-                let mut builder = client.request(method, &req.url);
-                builder = builder.headers(header_map);
-                if let Some(body) = req.body_text {
-                    builder = builder.body(body);
-                }
-
-                let response = builder
-                    .send()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                let status = response.status().as_u16();
-                let mut resp_headers = std::collections::HashMap::new();
-                for (key, value) in response.headers().iter() {
-                    let header_value = value.to_str().map_err(|e| {
-                        Exception::throw_message(
-                            &ctx_inner,
-                            &format!("invalid response header '{}': {}", key, e),
-                        )
-                    })?;
-                    resp_headers.insert(key.to_string(), header_value.to_string());
-                }
-                let body = response
-                    .text()
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))?;
-
-                // Redact BEFORE truncation to ensure sensitive values are caught while intact
-                let redacted_body = redact_body(&body);
-                let body_preview = if redacted_body.len() > 500 {
-                    // UTF-8 safe truncation: find valid char boundary at or before 500
-                    let truncated: String = redacted_body.char_indices()
-                        .take_while(|(i, _)| *i < 500)
-                        .map(|(_, c)| c)
-                        .collect();
-                    format!("{}... ({} bytes total)", truncated, body.len())
-                } else {
-                    redacted_body
-                };
-                log::info!(
-                    "[plugin:{}] HTTP {} {} -> {} | {}",
-                    pid,
-                    method_str,
-                    redacted_url,
-                    status,
-                    body_preview
-                );
-
-                let resp = HttpRespParams {
-                    status,
-                    headers: resp_headers,
-                    body_text: body,
-                };
-
-                serde_json::to_string(&resp)
-                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+                http.request(&req_json)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
             },
         )?,
     )?;
@@ -378,7 +687,9 @@ pub fn patch_http_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                     headers: req.headers || null,
                     bodyText: req.bodyText || null,
                     timeoutMs: req.timeoutMs || 10000,
-                    dangerouslyIgnoreTls: req.dangerouslyIgnoreTls || false
+                    dangerouslyIgnoreTls: req.dangerouslyIgnoreTls || false,
+                    retry: req.retry || null,
+                    cache: req.cache === true ? {} : (req.cache || null)
                 });
                 var respJson = rawFn(json);
                 return JSON.parse(respJson);
@@ -389,7 +700,7 @@ pub fn patch_http_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     )
 }
 
-/// Inject utility APIs (line builders, formatters, base64, jwt) onto __openusage_ctx
+/// Inject utility APIs (line builders, formatters, jwt) onto __openusage_ctx
 pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     ctx.eval::<(), _>(
         r#"
@@ -610,49 +921,6 @@ pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                 }
             };
 
-            // Base64
-            var b64chars = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-            ctx.base64 = {
-                decode: function(str) {
-                    str = str.replace(/-/g, "+").replace(/_/g, "/");
-                    while (str.length % 4) str += "=";
-                    str = str.replace(/=+$/, "");
-                    var result = "";
-                    var len = str.length;
-                    var i = 0;
-                    while (i < len) {
-                        var remaining = len - i;
-                        var a = b64chars.indexOf(str.charAt(i++));
-                        var b = b64chars.indexOf(str.charAt(i++));
-                        var c = remaining > 2 ? b64chars.indexOf(str.charAt(i++)) : 0;
-                        var d = remaining > 3 ? b64chars.indexOf(str.charAt(i++)) : 0;
-                        var n = (a << 18) | (b << 12) | (c << 6) | d;
-                        result += String.fromCharCode((n >> 16) & 0xff);
-                        if (remaining > 2) result += String.fromCharCode((n >> 8) & 0xff);
-                        if (remaining > 3) result += String.fromCharCode(n & 0xff);
-                    }
-                    return result;
-                },
-                encode: function(str) {
-                    var result = "";
-                    var len = str.length;
-                    var i = 0;
-                    while (i < len) {
-                        var chunkStart = i;
-                        var a = str.charCodeAt(i++);
-                        var b = i < len ? str.charCodeAt(i++) : 0;
-                        var c = i < len ? str.charCodeAt(i++) : 0;
-                        var bytesInChunk = i - chunkStart;
-                        var n = (a << 16) | (b << 8) | c;
-                        result += b64chars.charAt((n >> 18) & 63);
-                        result += b64chars.charAt((n >> 12) & 63);
-                        result += bytesInChunk < 2 ? "=" : b64chars.charAt((n >> 6) & 63);
-                        result += bytesInChunk < 3 ? "=" : b64chars.charAt(n & 63);
-                    }
-                    return result;
-                }
-            };
-
             // JWT
             ctx.jwt = {
                 decodePayload: function(token) {
@@ -672,257 +940,553 @@ pub fn inject_utils(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     )
 }
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct HttpReqParams {
-    url: String,
-    method: Option<String>,
-    headers: Option<std::collections::HashMap<String, String>>,
-    body_text: Option<String>,
-    timeout_ms: Option<u64>,
-    dangerously_ignore_tls: Option<bool>,
-}
-
-#[derive(serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct HttpRespParams {
-    status: u16,
-    headers: std::collections::HashMap<String, String>,
-    body_text: String,
+/// Backs `host.http.request`: takes the request as JSON and returns the
+/// response as JSON. Shared with the WASM host.
+pub(crate) struct HttpHost {
+    plugin_id: String,
+    cache: HttpCache,
+    vcr: Option<HttpVcr>,
+    url_overrides: Vec<(String, String)>,
 }
 
-// --- Language Server Discovery ---
+impl HttpHost {
+    pub(crate) fn new(
+        plugin_id: &str,
+        plugin_data_dir: &Path,
+        vcr: Option<HttpVcr>,
+        url_overrides: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            cache: HttpCache::new(plugin_data_dir),
+            vcr,
+            url_overrides,
+        }
+    }
 
-#[derive(serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct LsDiscoverOpts {
-    process_name: String,
-    markers: Vec<String>,
-    csrf_flag: String,
-    port_flag: Option<String>,
-    extra_flags: Option<Vec<String>>,
-}
+    pub(crate) fn request(&self, req_json: &str) -> Result<String, String> {
+        let pid = &self.plugin_id;
+        let (cache, vcr) = (&self.cache, &self.vcr);
+        let mut req: HttpReqParams =
+            serde_json::from_str(req_json).map_err(|e| format!("invalid request: {}", e))?;
+        req.url = apply_url_overrides(&req.url, &self.url_overrides);
 
-#[derive(serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-struct LsDiscoverResult {
-    pid: i32,
-    csrf: String,
-    ports: Vec<i32>,
-    extra: std::collections::HashMap<String, String>,
-    extension_port: Option<i32>,
-}
+        let method_str = req.method.as_deref().unwrap_or("GET");
+        let redacted_url = redact_url(&req.url);
 
-fn inject_ls<'js>(
-    ctx: &Ctx<'js>,
-    host: &Object<'js>,
-    plugin_id: &str,
-) -> rquickjs::Result<()> {
-    let ls_obj = Object::new(ctx.clone())?;
-    let pid = plugin_id.to_string();
+        let mut header_map = reqwest::header::HeaderMap::new();
+        if let Some(headers) = &req.headers {
+            for (key, val) in headers {
+                let name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
+                    .map_err(|e| {
+                        format!("invalid header name '{}': {}", key, e)
+                    })?;
+                let value = reqwest::header::HeaderValue::from_str(val).map_err(|e| {
+                    format!("invalid header value for '{}': {}", key, e)
+                })?;
+                header_map.insert(name, value);
+            }
+        }
 
-    ls_obj.set(
-        "_discoverRaw",
-        Function::new(
-            ctx.clone(),
-            move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
-                let opts: LsDiscoverOpts = serde_json::from_str(&opts_json).map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("invalid discover opts: {}", e),
-                    )
-                })?;
+        let timeout_ms = req.timeout_ms.unwrap_or(10_000);
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(std::time::Duration::from_millis(timeout_ms))
+            .redirect(reqwest::redirect::Policy::none());
+        if req.dangerously_ignore_tls.unwrap_or(false) {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let method = reqwest::Method::from_bytes(method_str.as_bytes()).map_err(|e| {
+            format!("invalid http method '{}': {}", method_str, e)
+        })?;
+        let policy = HttpRetryPolicy::from_opts(req.retry.as_ref(), &method);
+
+        if let Some(vcr) = vcr.as_ref().filter(|vcr| vcr.is_replay()) {
+            let replayed = vcr.replay(method_str, &req.url)?;
+            log::info!(
+                "[plugin:{}] HTTP {} {} -> {} (replayed)",
+                pid,
+                method_str,
+                redacted_url,
+                replayed.status
+            );
+            let resp = HttpRespParams {
+                status: replayed.status,
+                headers: replayed.headers,
+                body_text: replayed.body_text,
+            };
+            return serde_json::to_string(&resp)
+                .map_err(|e| e.to_string());
+        }
+        let vcr_request = || VcrRequest {
+            method: method_str.to_string(),
+            url: req.url.clone(),
+            headers: req.headers.clone().unwrap_or_default(),
+            body_text: req.body_text.clone(),
+        };
+
+        // The cache would hide requests from a recording, so it is off under VCR.
+        let cache_key = req.cache.as_ref().filter(|_| vcr.is_none()).map(|_| {
+            HttpCache::key(
+                method_str,
+                &req.url,
+                req.headers.as_ref(),
+                req.body_text.as_deref(),
+            )
+        });
+        let ttl_override_ms = req.cache.as_ref().and_then(|c| c.ttl_ms);
+        let cached = cache_key.as_deref().and_then(|key| cache.load(key));
+        if let Some(entry) = &cached {
+            if entry.is_fresh(http_cache::now_ms(), ttl_override_ms) {
+                log::info!(
+                    "[plugin:{}] HTTP {} {} -> {} (cached)",
+                    pid,
+                    method_str,
+                    redacted_url,
+                    entry.status
+                );
+                let resp = HttpRespParams {
+                    status: entry.status,
+                    headers: entry.headers.clone(),
+                    body_text: entry.body_text.clone(),
+                };
+                return serde_json::to_string(&resp)
+                    .map_err(|e| e.to_string());
+            }
+            // Stale: revalidate unless the plugin set its own validators.
+            if let Some(etag) = &entry.etag
+                && !header_map.contains_key(reqwest::header::IF_NONE_MATCH)
+                && let Ok(value) = reqwest::header::HeaderValue::from_str(etag)
+            {
+                header_map.insert(reqwest::header::IF_NONE_MATCH, value);
+            }
+            if let Some(last_modified) = &entry.last_modified
+                && !header_map.contains_key(reqwest::header::IF_MODIFIED_SINCE)
+                && let Ok(value) = reqwest::header::HeaderValue::from_str(last_modified)
+            {
+                header_map.insert(reqwest::header::IF_MODIFIED_SINCE, value);
+            }
+        }
 
+        let mut attempt: u32 = 1;
+        let resp = loop {
+            if attempt == 1 {
+                log::info!("[plugin:{}] HTTP {} {}", pid, method_str, redacted_url);
+            } else {
                 log::info!(
-                    "[plugin:{}] LS discover: processName={}, markers={:?}",
+                    "[plugin:{}] HTTP {} {} (attempt {}/{})",
                     pid,
-                    opts.process_name,
-                    opts.markers
+                    method_str,
+                    redacted_url,
+                    attempt,
+                    policy.max_attempts
                 );
+            }
 
-                let ps_output = match std::process::Command::new("/bin/ps")
-                    .args(["-ax", "-o", "pid=,command="])
-                    .output()
-                {
-                    Ok(o) => o,
-                    Err(e) => {
-                        log::warn!("[plugin:{}] ps failed: {}", pid, e);
-                        return Ok("null".to_string());
+            let mut builder = client.request(method.clone(), &req.url);
+            builder = builder.headers(header_map.clone());
+            if let Some(body) = &req.body_text {
+                builder = builder.body(body.clone());
+            }
+
+            let response = match builder.send().and_then(|response| {
+                let status = response.status().as_u16();
+                let headers = response.headers().clone();
+                response.text().map(|body| (status, headers, body))
+            }) {
+                Ok(response) => response,
+                Err(e) => {
+                    if attempt < policy.max_attempts && http_error_is_transient(&e) {
+                        let delay = policy.backoff_delay(attempt);
+                        log::warn!(
+                            "[plugin:{}] HTTP {} {} failed: {}; retrying in {}ms",
+                            pid,
+                            method_str,
+                            redacted_url,
+                            e,
+                            delay.as_millis()
+                        );
+                        std::thread::sleep(delay);
+                        attempt += 1;
+                        continue;
                     }
-                };
+                    if let Some(vcr) = &vcr {
+                        vcr.record(vcr_request(), Err(&e.to_string()));
+                    }
+                    return Err(e.to_string());
+                }
+            };
+            let (status, headers, body) = response;
+
+            let mut resp_headers = std::collections::HashMap::new();
+            for (key, value) in headers.iter() {
+                let header_value = value.to_str().map_err(|e| {
+                    format!("invalid response header '{}': {}", key, e)
+                })?;
+                resp_headers.insert(key.to_string(), header_value.to_string());
+            }
 
-                if !ps_output.status.success() {
-                    log::warn!("[plugin:{}] ps returned non-zero", pid);
-                    return Ok("null".to_string());
+            log::info!(
+                "[plugin:{}] HTTP {} {} -> {} | {}",
+                pid,
+                method_str,
+                redacted_url,
+                status,
+                http_body_preview(&body)
+            );
+
+            if attempt < policy.max_attempts && policy.statuses.contains(&status) {
+                let retry_after = resp_headers
+                    .get("retry-after")
+                    .and_then(|value| parse_retry_after(value, time::OffsetDateTime::now_utc()));
+                if let Some(delay) = policy.delay_for(attempt, retry_after) {
+                    log::warn!(
+                        "[plugin:{}] HTTP {} {} -> {}; retrying in {}ms",
+                        pid,
+                        method_str,
+                        redacted_url,
+                        status,
+                        delay.as_millis()
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                    continue;
                 }
+                log::warn!(
+                    "[plugin:{}] HTTP {} {} -> {}; Retry-After exceeds max backoff, not retrying",
+                    pid,
+                    method_str,
+                    redacted_url,
+                    status
+                );
+            }
 
-                let ps_stdout = String::from_utf8_lossy(&ps_output.stdout);
-                let process_name_lower = opts.process_name.to_lowercase();
-                let markers_lower: Vec<String> =
-                    opts.markers.iter().map(|m| m.to_lowercase()).collect();
-
-                // Find the target process. Marker patterns are Codeium-derived.
-                // Matching priority:
-                //   1. Exact --ide_name / --app_data_dir flag value (prevents
-                //      "windsurf" matching "windsurf-next")
-                //   2. Path substring (/<marker>/) as fallback when no flags found
-                let mut found: Option<(i32, String)> = None;
-
-                for line in ps_stdout.lines() {
-                    let trimmed = line.trim();
-                    if trimmed.is_empty() {
-                        continue;
-                    }
+            break HttpRespParams {
+                status,
+                headers: resp_headers,
+                body_text: body,
+            };
+        };
+
+        if let Some(vcr) = &vcr {
+            let recorded = VcrResponse {
+                status: resp.status,
+                headers: resp.headers.clone(),
+                body_text: resp.body_text.clone(),
+            };
+            vcr.record(vcr_request(), Ok(&recorded));
+        }
 
-                    let mut parts = trimmed.splitn(2, char::is_whitespace);
-                    let pid_str = match parts.next() {
-                        Some(s) => s.trim(),
-                        None => continue,
-                    };
-                    let command = match parts.next() {
-                        Some(s) => s.trim(),
-                        None => continue,
-                    };
+        let resp = match (cache_key.as_deref(), cached) {
+            (Some(key), Some(entry)) if resp.status == 304 && entry.has_validators() => {
+                log::info!(
+                    "[plugin:{}] HTTP {} {} -> 304, serving cached {}",
+                    pid,
+                    method_str,
+                    redacted_url,
+                    entry.status
+                );
+                let entry = cache.refresh(key, entry, &resp.headers, http_cache::now_ms());
+                HttpRespParams {
+                    status: entry.status,
+                    headers: entry.headers,
+                    body_text: entry.body_text,
+                }
+            }
+            (Some(key), _) if resp.status == 200 => {
+                cache.store(key, resp.status, &resp.headers, &resp.body_text, http_cache::now_ms());
+                resp
+            }
+            _ => resp,
+        };
 
-                    let command_lower = command.to_lowercase();
+        serde_json::to_string(&resp)
+            .map_err(|e| e.to_string())
+    }
+}
 
-                    if !command_lower.contains(&process_name_lower) {
-                        continue;
-                    }
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HttpReqParams {
+    url: String,
+    method: Option<String>,
+    headers: Option<std::collections::HashMap<String, String>>,
+    body_text: Option<String>,
+    timeout_ms: Option<u64>,
+    dangerously_ignore_tls: Option<bool>,
+    retry: Option<HttpRetryOpts>,
+    cache: Option<HttpCacheOpts>,
+}
 
-                    let ide_name = ls_extract_flag(command, "--ide_name")
-                        .map(|v| v.to_lowercase());
-                    let app_data = ls_extract_flag(command, "--app_data_dir")
-                        .map(|v| v.to_lowercase());
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HttpCacheOpts {
+    ttl_ms: Option<u64>,
+}
 
-                    let has_marker = markers_lower.iter().any(|m| {
-                        // Prefer exact flag match; skip path fallback when
-                        // a distinguishing flag exists.
-                        if let Some(ref name) = ide_name {
-                            return *name == *m;
-                        }
-                        if let Some(ref dir) = app_data {
-                            return *dir == *m;
-                        }
-                        // Fallback: path substring
-                        command_lower.contains(&format!("/{}/", m))
-                    });
-                    if !has_marker {
-                        continue;
-                    }
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HttpRetryOpts {
+    max_attempts: Option<u32>,
+    backoff_ms: Option<u64>,
+    max_backoff_ms: Option<u64>,
+    statuses: Option<Vec<u16>>,
+    idempotent_only: Option<bool>,
+}
 
-                    if let Ok(p) = pid_str.parse::<i32>() {
-                        found = Some((p, command.to_string()));
-                        break;
-                    }
-                }
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HttpRespParams {
+    status: u16,
+    headers: std::collections::HashMap<String, String>,
+    body_text: String,
+}
+
+const HTTP_RETRY_MAX_ATTEMPTS: u32 = 5;
+const HTTP_RETRY_DEFAULT_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+/// Resolved retry settings for a single `host.http.request` call.
+struct HttpRetryPolicy {
+    max_attempts: u32,
+    backoff_ms: u64,
+    max_backoff_ms: u64,
+    statuses: Vec<u16>,
+}
+
+impl HttpRetryPolicy {
+    /// No `retry` option means a single attempt. Non-idempotent methods are
+    /// only retried when the plugin opts out with `idempotentOnly: false`.
+    fn from_opts(opts: Option<&HttpRetryOpts>, method: &reqwest::Method) -> Self {
+        let Some(opts) = opts else {
+            return Self {
+                max_attempts: 1,
+                backoff_ms: 0,
+                max_backoff_ms: 0,
+                statuses: Vec::new(),
+            };
+        };
+
+        let idempotent = matches!(
+            *method,
+            reqwest::Method::GET
+                | reqwest::Method::HEAD
+                | reqwest::Method::OPTIONS
+                | reqwest::Method::PUT
+                | reqwest::Method::DELETE
+                | reqwest::Method::TRACE
+        );
+        let max_attempts = if opts.idempotent_only.unwrap_or(true) && !idempotent {
+            1
+        } else {
+            opts.max_attempts.unwrap_or(3).clamp(1, HTTP_RETRY_MAX_ATTEMPTS)
+        };
+        let backoff_ms = opts.backoff_ms.unwrap_or(500);
+        let max_backoff_ms = opts.max_backoff_ms.unwrap_or(8_000).max(backoff_ms);
+
+        Self {
+            max_attempts,
+            backoff_ms,
+            max_backoff_ms,
+            statuses: opts
+                .statuses
+                .clone()
+                .unwrap_or_else(|| HTTP_RETRY_DEFAULT_STATUSES.to_vec()),
+        }
+    }
+
+    /// Exponential backoff: backoffMs, 2x, 4x, ... capped at maxBackoffMs.
+    fn backoff_delay(&self, attempt: u32) -> std::time::Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        let ms = self.backoff_ms.saturating_mul(factor).min(self.max_backoff_ms);
+        std::time::Duration::from_millis(ms)
+    }
+
+    /// Delay before retrying a retryable status. A server-provided Retry-After
+    /// wins over backoff; if it asks for longer than maxBackoffMs we give up
+    /// and hand the response to the plugin instead of blocking the probe.
+    fn delay_for(
+        &self,
+        attempt: u32,
+        retry_after: Option<std::time::Duration>,
+    ) -> Option<std::time::Duration> {
+        match retry_after {
+            Some(delay) if delay.as_millis() > u128::from(self.max_backoff_ms) => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff_delay(attempt)),
+        }
+    }
+}
+
+/// Parse a Retry-After header (delta-seconds or HTTP-date).
+fn parse_retry_after(value: &str, now: time::OffsetDateTime) -> Option<std::time::Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(seconds));
+    }
+    let at = time::OffsetDateTime::parse(value, &time::format_description::well_known::Rfc2822).ok()?;
+    let delta = at - now;
+    if delta.is_negative() {
+        Some(std::time::Duration::ZERO)
+    } else {
+        std::time::Duration::try_from(delta).ok()
+    }
+}
+
+/// Connection failures, timeouts and resets are worth retrying; anything else
+/// (bad URL, TLS config, body encoding) will fail the same way again.
+fn http_error_is_transient(err: &reqwest::Error) -> bool {
+    if err.is_connect() || err.is_timeout() {
+        return true;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(inner) = source {
+        if let Some(io_err) = inner.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_err.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = inner.source();
+    }
+    false
+}
+
+/// Redacted, truncated response body for logging.
+fn http_body_preview(body: &str) -> String {
+    // Redact BEFORE truncation to ensure sensitive values are caught while intact
+    let redacted_body = redact_body(body);
+    if redacted_body.len() > 500 {
+        // UTF-8 safe truncation: find valid char boundary at or before 500
+        let truncated: String = redacted_body
+            .char_indices()
+            .take_while(|(i, _)| *i < 500)
+            .map(|(_, c)| c)
+            .collect();
+        format!("{}... ({} bytes total)", truncated, body.len())
+    } else {
+        redacted_body
+    }
+}
+
+// --- Language Server Discovery ---
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LsDiscoverOpts {
+    process_name: String,
+    markers: Vec<String>,
+    csrf_flag: String,
+    port_flag: Option<String>,
+    extra_flags: Option<Vec<String>>,
+    /// Flags that identify the workspace an instance serves.
+    workspace_flags: Option<Vec<String>>,
+}
+
+/// Workspace-identifying flags reported when `workspaceFlags` is not given.
+const LS_DEFAULT_WORKSPACE_FLAGS: [&str; 2] = ["--workspace_id", "--app_data_dir"];
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LsDiscoverResult {
+    pid: i32,
+    csrf: String,
+    ports: Vec<i32>,
+    extra: std::collections::HashMap<String, String>,
+    extension_port: Option<i32>,
+    started_at_ms: Option<u64>,
+    workspace: std::collections::HashMap<String, String>,
+}
+
+fn inject_ls<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    plugin_id: &str,
+) -> rquickjs::Result<()> {
+    let ls_obj = Object::new(ctx.clone())?;
+
+    let pid = plugin_id.to_string();
+    ls_obj.set(
+        "_discoverRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
+                let opts: LsDiscoverOpts = serde_json::from_str(&opts_json).map_err(|e| {
+                    Exception::throw_message(
+                        &ctx_inner,
+                        &format!("invalid discover opts: {}", e),
+                    )
+                })?;
+
+                log::info!(
+                    "[plugin:{}] LS discover: processName={}, markers={:?}",
+                    pid,
+                    opts.process_name,
+                    opts.markers
+                );
 
-                let (process_pid, command) = match found {
-                    Some(pair) => pair,
+                let processes = match ls_list_processes(&pid) {
+                    Some(processes) => processes,
+                    None => return Ok("null".to_string()),
+                };
+                let process = match ls_matching_processes(processes, &opts).into_iter().next() {
+                    Some(process) => process,
                     None => {
                         log::info!("[plugin:{}] LS process not found", pid);
                         return Ok("null".to_string());
                     }
                 };
-
-                // Extract CSRF token
-                let csrf = match ls_extract_flag(&command, &opts.csrf_flag) {
-                    Some(c) => c,
-                    None => {
-                        log::warn!(
-                            "[plugin:{}] CSRF token not found in process args",
-                            pid
-                        );
-                        return Ok("null".to_string());
-                    }
+                let result = match ls_describe_process(&pid, process, &opts) {
+                    Some(result) => result,
+                    None => return Ok("null".to_string()),
                 };
 
-                // Extract extension port (optional)
-                let extension_port = opts
-                    .port_flag
-                    .as_ref()
-                    .and_then(|flag| {
-                        ls_extract_flag(&command, flag)
-                            .and_then(|v| v.parse::<i32>().ok())
-                    });
-
-                // Extract extra flags (optional)
-                let mut extra = std::collections::HashMap::new();
-                if let Some(ref flags) = opts.extra_flags {
-                    for flag in flags {
-                        if let Some(val) = ls_extract_flag(&command, flag) {
-                            // Use flag name without leading dashes as key
-                            let key = flag.trim_start_matches('-').to_string();
-                            extra.insert(key, val);
-                        }
-                    }
-                }
-
-                // Find lsof binary
-                let lsof_path = ["/usr/sbin/lsof", "/usr/bin/lsof"]
-                    .iter()
-                    .find(|p| std::path::Path::new(p).exists())
-                    .copied();
-
-                let ports = if let Some(lsof) = lsof_path {
-                    match std::process::Command::new(lsof)
-                        .args([
-                            "-nP",
-                            "-iTCP",
-                            "-sTCP:LISTEN",
-                            "-a",
-                            "-p",
-                            &process_pid.to_string(),
-                        ])
-                        .output()
-                    {
-                        Ok(o) if o.status.success() => {
-                            ls_parse_listening_ports(
-                                &String::from_utf8_lossy(&o.stdout),
-                            )
-                        }
-                        Ok(_) => {
-                            log::warn!(
-                                "[plugin:{}] lsof returned non-zero",
-                                pid
-                            );
-                            Vec::new()
-                        }
-                        Err(e) => {
-                            log::warn!("[plugin:{}] lsof failed: {}", pid, e);
-                            Vec::new()
-                        }
-                    }
-                } else {
-                    log::warn!("[plugin:{}] lsof not found", pid);
-                    Vec::new()
-                };
+                serde_json::to_string(&result).map_err(|e| {
+                    Exception::throw_message(
+                        &ctx_inner,
+                        &format!("serialize failed: {}", e),
+                    )
+                })
+            },
+        )?,
+    )?;
 
-                if ports.is_empty() && extension_port.is_none() {
-                    log::warn!(
-                        "[plugin:{}] no listening ports found for pid {}",
-                        pid,
-                        process_pid
-                    );
-                    return Ok("null".to_string());
-                }
+    let pid = plugin_id.to_string();
+    ls_obj.set(
+        "_discoverAllRaw",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, opts_json: String| -> rquickjs::Result<String> {
+                let opts: LsDiscoverOpts = serde_json::from_str(&opts_json).map_err(|e| {
+                    Exception::throw_message(
+                        &ctx_inner,
+                        &format!("invalid discover opts: {}", e),
+                    )
+                })?;
 
                 log::info!(
-                    "[plugin:{}] LS found: pid={}, ports={:?}, csrf=[REDACTED]",
+                    "[plugin:{}] LS discoverAll: processName={}, markers={:?}",
                     pid,
-                    process_pid,
-                    ports
+                    opts.process_name,
+                    opts.markers
                 );
 
-                let result = LsDiscoverResult {
-                    pid: process_pid,
-                    csrf,
-                    ports,
-                    extra,
-                    extension_port,
-                };
+                let processes = ls_list_processes(&pid).unwrap_or_default();
+                let mut results: Vec<LsDiscoverResult> = ls_matching_processes(processes, &opts)
+                    .into_iter()
+                    .filter_map(|process| ls_describe_process(&pid, process, &opts))
+                    .collect();
+                // Newest first: a freshly opened window beats a stale one.
+                results.sort_by_key(|result| std::cmp::Reverse(result.started_at_ms));
 
-                serde_json::to_string(&result).map_err(|e| {
+                log::info!("[plugin:{}] LS discoverAll found {} instance(s)", pid, results.len());
+
+                serde_json::to_string(&results).map_err(|e| {
                     Exception::throw_message(
                         &ctx_inner,
                         &format!("serialize failed: {}", e),
@@ -936,6 +1500,110 @@ fn inject_ls<'js>(
     Ok(())
 }
 
+/// Processes matching `processName` and one of the markers, in listing order.
+fn ls_matching_processes(processes: Vec<LsProcess>, opts: &LsDiscoverOpts) -> Vec<LsProcess> {
+    let process_name_lower = opts.process_name.to_lowercase();
+    let markers_lower: Vec<String> = opts.markers.iter().map(|m| m.to_lowercase()).collect();
+
+    // Marker patterns are Codeium-derived. Matching priority:
+    //   1. Exact --ide_name / --app_data_dir flag value (prevents
+    //      "windsurf" matching "windsurf-next")
+    //   2. Path substring (/<marker>/) as fallback when no flags found
+    processes
+        .into_iter()
+        .filter(|process| {
+            let command_lower = process.command().to_lowercase();
+            if !command_lower.contains(&process_name_lower) {
+                return false;
+            }
+
+            let ide_name = ls_extract_flag(&process.argv, "--ide_name").map(|v| v.to_lowercase());
+            let app_data =
+                ls_extract_flag(&process.argv, "--app_data_dir").map(|v| v.to_lowercase());
+
+            markers_lower.iter().any(|m| {
+                // Prefer exact flag match; skip path fallback when
+                // a distinguishing flag exists.
+                if let Some(ref name) = ide_name {
+                    return *name == *m;
+                }
+                if let Some(ref dir) = app_data {
+                    return *dir == *m;
+                }
+                // Fallback: path substring
+                command_lower.contains(&format!("/{}/", m))
+            })
+        })
+        .collect()
+}
+
+/// Read CSRF token, flags and listening ports for a matched process. `None`
+/// when the process has no CSRF token or nothing to connect to.
+fn ls_describe_process(
+    plugin_id: &str,
+    process: LsProcess,
+    opts: &LsDiscoverOpts,
+) -> Option<LsDiscoverResult> {
+    let csrf = match ls_extract_flag(&process.argv, &opts.csrf_flag) {
+        Some(c) => c,
+        None => {
+            log::warn!(
+                "[plugin:{}] CSRF token not found in args of pid {}",
+                plugin_id,
+                process.pid
+            );
+            return None;
+        }
+    };
+
+    let extension_port = opts.port_flag.as_ref().and_then(|flag| {
+        ls_extract_flag(&process.argv, flag).and_then(|v| v.parse::<i32>().ok())
+    });
+
+    // Flag values keyed by flag name without leading dashes.
+    let collect_flags = |flags: &[String]| {
+        flags
+            .iter()
+            .filter_map(|flag| {
+                ls_extract_flag(&process.argv, flag)
+                    .map(|val| (flag.trim_start_matches('-').to_string(), val))
+            })
+            .collect::<std::collections::HashMap<String, String>>()
+    };
+    let extra = collect_flags(opts.extra_flags.as_deref().unwrap_or_default());
+    let workspace = match &opts.workspace_flags {
+        Some(flags) => collect_flags(flags),
+        None => collect_flags(&LS_DEFAULT_WORKSPACE_FLAGS.map(str::to_string)),
+    };
+
+    let ports = ls_listening_ports(plugin_id, process.pid);
+    if ports.is_empty() && extension_port.is_none() {
+        log::warn!(
+            "[plugin:{}] no listening ports found for pid {}",
+            plugin_id,
+            process.pid
+        );
+        return None;
+    }
+
+    log::info!(
+        "[plugin:{}] LS found: pid={}, ports={:?}, csrf=[REDACTED]",
+        plugin_id,
+        process.pid,
+        ports
+    );
+
+    Some(LsDiscoverResult {
+        pid: process.pid,
+        csrf,
+        ports,
+        extra,
+        extension_port,
+        started_at_ms: process.started_at_ms,
+        workspace,
+    })
+}
+
 pub fn patch_ls_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
     ctx.eval::<(), _>(
         r#"
@@ -948,21 +1616,135 @@ pub fn patch_ls_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
                 if (json === "null") return null;
                 return JSON.parse(json);
             };
+            var rawAllFn = __openusage_ctx.host.ls._discoverAllRaw;
+            __openusage_ctx.host.ls.discoverAll = function(opts) {
+                var optsJson;
+                try { optsJson = JSON.stringify(opts); } catch (e) { return []; }
+                return JSON.parse(rawAllFn(optsJson));
+            };
         })();
         "#
         .as_bytes(),
     )
 }
 
-/// Extract value of a CLI flag from a command string.
+/// Running processes with their argv. Linux reads `/proc` directly so argument
+/// boundaries survive; elsewhere (or without `/proc`) this falls back to `ps`,
+/// whose output can only be split on whitespace.
+fn ls_list_processes(plugin_id: &str) -> Option<Vec<LsProcess>> {
+    if cfg!(target_os = "linux")
+        && let Some(processes) = ls_procfs::list_processes(Path::new("/proc"))
+    {
+        return Some(processes);
+    }
+
+    let ps_output = match std::process::Command::new("/bin/ps")
+        .args(["-ax", "-o", "pid=,etime=,command="])
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => {
+            log::warn!("[plugin:{}] ps failed: {}", plugin_id, e);
+            return None;
+        }
+    };
+    if !ps_output.status.success() {
+        log::warn!("[plugin:{}] ps returned non-zero", plugin_id);
+        return None;
+    }
+    Some(ls_parse_ps_output(
+        &String::from_utf8_lossy(&ps_output.stdout),
+        http_cache::now_ms(),
+    ))
+}
+
+/// Parse `ps -ax -o pid=,etime=,command=` output.
+fn ls_parse_ps_output(output: &str, now_ms: u64) -> Vec<LsProcess> {
+    let mut processes = Vec::new();
+    for line in output.lines() {
+        // Columns are space-padded to the widest value, e.g. "  123       05:12 /path/ls".
+        let Some((pid_str, rest)) = line.trim_start().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some((etime, command)) = rest.trim_start().split_once(char::is_whitespace) else {
+            continue;
+        };
+        if let Ok(pid) = pid_str.parse::<i32>() {
+            processes.push(LsProcess {
+                pid,
+                argv: command.split_whitespace().map(str::to_string).collect(),
+                started_at_ms: ls_parse_etime_secs(etime)
+                    .map(|secs| now_ms.saturating_sub(secs * 1000)),
+            });
+        }
+    }
+    processes
+}
+
+/// Parse ps `etime` (`[[dd-]hh:]mm:ss`) into seconds.
+fn ls_parse_etime_secs(etime: &str) -> Option<u64> {
+    let (days, clock) = match etime.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, etime),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(days * 86_400 + secs)
+}
+
+/// Listening TCP ports of `process_pid`, from `/proc` on Linux or `lsof` elsewhere.
+fn ls_listening_ports(plugin_id: &str, process_pid: i32) -> Vec<i32> {
+    if cfg!(target_os = "linux")
+        && let Some(ports) = ls_procfs::listening_ports(Path::new("/proc"), process_pid)
+    {
+        return ports;
+    }
+
+    // Find lsof binary
+    let lsof_path = ["/usr/sbin/lsof", "/usr/bin/lsof"]
+        .iter()
+        .find(|p| std::path::Path::new(p).exists())
+        .copied();
+
+    let Some(lsof) = lsof_path else {
+        log::warn!("[plugin:{}] lsof not found", plugin_id);
+        return Vec::new();
+    };
+    match std::process::Command::new(lsof)
+        .args([
+            "-nP",
+            "-iTCP",
+            "-sTCP:LISTEN",
+            "-a",
+            "-p",
+            &process_pid.to_string(),
+        ])
+        .output()
+    {
+        Ok(o) if o.status.success() => {
+            ls_parse_listening_ports(&String::from_utf8_lossy(&o.stdout))
+        }
+        Ok(_) => {
+            log::warn!("[plugin:{}] lsof returned non-zero", plugin_id);
+            Vec::new()
+        }
+        Err(e) => {
+            log::warn!("[plugin:{}] lsof failed: {}", plugin_id, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Extract value of a CLI flag from a process's argv.
 /// Handles both `--flag value` and `--flag=value` forms.
-fn ls_extract_flag(command: &str, flag: &str) -> Option<String> {
-    let parts: Vec<&str> = command.split_whitespace().collect();
+fn ls_extract_flag(argv: &[String], flag: &str) -> Option<String> {
     let flag_eq = format!("{}=", flag);
-    for (i, part) in parts.iter().enumerate() {
+    for (i, part) in argv.iter().enumerate() {
         if *part == flag {
-            if i + 1 < parts.len() {
-                return Some(parts[i + 1].to_string());
+            if i + 1 < argv.len() {
+                return Some(argv[i + 1].clone());
             }
         } else if part.starts_with(&flag_eq) {
             return Some(part[flag_eq.len()..].to_string());
@@ -995,232 +1777,334 @@ fn ls_parse_listening_ports(output: &str) -> Vec<i32> {
     ports.into_iter().collect()
 }
 
-fn inject_keychain<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
+fn inject_keychain<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    plugin_id: &str,
+    store: Arc<dyn CredentialStore>,
+) -> rquickjs::Result<()> {
     let keychain_obj = Object::new(ctx.clone())?;
+    log::debug!("[plugin:{}] keychain backend: {}", plugin_id, store.name());
+    let store: Arc<dyn CredentialStore> = Arc::new(PluginKeychain(store));
 
+    let read_store = Arc::clone(&store);
     keychain_obj.set(
         "readGenericPassword",
         Function::new(
             ctx.clone(),
             move |ctx_inner: Ctx<'_>, service: String| -> rquickjs::Result<String> {
-                if !cfg!(target_os = "macos") {
-                    if service == "gh:github.com" {
-                        let output = std::process::Command::new("gh")
-                            .args(["auth", "token"])
-                            .output()
-                            .map_err(|e| {
-                                Exception::throw_message(
-                                    &ctx_inner,
-                                    &format!("gh auth token failed: {}", e),
-                                )
-                            })?;
-
-                        if !output.status.success() {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            let first_line = stderr.lines().next().unwrap_or("").trim();
-                            return Err(Exception::throw_message(
-                                &ctx_inner,
-                                &format!("gh auth token failed: {}", first_line),
-                            ));
-                        }
+                read_store
+                    .read(&service)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+            },
+        )?,
+    )?;
 
-                        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                        if token.is_empty() {
-                            return Err(Exception::throw_message(
-                                &ctx_inner,
-                                "gh auth token returned empty output",
-                            ));
-                        }
+    let write_store = Arc::clone(&store);
+    keychain_obj.set(
+        "writeGenericPassword",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, service: String, value: String| -> rquickjs::Result<()> {
+                write_store
+                    .write(&service, &value)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+            },
+        )?,
+    )?;
 
-                        return Ok(token);
-                    }
+    let delete_store = Arc::clone(&store);
+    keychain_obj.set(
+        "deleteGenericPassword",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, service: String| -> rquickjs::Result<()> {
+                delete_store
+                    .delete(&service)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+            },
+        )?,
+    )?;
 
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "keychain API is only supported on macOS",
-                    ));
-                }
-                let output = std::process::Command::new("security")
-                    .args(["find-generic-password", "-s", &service, "-w"])
-                    .output()
-                    .map_err(|e| {
-                        Exception::throw_message(
-                            &ctx_inner,
-                            &format!("keychain read failed: {}", e),
-                        )
-                    })?;
+    keychain_obj.set(
+        "listGenericPasswords",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, prefix: String| -> rquickjs::Result<Vec<String>> {
+                store
+                    .list(&prefix)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e.to_string()))
+            },
+        )?,
+    )?;
 
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let first_line = stderr.lines().next().unwrap_or("").trim();
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        &format!("keychain item not found: {}", first_line),
-                    ));
+    host.set("keychain", keychain_obj)?;
+    Ok(())
+}
+
+fn inject_store<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    app_data_dir: &Path,
+    plugin_id: &str,
+) -> rquickjs::Result<()> {
+    let store = Arc::new(PluginStore::new(app_data_dir, plugin_id));
+    let store_obj = Object::new(ctx.clone())?;
+
+    let kv = Arc::clone(&store);
+    store_obj.set(
+        "get",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>, key: String| -> rquickjs::Result<rquickjs::Value<'js>> {
+                match kv.get(&key).map_err(|e| Exception::throw_message(&ctx_inner, &e))? {
+                    Some(json) => ctx_inner.json_parse(json),
+                    None => Ok(rquickjs::Value::new_null(ctx_inner.clone())),
                 }
+            },
+        )?,
+    )?;
 
-                Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    let kv = Arc::clone(&store);
+    store_obj.set(
+        "set",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>,
+                  key: String,
+                  value: rquickjs::Value<'js>,
+                  opts: Opt<rquickjs::Value<'js>>|
+                  -> rquickjs::Result<()> {
+                let json = match ctx_inner.json_stringify(value)? {
+                    Some(json) => json.to_string()?,
+                    None => {
+                        return Err(Exception::throw_message(
+                            &ctx_inner,
+                            "store value must be JSON-serializable",
+                        ));
+                    }
+                };
+                let ttl_ms = match opts.0 {
+                    Some(opts) if opts.is_object() => opts
+                        .as_object()
+                        .and_then(|obj| obj.get::<_, Option<f64>>("ttlMs").ok().flatten()),
+                    _ => None,
+                };
+                let ttl = ttl_ms.map(|ms| std::time::Duration::from_millis(ms.max(0.0) as u64));
+                kv.set(&key, &json, ttl)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
             },
         )?,
     )?;
 
-    keychain_obj.set(
-        "writeGenericPassword",
+    let kv = Arc::clone(&store);
+    store_obj.set(
+        "delete",
         Function::new(
             ctx.clone(),
-            move |ctx_inner: Ctx<'_>, service: String, value: String| -> rquickjs::Result<()> {
-                if !cfg!(target_os = "macos") {
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "keychain API is only supported on macOS",
-                    ));
-                }
+            move |ctx_inner: Ctx<'_>, key: String| -> rquickjs::Result<bool> {
+                kv.delete(&key)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
 
-                // First, try to find existing entry and extract its account
-                let mut account_arg: Option<String> = None;
-                let find_output = std::process::Command::new("security")
-                    .args(["find-generic-password", "-s", &service])
-                    .output();
-
-                if let Ok(output) = find_output {
-                    if output.status.success() {
-                        // Parse account from output: "acct"<blob>="value"
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        for line in stdout.lines() {
-                            if let Some(start) = line.find("\"acct\"<blob>=\"") {
-                                let rest = &line[start + 14..];
-                                if let Some(end) = rest.find('"') {
-                                    account_arg = Some(rest[..end].to_string());
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
+    store_obj.set(
+        "list",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, prefix: Opt<String>| -> rquickjs::Result<Vec<String>> {
+                store
+                    .list(prefix.0.as_deref().unwrap_or(""))
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
 
-                // Build command with account if found
-                let output = if let Some(ref acct) = account_arg {
-                    std::process::Command::new("security")
-                        .args([
-                            "add-generic-password",
-                            "-s",
-                            &service,
-                            "-a",
-                            acct,
-                            "-w",
-                            &value,
-                            "-U",
-                        ])
-                        .output()
-                } else {
-                    std::process::Command::new("security")
-                        .args([
-                            "add-generic-password",
-                            "-s",
-                            &service,
-                            "-w",
-                            &value,
-                            "-U",
-                        ])
-                        .output()
-                }
-                .map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("keychain write failed: {}", e),
-                    )
-                })?;
+    host.set("store", store_obj)?;
+    Ok(())
+}
+
+fn inject_oauth<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    manager: OAuthManager,
+    http: Arc<HttpHost>,
+) -> rquickjs::Result<()> {
+    let manager = Arc::new(manager);
+    let transport = Arc::new(oauth::http_transport(http));
+    let oauth_obj = Object::new(ctx.clone())?;
+
+    let (oauth, send) = (Arc::clone(&manager), Arc::clone(&transport));
+    oauth_obj.set(
+        "accessToken",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>| -> rquickjs::Result<Option<String>> {
+                oauth
+                    .access_token(&*send)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
+
+    let (oauth, send) = (Arc::clone(&manager), transport);
+    oauth_obj.set(
+        "refresh",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'_>, rejected: Opt<Option<String>>| -> rquickjs::Result<String> {
+                oauth
+                    .refresh(rejected.0.flatten().as_deref(), &*send)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
+            },
+        )?,
+    )?;
 
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let first_line = stderr.lines().next().unwrap_or("").trim();
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        &format!("keychain write failed: {}", first_line),
-                    ));
+    oauth_obj.set(
+        "credentials",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>| -> rquickjs::Result<rquickjs::Value<'js>> {
+                match manager
+                    .credentials()
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?
+                {
+                    Some(creds) => ctx_inner.json_parse(creds.to_string()),
+                    None => Ok(rquickjs::Value::new_null(ctx_inner.clone())),
                 }
-
-                Ok(())
             },
         )?,
     )?;
 
-    host.set("keychain", keychain_obj)?;
+    host.set("oauth", oauth_obj)?;
     Ok(())
 }
 
-fn inject_sqlite<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()> {
-    let sqlite_obj = Object::new(ctx.clone())?;
-
-    sqlite_obj.set(
-        "query",
+fn inject_exec<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    plugin_id: &str,
+    allowlist: Vec<Vec<String>>,
+) -> rquickjs::Result<()> {
+    let pid = plugin_id.to_string();
+    host.set(
+        "_execRaw",
         Function::new(
             ctx.clone(),
-            move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<String> {
-                if sql.lines().any(|line| line.trim_start().starts_with('.')) {
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "sqlite3 dot-commands are not allowed",
-                    ));
-                }
-                let expanded = expand_path(&db_path);
-                let uri_path = sqlite_readonly_uri(&expanded);
-                let conn = Connection::open_with_flags(
-                    &uri_path,
-                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
-                )
-                .map_err(|e| {
+            move |ctx_inner: Ctx<'_>, argv_json: String, opts_json: String| -> rquickjs::Result<String> {
+                let argv: Vec<String> = serde_json::from_str(&argv_json).map_err(|e| {
                     Exception::throw_message(
                         &ctx_inner,
-                        &format!("sqlite open failed: {}", e),
+                        &format!("invalid exec command: {}", e),
                     )
                 })?;
-
-                let mut stmt = conn.prepare(&sql).map_err(|e| {
+                let opts: ExecOpts = serde_json::from_str(&opts_json).map_err(|e| {
                     Exception::throw_message(
                         &ctx_inner,
-                        &format!("sqlite prepare failed: {}", e),
+                        &format!("invalid exec opts: {}", e),
                     )
                 })?;
-                let column_count = stmt.column_count();
-                let column_names: Vec<String> = stmt
-                    .column_names()
-                    .iter()
-                    .map(|name| (*name).to_string())
-                    .collect();
+                let command = redact_log_message(&argv.join(" "));
 
-                let mut rows = stmt.query([]).map_err(|e| {
-                    Exception::throw_message(
+                if !exec::is_allowed(&allowlist, &argv) {
+                    log::warn!("[plugin:{}] exec blocked: {}", pid, command);
+                    return Err(Exception::throw_message(
                         &ctx_inner,
-                        &format!("sqlite query failed: {}", e),
-                    )
+                        &format!("command not allowed by plugin manifest: {}", command),
+                    ));
+                }
+
+                let started = std::time::Instant::now();
+                let output = exec::run(&argv, opts.timeout()).map_err(|e| {
+                    log::warn!("[plugin:{}] exec {} failed: {}", pid, command, e);
+                    Exception::throw_message(&ctx_inner, &e)
                 })?;
+                log::info!(
+                    "[plugin:{}] exec {} -> exit {:?}, {} bytes stdout, {} bytes stderr ({}ms)",
+                    pid,
+                    command,
+                    output.exit_code,
+                    output.stdout.len(),
+                    output.stderr.len(),
+                    started.elapsed().as_millis()
+                );
 
-                let mut out = Vec::<serde_json::Value>::new();
-                while let Some(row) = rows.next().map_err(|e| {
+                serde_json::to_string(&output).map_err(|e| {
                     Exception::throw_message(
                         &ctx_inner,
-                        &format!("sqlite rows failed: {}", e),
+                        &format!("serialize failed: {}", e),
                     )
-                })? {
-                    let mut object = serde_json::Map::with_capacity(column_count);
-                    for index in 0..column_count {
-                        let key = column_names
-                            .get(index)
-                            .cloned()
-                            .unwrap_or_else(|| format!("column{}", index));
-                        let value_ref = row.get_ref(index).map_err(|e| {
-                            Exception::throw_message(
-                                &ctx_inner,
-                                &format!("sqlite value failed: {}", e),
-                            )
-                        })?;
-                        object.insert(key, sqlite_value_ref_to_json(value_ref));
-                    }
-                    out.push(serde_json::Value::Object(object));
-                }
+                })
+            },
+        )?,
+    )?;
+    Ok(())
+}
+
+pub fn patch_fs_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(
+        r#"
+        (function() {
+            var fs = __openusage_ctx.host.fs;
+            var rawFn = fs._tailJsonlRaw;
+            fs.tailJsonl = function(pattern, checkpointKey) {
+                return JSON.parse(rawFn(pattern, checkpointKey));
+            };
+        })();
+        "#
+        .as_bytes(),
+    )
+}
+
+pub fn patch_exec_wrapper(ctx: &rquickjs::Ctx<'_>) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(
+        r#"
+        (function() {
+            var rawFn = __openusage_ctx.host._execRaw;
+            __openusage_ctx.host.exec = function(argv, opts) {
+                return JSON.parse(rawFn(JSON.stringify(argv), JSON.stringify(opts || {})));
+            };
+        })();
+        "#
+        .as_bytes(),
+    )
+}
+
+pub(crate) fn apply_url_overrides(url: &str, overrides: &[(String, String)]) -> String {
+    for (from, to) in overrides {
+        if let Some(rest) = url.strip_prefix(from.as_str()) {
+            return format!("{}{}", to, rest);
+        }
+    }
+    url.to_string()
+}
+
+/// How long `host.sqlite.exec` waits for an editor holding the write lock.
+const SQLITE_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn inject_sqlite<'js>(
+    ctx: &Ctx<'js>,
+    host: &Object<'js>,
+    home: Option<PathBuf>,
+    write_allowlist: Vec<String>,
+) -> rquickjs::Result<()> {
+    let sqlite_obj = Object::new(ctx.clone())?;
 
-                serde_json::to_string(&out).map_err(|e| {
+    let home_dir = home.clone();
+    sqlite_obj.set(
+        "query",
+        Function::new(
+            ctx.clone(),
+            move |ctx_inner: Ctx<'js>,
+                  db_path: String,
+                  sql: String,
+                  params: Opt<rquickjs::Value<'js>>|
+                  -> rquickjs::Result<String> {
+                let params = sqlite_params_to_json(&ctx_inner, params)?;
+                let rows = sqlite_query(&db_path, &sql, params.as_ref(), home_dir.as_deref())
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))?;
+                serde_json::to_string(&rows).map_err(|e| {
                     Exception::throw_message(
                         &ctx_inner,
                         &format!("sqlite json encode failed: {}", e),
@@ -1234,32 +2118,14 @@ fn inject_sqlite<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()
         "exec",
         Function::new(
             ctx.clone(),
-            move |ctx_inner: Ctx<'_>, db_path: String, sql: String| -> rquickjs::Result<()> {
-                if sql.lines().any(|line| line.trim_start().starts_with('.')) {
-                    return Err(Exception::throw_message(
-                        &ctx_inner,
-                        "sqlite3 dot-commands are not allowed",
-                    ));
-                }
-                let expanded = expand_path(&db_path);
-                let conn = Connection::open_with_flags(
-                    &expanded,
-                    OpenFlags::SQLITE_OPEN_READ_WRITE,
-                )
-                .map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("sqlite open failed: {}", e),
-                    )
-                })?;
-                conn.execute_batch(&sql).map_err(|e| {
-                    Exception::throw_message(
-                        &ctx_inner,
-                        &format!("sqlite exec failed: {}", e),
-                    )
-                })?;
-
-                Ok(())
+            move |ctx_inner: Ctx<'js>,
+                  db_path: String,
+                  sql: String,
+                  params: Opt<rquickjs::Value<'js>>|
+                  -> rquickjs::Result<()> {
+                let params = sqlite_params_to_json(&ctx_inner, params)?;
+                sqlite_exec(&db_path, &sql, params.as_ref(), home.as_deref(), &write_allowlist)
+                    .map_err(|e| Exception::throw_message(&ctx_inner, &e))
             },
         )?,
     )?;
@@ -1268,6 +2134,196 @@ fn inject_sqlite<'js>(ctx: &Ctx<'js>, host: &Object<'js>) -> rquickjs::Result<()
     Ok(())
 }
 
+/// Backs `host.sqlite.query`: runs `sql` on a read-only, immutable
+/// connection and returns one JSON object per row.
+pub(crate) fn sqlite_query(
+    db_path: &str,
+    sql: &str,
+    params: Option<&serde_json::Value>,
+    home: Option<&Path>,
+) -> Result<Vec<serde_json::Value>, String> {
+    sqlite_reject_dot_commands(sql)?;
+    let expanded = expand_path(db_path, home);
+    let conn = Connection::open_with_flags(
+        sqlite_readonly_uri(&expanded),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+    )
+    .map_err(|e| format!("sqlite open failed: {}", e))?;
+
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("sqlite prepare failed: {}", e))?;
+    if let Some(params) = params {
+        sqlite_bind_params(&mut stmt, params)?;
+    }
+    let column_count = stmt.column_count();
+    let column_names: Vec<String> = stmt
+        .column_names()
+        .iter()
+        .map(|name| (*name).to_string())
+        .collect();
+
+    let mut rows = stmt.raw_query();
+    let mut out = Vec::<serde_json::Value>::new();
+    while let Some(row) = rows.next().map_err(|e| format!("sqlite query failed: {}", e))? {
+        let mut object = serde_json::Map::with_capacity(column_count);
+        for index in 0..column_count {
+            let key = column_names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("column{}", index));
+            let value_ref = row
+                .get_ref(index)
+                .map_err(|e| format!("sqlite value failed: {}", e))?;
+            object.insert(key, sqlite_value_ref_to_json(value_ref));
+        }
+        out.push(serde_json::Value::Object(object));
+    }
+    Ok(out)
+}
+
+/// Backs `host.sqlite.exec`: checks `db_path` against the manifest's
+/// `sqliteWrite` allowlist, then runs `sql` in a transaction.
+pub(crate) fn sqlite_exec(
+    db_path: &str,
+    sql: &str,
+    params: Option<&serde_json::Value>,
+    home: Option<&Path>,
+    write_allowlist: &[String],
+) -> Result<(), String> {
+    sqlite_reject_dot_commands(sql)?;
+    let expanded = expand_path(db_path, home);
+    if !sqlite_write_allowed(write_allowlist, &expanded, home) {
+        return Err(format!("sqlite write not allowed by plugin manifest: {}", db_path));
+    }
+    sqlite_exec_in_transaction(&expanded, sql, params)
+}
+
+fn sqlite_reject_dot_commands(sql: &str) -> Result<(), String> {
+    if sql.lines().any(|line| line.trim_start().starts_with('.')) {
+        return Err("sqlite3 dot-commands are not allowed".to_string());
+    }
+    Ok(())
+}
+
+/// Runs `sql` in an IMMEDIATE transaction so a half-applied script never
+/// reaches the editor. With params, `sql` must be a single statement.
+fn sqlite_exec_in_transaction(
+    path: &str,
+    sql: &str,
+    params: Option<&serde_json::Value>,
+) -> Result<(), String> {
+    let mut conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| format!("sqlite open failed: {}", e))?;
+    conn.busy_timeout(SQLITE_BUSY_TIMEOUT)
+        .map_err(|e| format!("sqlite open failed: {}", e))?;
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|e| format!("sqlite begin failed: {}", e))?;
+    match params {
+        Some(params) => {
+            let mut stmt = tx
+                .prepare(sql)
+                .map_err(|e| format!("sqlite prepare failed: {}", e))?;
+            sqlite_bind_params(&mut stmt, params)?;
+            stmt.raw_execute()
+                .map_err(|e| format!("sqlite exec failed: {}", e))?;
+        }
+        None => tx
+            .execute_batch(sql)
+            .map_err(|e| format!("sqlite exec failed: {}", e))?,
+    }
+    tx.commit().map_err(|e| format!("sqlite commit failed: {}", e))
+}
+
+/// `permissions.sqliteWrite` entries are expanded like any other path and
+/// compared both literally and after resolving symlinks.
+fn sqlite_write_allowed(allowlist: &[String], expanded: &str, home: Option<&Path>) -> bool {
+    let canonical = Path::new(expanded).canonicalize().ok();
+    allowlist.iter().any(|declared| {
+        let declared = expand_path(declared, home);
+        declared == expanded
+            || (canonical.is_some() && Path::new(&declared).canonicalize().ok() == canonical)
+    })
+}
+
+/// `undefined` and `null` mean "no params"; anything else goes through JSON.
+fn sqlite_params_to_json<'js>(
+    ctx: &Ctx<'js>,
+    params: Opt<rquickjs::Value<'js>>,
+) -> rquickjs::Result<Option<serde_json::Value>> {
+    let Some(params) = params.0.filter(|value| !value.is_undefined() && !value.is_null()) else {
+        return Ok(None);
+    };
+    let json = match ctx.json_stringify(params)? {
+        Some(json) => json.to_string()?,
+        None => String::new(),
+    };
+    match serde_json::from_str::<serde_json::Value>(&json) {
+        Ok(value @ (serde_json::Value::Array(_) | serde_json::Value::Object(_))) => Ok(Some(value)),
+        _ => Err(Exception::throw_message(
+            ctx,
+            "sqlite params must be an array or an object",
+        )),
+    }
+}
+
+/// An array binds `?` / `?N` in order; an object binds `:name`, `@name` or
+/// `$name` (keys may include the prefix or leave it off).
+fn sqlite_bind_params(
+    stmt: &mut rusqlite::Statement<'_>,
+    params: &serde_json::Value,
+) -> Result<(), String> {
+    let expected = stmt.parameter_count();
+    match params {
+        serde_json::Value::Array(values) => {
+            if values.len() != expected {
+                return Err(format!(
+                    "sqlite expected {} params, got {}",
+                    expected,
+                    values.len()
+                ));
+            }
+            for (index, value) in values.iter().enumerate() {
+                stmt.raw_bind_parameter(index + 1, sqlite_json_to_value(value)?)
+                    .map_err(|e| format!("sqlite bind failed: {}", e))?;
+            }
+        }
+        serde_json::Value::Object(values) => {
+            for (name, value) in values {
+                let index = if name.starts_with([':', '@', '$']) {
+                    stmt.parameter_index(name)
+                } else {
+                    [":", "@", "$"]
+                        .iter()
+                        .map(|prefix| stmt.parameter_index(&format!("{}{}", prefix, name)))
+                        .find(|index| !matches!(index, Ok(None)))
+                        .unwrap_or(Ok(None))
+                }
+                .map_err(|e| format!("sqlite bind failed: {}", e))?
+                .ok_or_else(|| format!("unknown sqlite param: {}", name))?;
+                stmt.raw_bind_parameter(index, sqlite_json_to_value(value)?)
+                    .map_err(|e| format!("sqlite bind failed: {}", e))?;
+            }
+        }
+        _ => return Err("sqlite params must be an array or an object".to_string()),
+    }
+    Ok(())
+}
+
+fn sqlite_json_to_value(value: &serde_json::Value) -> Result<SqlValue, String> {
+    Ok(match value {
+        serde_json::Value::Null => SqlValue::Null,
+        serde_json::Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(text) => SqlValue::Text(text.clone()),
+        _ => return Err("sqlite params must be strings, numbers, booleans or null".to_string()),
+    })
+}
+
 fn sqlite_readonly_uri(expanded_path: &str) -> String {
     // Use immutable=1 to bypass WAL/SHM file access issues with read-only access.
     let encoded = expanded_path
@@ -1288,7 +2344,7 @@ fn sqlite_value_ref_to_json(value: ValueRef<'_>) -> serde_json::Value {
     }
 }
 
-fn iso_now() -> String {
+pub(crate) fn iso_now() -> String {
     time::OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc3339)
         .unwrap_or_else(|err| {
@@ -1297,14 +2353,15 @@ fn iso_now() -> String {
         })
 }
 
-fn expand_path(path: &str) -> String {
+pub(crate) fn expand_path(path: &str, home_override: Option<&Path>) -> String {
+    let home_dir = || home_override.map(Path::to_path_buf).or_else(dirs::home_dir);
     if path == "~" {
-        if let Some(home) = dirs::home_dir() {
+        if let Some(home) = home_dir() {
             return home.to_string_lossy().to_string();
         }
     }
     if path.starts_with("~/") {
-        if let Some(home) = dirs::home_dir() {
+        if let Some(home) = home_dir() {
             return home.join(&path[2..]).to_string_lossy().to_string();
         }
     }
@@ -1314,6 +2371,7 @@ fn expand_path(path: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plugin_engine::test_util::TempDir;
     use rquickjs::{Context, Function, Object, Runtime};
 
     #[test]
@@ -1321,8 +2379,8 @@ mod tests {
         let rt = Runtime::new().expect("runtime");
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
-            let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            let app_data = TempDir::new("host-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default()).expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
@@ -1336,13 +2394,275 @@ mod tests {
         });
     }
 
+    #[test]
+    fn keychain_api_deletes_and_lists_items() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("host-api");
+            let options = HostOptions {
+                keychain: Some(HashMap::from([
+                    ("OpenUsage-copilot".to_string(), "{}".to_string()),
+                    ("OpenUsage-claude".to_string(), "{}".to_string()),
+                    ("gh:github.com".to_string(), "gho".to_string()),
+                ])),
+                ..HostOptions::default()
+            };
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &options).expect("inject host api");
+            let listed: String = ctx
+                .eval(
+                    r#"(function() {
+                        var kc = __openusage_ctx.host.keychain;
+                        kc.deleteGenericPassword("OpenUsage-claude");
+                        var missing = "";
+                        try { kc.deleteGenericPassword("OpenUsage-claude"); } catch (e) { missing = String(e); }
+                        return kc.listGenericPasswords("OpenUsage-").join(",") + "|" + missing;
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(
+                listed,
+                "OpenUsage-copilot|Error: keychain item not found: OpenUsage-claude"
+            );
+        });
+    }
+
+    #[test]
+    fn store_api_roundtrips_json_values() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("store-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default())
+                .expect("inject host api");
+            let result: String = ctx
+                .eval(
+                    r#"(function() {
+                        var store = __openusage_ctx.host.store;
+                        store.set("state", { counter: 2, tags: ["a"] });
+                        store.set("cache:models", [1, 2], { ttlMs: 60000 });
+                        var state = store.get("state");
+                        var missing = store.get("nope");
+                        var keys = store.list().join(",");
+                        var cached = store.list("cache:").join(",");
+                        var deleted = store.delete("state");
+                        var bad = "";
+                        try { store.set("x", undefined); } catch (e) { bad = String(e); }
+                        return [state.counter, state.tags[0], missing, keys, cached, deleted, store.get("state"), bad].join("|");
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(
+                result,
+                "2|a||cache:models,state|cache:models|true||Error: store value must be JSON-serializable"
+            );
+        });
+    }
+
+    #[test]
+    fn crypto_api_hashes_and_decodes_utf8_jwts() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("host-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default())
+                .expect("inject host api");
+            inject_utils(&ctx).expect("inject utils");
+            let result: String = ctx
+                .eval(
+                    r#"(function() {
+                        var c = __openusage_ctx;
+                        var payload = c.base64.encodeUrl(JSON.stringify({ name: "José ✓" }));
+                        var jwt = c.jwt.decodePayload("eyJhbGciOiJub25lIn0." + payload + ".sig");
+                        var pkce = c.crypto.pkce();
+                        return [
+                            c.crypto.sha256("abc").slice(0, 8),
+                            c.crypto.sha256("abc", "base64url").length,
+                            c.crypto.hmac("sha1", "key", "data").length,
+                            c.crypto.randomBytes(4).length,
+                            c.crypto.uuid().length,
+                            pkce.challenge === c.crypto.sha256(pkce.verifier, "base64url"),
+                            c.base64.decode(c.base64.encode("héllo")),
+                            c.hex.encode("é"),
+                            c.hex.decode("c3a9"),
+                            jwt.name,
+                        ].join("|");
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(result, "ba7816bf|43|40|8|36|true|héllo|c3a9|é|José ✓");
+        });
+    }
+
+    #[test]
+    fn pricing_api_costs_usage_by_model() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("pricing-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default())
+                .expect("inject host api");
+            let result: String = ctx
+                .eval(
+                    r#"(function() {
+                        var pricing = __openusage_ctx.pricing;
+                        var cost = pricing.cost({ model: "claude-haiku-4-5-20251001", inputTokens: 2000000, outputTokens: 100000 });
+                        var unknown = pricing.cost({ model: "nope" });
+                        return [cost, unknown, pricing.lookup("gpt-5-mini").output, pricing.lookup("nope")].join("|");
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(result, "2.5||2|");
+        });
+    }
+
+    #[test]
+    fn fs_api_lists_stats_globs_and_reads_bytes_under_home() {
+        let home = TempDir::new("fs-api");
+        std::fs::create_dir_all(home.join(".codex/sessions")).unwrap();
+        std::fs::write(home.join(".codex/sessions/a.jsonl"), "{}").unwrap();
+        std::fs::write(home.join(".codex/sessions/b.jsonl"), "hi").unwrap();
+
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let options = HostOptions {
+                home_dir: Some(home.clone()),
+                ..HostOptions::default()
+            };
+            inject_host_api(&ctx, "test", &home, "0.0.0", &PluginPermissions::default(), None, &options)
+                .expect("inject host api");
+            let result: String = ctx
+                .eval(
+                    r#"(function() {
+                        var fs = __openusage_ctx.host.fs;
+                        var names = fs.listDir("~/.codex/sessions").join(",");
+                        var matches = fs.glob("~/.codex/sessions/*.jsonl").length;
+                        var st = fs.stat("~/.codex/sessions/b.jsonl");
+                        var dir = fs.stat("~/.codex").isDir;
+                        var missing = "";
+                        try { fs.stat("~/.codex/nope"); } catch (e) { missing = "threw"; }
+                        return [names, matches, st.size, st.isDir, st.mtimeMs > 0, dir,
+                            fs.readBytes("~/.codex/sessions/b.jsonl"), missing].join("|");
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(result, "a.jsonl,b.jsonl|2|2|false|true|true|aGk=|threw");
+
+            patch_fs_wrapper(&ctx).expect("patch fs wrapper");
+            std::fs::write(home.join(".codex/sessions/c.jsonl"), "{\"n\":1}\n{\"n\":2}\n").unwrap();
+            let tail = r#"(function() {
+                var out = __openusage_ctx.host.fs.tailJsonl("~/.codex/sessions/c.jsonl", "codex");
+                return out.records.map(function(r) { return r.record.n; }).join(",") + "|" + out.more;
+            })()"#;
+            let first: String = ctx.eval(tail).expect("first tail");
+            assert_eq!(first, "1,2|false");
+            let second: String = ctx.eval(tail).expect("second tail");
+            assert_eq!(second, "|false");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_api_runs_only_declared_commands() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("host-api");
+            let permissions = PluginPermissions {
+                exec: vec![vec!["echo".to_string(), "hello".to_string()]],
+                ..PluginPermissions::default()
+            };
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &permissions, None, &HostOptions::default())
+                .expect("inject host api");
+            patch_exec_wrapper(&ctx).expect("patch exec wrapper");
+            let result: String = ctx
+                .eval(
+                    r#"(function() {
+                        var exec = __openusage_ctx.host.exec;
+                        var out = exec(["echo", "hello"]);
+                        var blocked = "";
+                        try { exec(["echo", "hello", "; rm -rf ~"]); } catch (e) { blocked = String(e); }
+                        return JSON.stringify(out) + "|" + blocked;
+                    })()"#,
+                )
+                .expect("eval");
+            assert_eq!(
+                result,
+                r#"{"stdout":"hello\n","stderr":"","exitCode":0,"truncated":false}|Error: command not allowed by plugin manifest: echo hello ; rm -rf ~"#
+            );
+        });
+    }
+
+    fn sqlite_test_db(dir: &Path, label: &str) -> PathBuf {
+        let path = dir.join(format!("{}.vscdb", label));
+        let conn = Connection::open(&path).expect("open db");
+        conn.execute_batch(
+            "CREATE TABLE ItemTable (key TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO ItemTable VALUES ('a', 'it''s'), ('b', '2');",
+        )
+        .expect("seed db");
+        path
+    }
+
+    #[test]
+    fn sqlite_api_binds_params_and_limits_writes_to_declared_dbs() {
+        let dir = TempDir::new("sqlite");
+        let db = sqlite_test_db(&dir, "params");
+        let other = sqlite_test_db(&dir, "undeclared");
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = TempDir::new("host-api");
+            let permissions = PluginPermissions {
+                sqlite_write: vec![db.to_string_lossy().to_string()],
+                ..PluginPermissions::default()
+            };
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &permissions, None, &HostOptions::default())
+                .expect("inject host api");
+            let script = format!(
+                r#"(function() {{
+                    var sqlite = __openusage_ctx.host.sqlite;
+                    var db = {db}, other = {other};
+                    var out = [];
+                    out.push(sqlite.query(db, "SELECT value FROM ItemTable WHERE key = ?", ["a"]));
+                    sqlite.exec(db, "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (:key, :value)",
+                        {{ key: "c", value: "x'); DROP TABLE ItemTable; --" }});
+                    out.push(sqlite.query(db, "SELECT value FROM ItemTable WHERE key = @key", {{ "@key": "c" }}));
+                    try {{ sqlite.query(db, "SELECT ?", [1, 2]); }} catch (e) {{ out.push(String(e)); }}
+                    try {{ sqlite.exec(other, "DELETE FROM ItemTable"); }} catch (e) {{ out.push(String(e)); }}
+                    try {{
+                        sqlite.exec(db, "UPDATE ItemTable SET value = 'gone'; INSERT INTO ItemTable VALUES ('a', 'dup');");
+                    }} catch (e) {{ out.push(String(e).split(":")[0]); }}
+                    out.push(sqlite.query(db, "SELECT count(*) AS n FROM ItemTable WHERE value = 'gone'"));
+                    return out.join("|");
+                }})()"#,
+                db = serde_json::to_string(&db.to_string_lossy()).unwrap(),
+                other = serde_json::to_string(&other.to_string_lossy()).unwrap(),
+            );
+            let result: String = ctx.eval(script).expect("eval");
+            let parts: Vec<&str> = result.split('|').collect();
+            assert_eq!(parts[0], r#"[{"value":"it's"}]"#);
+            assert_eq!(parts[1], r#"[{"value":"x'); DROP TABLE ItemTable; --"}]"#);
+            assert_eq!(parts[2], "Error: sqlite expected 1 params, got 2");
+            assert!(
+                parts[3].starts_with("Error: sqlite write not allowed by plugin manifest"),
+                "{}",
+                parts[3]
+            );
+            // The failed script was rolled back as a whole.
+            assert_eq!(parts[4], "Error");
+            assert_eq!(parts[5], r#"[{"n":0}]"#);
+        });
+    }
+
     #[test]
     fn env_api_respects_allowlist_in_host_and_js() {
         let rt = Runtime::new().expect("runtime");
         let ctx = Context::full(&rt).expect("context");
         ctx.with(|ctx| {
-            let app_data = std::env::temp_dir();
-            inject_host_api(&ctx, "test", &app_data, "0.0.0").expect("inject host api");
+            let app_data = TempDir::new("host-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default()).expect("inject host api");
             let globals = ctx.globals();
             let probe_ctx: Object = globals.get("__openusage_ctx").expect("probe ctx");
             let host: Object = probe_ctx.get("host").expect("host");
@@ -1370,6 +2690,241 @@ mod tests {
         });
     }
 
+    #[test]
+    fn env_api_exposes_declared_vars() {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let permissions = PluginPermissions {
+                env: vec!["GATEWAY_TOKEN".to_string()],
+                ..Default::default()
+            };
+            let options = HostOptions {
+                env: Some(HashMap::from([
+                    ("GATEWAY_TOKEN".to_string(), "tok".to_string()),
+                    ("OTHER_SECRET".to_string(), "nope".to_string()),
+                ])),
+                ..Default::default()
+            };
+            let app_data = TempDir::new("env-api");
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &permissions, None, &options)
+                .expect("inject host api");
+            let values: Vec<Option<String>> = ctx
+                .eval(r#"["GATEWAY_TOKEN", "OTHER_SECRET"].map(function (n) { return __openusage_ctx.host.env.get(n) })"#)
+                .expect("eval");
+            assert_eq!(values, vec![Some("tok".to_string()), None]);
+        });
+    }
+
+    /// Serve canned raw HTTP responses, one per accepted connection.
+    /// Returns the raw requests received, in order.
+    fn serve_responses(
+        responses: Vec<&'static str>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/", listener.local_addr().expect("addr"));
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for raw in responses {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                requests.push(String::from_utf8_lossy(&buf[..n]).to_string());
+                stream.write_all(raw.as_bytes()).expect("write");
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn eval_http_request(req_js: &str) -> serde_json::Value {
+        eval_http_request_in(&TempDir::new("http"), req_js)
+    }
+
+    fn eval_http_request_in(app_data: &std::path::Path, req_js: &str) -> serde_json::Value {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| {
+            let app_data = app_data.to_path_buf();
+            inject_host_api(&ctx, "test", &app_data, "0.0.0", &PluginPermissions::default(), None, &HostOptions::default()).expect("inject host api");
+            patch_http_wrapper(&ctx).expect("patch http wrapper");
+            let js = format!("JSON.stringify(__openusage_ctx.host.http.request({}))", req_js);
+            let json: String = ctx.eval(js).expect("http request");
+            serde_json::from_str(&json).expect("response json")
+        })
+    }
+
+    #[test]
+    fn http_retry_retries_transient_status_then_succeeds() {
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let resp = eval_http_request(&format!(
+            r#"{{ url: "{}", retry: {{ maxAttempts: 3, backoffMs: 1 }} }}"#,
+            url
+        ));
+        assert_eq!(resp["status"], 200);
+        assert_eq!(resp["bodyText"], "ok");
+        assert_eq!(server.join().expect("server").len(), 2);
+    }
+
+    #[test]
+    fn http_without_retry_returns_first_response() {
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ]);
+        let resp = eval_http_request(&format!(r#"{{ url: "{}" }}"#, url));
+        assert_eq!(resp["status"], 503);
+        assert_eq!(server.join().expect("server").len(), 1);
+    }
+
+    #[test]
+    fn http_cache_revalidates_with_etag_and_serves_cached_body_on_304() {
+        let app_data = TempDir::new("http-cache");
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfirst",
+            "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n",
+        ]);
+        let req = format!(r#"{{ url: "{}", cache: true }}"#, url);
+        let first = eval_http_request_in(&app_data, &req);
+        assert_eq!(first["bodyText"], "first");
+        let second = eval_http_request_in(&app_data, &req);
+        assert_eq!(second["status"], 200);
+        assert_eq!(second["bodyText"], "first");
+
+        let requests = server.join().expect("server");
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1].to_lowercase().contains("if-none-match: \"v1\""));
+    }
+
+    #[test]
+    fn http_cache_ttl_override_skips_network() {
+        let app_data = TempDir::new("http-ttl");
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let req = format!(r#"{{ url: "{}", cache: {{ ttlMs: 60000 }} }}"#, url);
+        eval_http_request_in(&app_data, &req);
+        let cached = eval_http_request_in(&app_data, &req);
+        assert_eq!(cached["bodyText"], "ok");
+        assert_eq!(server.join().expect("server").len(), 1);
+    }
+
+    #[test]
+    fn http_retry_policy_skips_non_idempotent_by_default() {
+        let opts: HttpRetryOpts = serde_json::from_str(r#"{"maxAttempts": 4}"#).expect("opts");
+        assert_eq!(HttpRetryPolicy::from_opts(Some(&opts), &reqwest::Method::POST).max_attempts, 1);
+        assert_eq!(HttpRetryPolicy::from_opts(Some(&opts), &reqwest::Method::GET).max_attempts, 4);
+
+        let opts: HttpRetryOpts =
+            serde_json::from_str(r#"{"maxAttempts": 4, "idempotentOnly": false}"#).expect("opts");
+        assert_eq!(HttpRetryPolicy::from_opts(Some(&opts), &reqwest::Method::POST).max_attempts, 4);
+        assert_eq!(HttpRetryPolicy::from_opts(None, &reqwest::Method::GET).max_attempts, 1);
+    }
+
+    #[test]
+    fn http_retry_policy_backoff_and_retry_after() {
+        let opts: HttpRetryOpts =
+            serde_json::from_str(r#"{"backoffMs": 100, "maxBackoffMs": 300}"#).expect("opts");
+        let policy = HttpRetryPolicy::from_opts(Some(&opts), &reqwest::Method::GET);
+        assert_eq!(policy.statuses, vec![429, 502, 503, 504]);
+        assert_eq!(policy.backoff_delay(1).as_millis(), 100);
+        assert_eq!(policy.backoff_delay(2).as_millis(), 200);
+        assert_eq!(policy.backoff_delay(3).as_millis(), 300);
+
+        let short = Some(std::time::Duration::from_millis(250));
+        assert_eq!(policy.delay_for(1, short), short);
+        assert!(policy.delay_for(1, Some(std::time::Duration::from_secs(60))).is_none());
+    }
+
+    #[test]
+    fn parse_retry_after_accepts_seconds_and_http_date() {
+        let now = time::OffsetDateTime::from_unix_timestamp(1_445_412_470).expect("now");
+        assert_eq!(parse_retry_after("5", now), Some(std::time::Duration::from_secs(5)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(std::time::Duration::ZERO)
+        );
+        assert!(parse_retry_after("soon", now).is_none());
+    }
+
+    #[test]
+    fn ls_extract_flag_reads_exact_argv_values() {
+        let argv: Vec<String> = [
+            "/opt/Windsurf - Next/language_server",
+            "--app_data_dir",
+            "/home/me/.codeium/windsurf - next",
+            "--csrf_token=abc",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(
+            ls_extract_flag(&argv, "--app_data_dir").as_deref(),
+            Some("/home/me/.codeium/windsurf - next")
+        );
+        assert_eq!(ls_extract_flag(&argv, "--csrf_token").as_deref(), Some("abc"));
+        assert_eq!(ls_extract_flag(&argv, "--missing"), None);
+    }
+
+    #[test]
+    fn ls_parse_etime_handles_days_and_short_forms() {
+        assert_eq!(ls_parse_etime_secs("05:07"), Some(307));
+        assert_eq!(ls_parse_etime_secs("2-01:00:00"), Some(2 * 86_400 + 3_600));
+        assert_eq!(ls_parse_etime_secs("bogus"), None);
+    }
+
+    #[test]
+    fn ls_matching_processes_returns_every_instance() {
+        let process = |pid: i32, args: &str| LsProcess {
+            pid,
+            argv: args.split(' ').map(str::to_string).collect(),
+            started_at_ms: None,
+        };
+        let opts: LsDiscoverOpts = serde_json::from_str(
+            r#"{"processName": "language_server", "markers": ["windsurf"], "csrfFlag": "--csrf_token"}"#,
+        )
+        .expect("opts");
+        let matches = ls_matching_processes(
+            vec![
+                process(1, "language_server --ide_name windsurf --workspace_id a"),
+                process(2, "language_server --ide_name windsurf-next"),
+                process(3, "language_server --ide_name windsurf --workspace_id b"),
+                process(4, "other --ide_name windsurf"),
+            ],
+            &opts,
+        );
+        let pids: Vec<i32> = matches.iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![1, 3]);
+    }
+
+    #[test]
+    fn ls_parse_ps_output_splits_pid_and_command() {
+        let output = concat!(
+            "   12    01:02:03 /usr/bin/ls --csrf_token abc\n",
+            "34567       05:12 /Applications/Windsurf.app/language_server_macos_arm --port 0\n",
+            "bogus\n",
+        );
+        let processes = ls_parse_ps_output(output, 10_000_000);
+        assert_eq!(processes.len(), 2);
+        assert_eq!(processes[0].pid, 12);
+        assert_eq!(processes[0].started_at_ms, Some(10_000_000 - 3_723_000));
+        assert_eq!(processes[0].argv[0], "/usr/bin/ls");
+        assert_eq!(ls_extract_flag(&processes[0].argv, "--csrf_token").as_deref(), Some("abc"));
+        assert_eq!(processes[1].pid, 34567);
+        assert_eq!(processes[1].started_at_ms, Some(10_000_000 - 312_000));
+        assert_eq!(
+            processes[1].argv[0],
+            "/Applications/Windsurf.app/language_server_macos_arm"
+        );
+    }
+
     #[test]
     fn redact_value_shows_first_and_last_four() {
         assert_eq!(redact_value("sk-1234567890abcdef"), "sk-1...cdef");
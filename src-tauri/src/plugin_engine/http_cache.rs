@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Headers that are sent by the cache itself and must not affect the key.
+const CONDITIONAL_HEADERS: [&str; 2] = ["if-none-match", "if-modified-since"];
+/// Entries neither fresh nor stored or revalidated this long ago are deleted
+/// on the next store. A plugin's `ttlMs` or validators may still use a stale
+/// entry until then.
+const PRUNE_AFTER_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// A stored response plus the validators needed to revalidate it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body_text: String,
+    pub stored_at_ms: u64,
+    /// Freshness lifetime from `Cache-Control: max-age`. `0` means the entry
+    /// must be revalidated before use.
+    pub max_age_ms: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl CachedResponse {
+    /// A manual `ttlMs` from the plugin wins over the server's max-age.
+    pub fn is_fresh(&self, now_ms: u64, ttl_override_ms: Option<u64>) -> bool {
+        let lifetime = ttl_override_ms.unwrap_or(self.max_age_ms);
+        now_ms.saturating_sub(self.stored_at_ms) < lifetime
+    }
+
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub max_age_secs: Option<u64>,
+}
+
+pub fn parse_cache_control(value: &str) -> CacheControl {
+    let mut out = CacheControl::default();
+    for directive in value.split(',') {
+        let directive = directive.trim().to_ascii_lowercase();
+        if directive == "no-store" {
+            out.no_store = true;
+        } else if directive == "no-cache" {
+            out.no_cache = true;
+        } else if let Some(raw) = directive.strip_prefix("max-age=") {
+            out.max_age_secs = raw.trim_matches('"').parse::<u64>().ok();
+        }
+    }
+    out
+}
+
+/// On-disk response cache rooted in a plugin's data dir.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(plugin_data_dir: &Path) -> Self {
+        Self {
+            dir: plugin_data_dir.join("http_cache"),
+        }
+    }
+
+    /// Key on everything that can change the response: method, URL, request
+    /// headers (including auth, so accounts never share entries) and body.
+    /// A SHA-256 digest, so keys stay the same across app builds.
+    pub fn key(
+        method: &str,
+        url: &str,
+        headers: Option<&HashMap<String, String>>,
+        body: Option<&str>,
+    ) -> String {
+        // NUL never appears in a method, URL or header, so fields can't run
+        // into each other; the body goes last since it may contain anything.
+        let mut input = format!("{}\0{}\0", method.to_ascii_uppercase(), url);
+        if let Some(headers) = headers {
+            let mut pairs: Vec<(String, &String)> = headers
+                .iter()
+                .map(|(k, v)| (k.to_ascii_lowercase(), v))
+                .filter(|(k, _)| !CONDITIONAL_HEADERS.contains(&k.as_str()))
+                .collect();
+            pairs.sort();
+            for (name, value) in pairs {
+                input.push_str(&format!("{}:{}\0", name, value));
+            }
+        }
+        if let Some(body) = body {
+            input.push('\0');
+            input.push_str(body);
+        }
+        let digest = ring::digest::digest(&ring::digest::SHA256, input.as_bytes());
+        digest.as_ref()[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn load(&self, key: &str) -> Option<CachedResponse> {
+        let text = std::fs::read_to_string(self.entry_path(key)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Store a successful response. Returns false when the server forbids
+    /// caching or the write fails; failures only cost a future cache miss.
+    pub fn store(
+        &self,
+        key: &str,
+        status: u16,
+        headers: &HashMap<String, String>,
+        body_text: &str,
+        now_ms: u64,
+    ) -> bool {
+        let control = headers
+            .get("cache-control")
+            .map(|v| parse_cache_control(v))
+            .unwrap_or_default();
+        if control.no_store {
+            self.remove(key);
+            return false;
+        }
+        let max_age_ms = if control.no_cache {
+            0
+        } else {
+            control.max_age_secs.unwrap_or(0).saturating_mul(1000)
+        };
+        let entry = CachedResponse {
+            status,
+            headers: headers.clone(),
+            body_text: body_text.to_string(),
+            stored_at_ms: now_ms,
+            max_age_ms,
+            etag: headers.get("etag").cloned(),
+            last_modified: headers.get("last-modified").cloned(),
+        };
+        self.prune(now_ms);
+        self.write(key, &entry)
+    }
+
+    /// Deletes expired entries (see `PRUNE_AFTER_MS`) and unreadable files.
+    fn prune(&self, now_ms: u64) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let expired = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| serde_json::from_str::<CachedResponse>(&text).ok())
+                .is_none_or(|entry| {
+                    now_ms.saturating_sub(entry.stored_at_ms) >= entry.max_age_ms.max(PRUNE_AFTER_MS)
+                });
+            if expired {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+
+    /// Mark a cached entry as revalidated after a 304, picking up any new
+    /// freshness and validator headers the server sent along.
+    pub fn refresh(
+        &self,
+        key: &str,
+        mut entry: CachedResponse,
+        headers: &HashMap<String, String>,
+        now_ms: u64,
+    ) -> CachedResponse {
+        entry.stored_at_ms = now_ms;
+        if let Some(value) = headers.get("cache-control") {
+            let control = parse_cache_control(value);
+            entry.max_age_ms = if control.no_cache {
+                0
+            } else {
+                control.max_age_secs.unwrap_or(0).saturating_mul(1000)
+            };
+        }
+        if let Some(etag) = headers.get("etag") {
+            entry.etag = Some(etag.clone());
+        }
+        if let Some(last_modified) = headers.get("last-modified") {
+            entry.last_modified = Some(last_modified.clone());
+        }
+        self.write(key, &entry);
+        entry
+    }
+
+    pub fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.entry_path(key));
+    }
+
+    fn write(&self, key: &str, entry: &CachedResponse) -> bool {
+        if let Err(err) = std::fs::create_dir_all(&self.dir) {
+            log::warn!("http cache dir {} unavailable: {}", self.dir.display(), err);
+            return false;
+        }
+        let text = match serde_json::to_string(entry) {
+            Ok(text) => text,
+            Err(err) => {
+                log::warn!("http cache encode failed: {}", err);
+                return false;
+            }
+        };
+        match std::fs::write(self.entry_path(key), text) {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!("http cache write failed: {}", err);
+                false
+            }
+        }
+    }
+}
+
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn parse_cache_control_reads_directives() {
+        assert_eq!(
+            parse_cache_control("private, max-age=300"),
+            CacheControl {
+                no_store: false,
+                no_cache: false,
+                max_age_secs: Some(300)
+            }
+        );
+        assert!(parse_cache_control("No-Store").no_store);
+        assert!(parse_cache_control("no-cache, max-age=60").no_cache);
+    }
+
+    #[test]
+    fn key_ignores_conditional_headers_but_not_auth() {
+        let mut a = HashMap::new();
+        a.insert("Authorization".to_string(), "Bearer a".to_string());
+        let mut a_conditional = a.clone();
+        a_conditional.insert("If-None-Match".to_string(), "\"v1\"".to_string());
+        let mut b = HashMap::new();
+        b.insert("Authorization".to_string(), "Bearer b".to_string());
+
+        let key_a = HttpCache::key("GET", "https://x", Some(&a), None);
+        assert_eq!(key_a, HttpCache::key("get", "https://x", Some(&a_conditional), None));
+        assert_ne!(key_a, HttpCache::key("GET", "https://x", Some(&b), None));
+        assert_ne!(key_a, HttpCache::key("POST", "https://x", Some(&a), Some("{}")));
+    }
+
+    #[test]
+    fn store_respects_no_store_and_max_age() {
+        let dir = TempDir::new("http-cache-store");
+        let cache = HttpCache::new(&dir);
+        let mut headers = HashMap::new();
+        headers.insert("cache-control".to_string(), "no-store".to_string());
+        assert!(!cache.store("k", 200, &headers, "body", 1_000));
+        assert!(cache.load("k").is_none());
+
+        headers.insert("cache-control".to_string(), "max-age=60".to_string());
+        headers.insert("etag".to_string(), "\"v1\"".to_string());
+        assert!(cache.store("k", 200, &headers, "body", 1_000));
+        let entry = cache.load("k").expect("entry");
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        assert!(entry.is_fresh(60_999, None));
+        assert!(!entry.is_fresh(61_000, None));
+        assert!(entry.is_fresh(61_000, Some(120_000)));
+    }
+
+    #[test]
+    fn store_prunes_expired_entries() {
+        let dir = TempDir::new("http-cache-prune");
+        let cache = HttpCache::new(&dir);
+        let mut headers = HashMap::new();
+        headers.insert("etag".to_string(), "\"v1\"".to_string());
+        cache.store("old", 200, &headers, "body", 1_000);
+        cache.store("recent", 200, &headers, "body", PRUNE_AFTER_MS);
+        assert!(cache.load("old").is_some());
+
+        cache.store("new", 200, &headers, "body", PRUNE_AFTER_MS + 1_000);
+        assert!(cache.load("old").is_none());
+        assert!(cache.load("recent").is_some());
+        assert!(cache.load("new").is_some());
+    }
+
+    #[test]
+    fn refresh_updates_timestamp_and_validators() {
+        let dir = TempDir::new("http-cache-refresh");
+        let cache = HttpCache::new(&dir);
+        let mut headers = HashMap::new();
+        headers.insert("etag".to_string(), "\"v1\"".to_string());
+        cache.store("k", 200, &headers, "body", 1_000);
+        let entry = cache.load("k").expect("entry");
+        assert!(!entry.is_fresh(1_001, None));
+
+        let mut not_modified = HashMap::new();
+        not_modified.insert("etag".to_string(), "\"v2\"".to_string());
+        not_modified.insert("cache-control".to_string(), "max-age=10".to_string());
+        let refreshed = cache.refresh("k", entry, &not_modified, 5_000);
+        assert_eq!(refreshed.body_text, "body");
+        assert_eq!(refreshed.etag.as_deref(), Some("\"v2\""));
+        assert!(cache.load("k").expect("entry").is_fresh(14_999, None));
+    }
+}
@@ -0,0 +1,242 @@
+//! Extra copies of a plugin for additional accounts.
+//!
+//! An instance runs the same entry script under its own id, so its data dir,
+//! settings (`ctx.config`), store, results and tray selection are separate from
+//! the plugin it copies. Instances are listed in `<appDataDir>/plugin_instances.json`.
+
+use crate::plugin_engine::credential_store::{CredentialError, CredentialStore};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::kv_store::PluginStore;
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::oauth;
+use crate::plugin_engine::plugin_settings::PluginSettings;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+const INSTANCES_FILE: &str = "plugin_instances.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInstance {
+    pub id: String,
+    pub plugin_id: String,
+    pub name: String,
+}
+
+pub fn load(app_data_dir: &Path) -> Vec<PluginInstance> {
+    let path = app_data_dir.join(INSTANCES_FILE);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            log::warn!("failed to read {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        log::warn!("invalid {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+fn save(app_data_dir: &Path, instances: &[PluginInstance]) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(instances).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
+    fs_atomic::write(
+        &app_data_dir.join(INSTANCES_FILE),
+        text.as_bytes(),
+        WriteOpts::default(),
+        app_data_dir,
+    )
+}
+
+/// Appends a copy of the base plugin for every instance. Instances of plugins
+/// that are no longer installed, or whose id is taken, are skipped.
+pub fn expand(mut plugins: Vec<LoadedPlugin>, instances: &[PluginInstance]) -> Vec<LoadedPlugin> {
+    for instance in instances {
+        if plugins.iter().any(|plugin| plugin.manifest.id == instance.id) {
+            log::warn!("plugin instance id '{}' is already in use; skipped", instance.id);
+            continue;
+        }
+        let Some(base) = plugins
+            .iter()
+            .find(|plugin| plugin.manifest.id == instance.plugin_id && plugin.instance_of.is_none())
+        else {
+            log::warn!(
+                "plugin instance '{}' refers to missing plugin '{}'",
+                instance.id,
+                instance.plugin_id
+            );
+            continue;
+        };
+        let mut copy = base.clone();
+        copy.manifest.id = instance.id.clone();
+        copy.manifest.name = format!("{} ({})", base.manifest.name, instance.name);
+        copy.instance_of = Some(instance.plugin_id.clone());
+        plugins.push(copy);
+    }
+    plugins.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+    plugins
+}
+
+/// Registers a new instance of `plugin_id` and returns it. The id is derived
+/// from the name, e.g. "Work" on `claude` becomes `claude-work`.
+pub fn create(
+    app_data_dir: &Path,
+    plugins: &[LoadedPlugin],
+    plugin_id: &str,
+    name: &str,
+) -> Result<PluginInstance, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("account name cannot be empty".to_string());
+    }
+    let base = plugins
+        .iter()
+        .find(|plugin| plugin.manifest.id == plugin_id && plugin.instance_of.is_none())
+        .ok_or_else(|| format!("unknown plugin: {}", plugin_id))?;
+    if base.manifest.settings.is_empty() {
+        return Err(format!(
+            "{} has no settings to tell accounts apart",
+            base.manifest.name
+        ));
+    }
+
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let stem = if slug.is_empty() {
+        plugin_id.to_string()
+    } else {
+        format!("{}-{}", plugin_id, slug)
+    };
+    let mut instances = load(app_data_dir);
+    let taken = |id: &str| {
+        plugins.iter().any(|plugin| plugin.manifest.id == id)
+            || instances.iter().any(|instance| instance.id == id)
+    };
+    let id = std::iter::once(stem.clone())
+        .chain((2..).map(|n| format!("{}-{}", stem, n)))
+        .find(|id| !taken(id))
+        .unwrap_or(stem);
+
+    let instance = PluginInstance {
+        id,
+        plugin_id: plugin_id.to_string(),
+        name: name.to_string(),
+    };
+    instances.push(instance.clone());
+    save(app_data_dir, &instances)?;
+    log::info!("[plugin:{}] instance '{}' created", plugin_id, instance.id);
+    Ok(instance)
+}
+
+/// Unregisters an instance and deletes what it stored: settings, secrets,
+/// in-app login tokens, `host.store` entries and its data dir. Anything left
+/// behind is pruned at the next launch like for any uninstalled plugin.
+pub fn remove(
+    app_data_dir: &Path,
+    instance: &LoadedPlugin,
+    store: Arc<dyn CredentialStore>,
+) -> Result<(), String> {
+    let id = &instance.manifest.id;
+    if instance.instance_of.is_none() {
+        return Err(format!("{} is not an added account", instance.manifest.name));
+    }
+    let mut instances = load(app_data_dir);
+    instances.retain(|entry| &entry.id != id);
+    save(app_data_dir, &instances)?;
+
+    PluginSettings::new(app_data_dir, id, Arc::clone(&store)).clear(&instance.manifest.settings)?;
+    match store.delete(&oauth::login_service(id)) {
+        Ok(()) | Err(CredentialError::NotFound(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+    PluginStore::new(app_data_dir, id).clear()?;
+    let data_dir = app_data_dir.join("plugins_data").join(id);
+    if data_dir.exists() {
+        std::fs::remove_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    }
+    log::info!("[plugin:{}] instance removed", id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::credential_store::MemoryStore;
+    use crate::plugin_engine::manifest::PluginManifest;
+    use crate::plugin_engine::test_util::TempDir;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn plugin(id: &str, settings: &str) -> LoadedPlugin {
+        let manifest: PluginManifest = serde_json::from_str(&format!(
+            r#"{{"schemaVersion": 1, "id": "{id}", "name": "{id}", "version": "0.0.1",
+                "entry": "plugin.js", "icon": "icon.svg", "brandColor": null, "lines": [],
+                "settings": {settings}}}"#
+        ))
+        .expect("manifest");
+        LoadedPlugin {
+            manifest,
+            plugin_dir: PathBuf::from("."),
+            entry_script: String::new(),
+            icon_data_url: String::new(),
+            instance_of: None,
+            native: None,
+            entry_wasm: None,
+        }
+    }
+
+    #[test]
+    fn instances_get_their_own_ids() {
+        let dir = TempDir::new("instances-create");
+        let settings = r#"[{"key": "credentialsFile", "label": "Credentials file", "type": "path"}]"#;
+        let plugins = vec![plugin("claude", settings), plugin("codex", "[]")];
+
+        let work = create(&dir, &plugins, "claude", " Work ").unwrap();
+        assert_eq!(work.id, "claude-work");
+        assert_eq!(create(&dir, &plugins, "claude", "work!").unwrap().id, "claude-work-2");
+        assert_eq!(
+            create(&dir, &plugins, "codex", "Work").unwrap_err(),
+            "codex has no settings to tell accounts apart"
+        );
+        assert!(create(&dir, &plugins, "claude", "  ").is_err());
+
+        let expanded = expand(plugins, &load(&dir));
+        let ids: Vec<_> = expanded.iter().map(|plugin| plugin.manifest.id.as_str()).collect();
+        assert_eq!(ids, vec!["claude", "claude-work", "claude-work-2", "codex"]);
+        assert_eq!(expanded[1].manifest.name, "claude (Work)");
+        assert_eq!(expanded[1].instance_of.as_deref(), Some("claude"));
+        assert_eq!(expanded[1].manifest.settings.len(), 1);
+    }
+
+    #[test]
+    fn removing_an_instance_deletes_its_state() {
+        let dir = TempDir::new("instances-remove");
+        let settings = r#"[{"key": "token", "label": "Token", "type": "secret"}]"#;
+        let plugins = vec![plugin("claude", settings)];
+        let instance = create(&dir, &plugins, "claude", "Work").unwrap();
+        let expanded = expand(plugins, &load(&dir));
+        let store: Arc<dyn CredentialStore> = Arc::new(MemoryStore::new(HashMap::from([
+            ("openusage:claude-work:setting:token".to_string(), "t".to_string()),
+            ("openusage:claude:setting:token".to_string(), "base".to_string()),
+        ])));
+        std::fs::create_dir_all(dir.join("plugins_data").join(&instance.id)).unwrap();
+        PluginStore::new(&dir, &instance.id).set("cursor", "1", None).unwrap();
+        PluginStore::new(&dir, "claude").set("cursor", "0", None).unwrap();
+
+        assert!(remove(&dir, &expanded[0], Arc::clone(&store)).is_err());
+        remove(&dir, &expanded[1], Arc::clone(&store)).unwrap();
+        assert!(load(&dir).is_empty());
+        assert!(store.read("openusage:claude-work:setting:token").is_err());
+        assert_eq!(store.read("openusage:claude:setting:token").unwrap(), "base");
+        assert!(!dir.join("plugins_data").join(&instance.id).exists());
+        assert!(PluginStore::new(&dir, &instance.id).list("").unwrap().is_empty());
+        assert_eq!(PluginStore::new(&dir, "claude").list("").unwrap(), vec!["cursor"]);
+    }
+}
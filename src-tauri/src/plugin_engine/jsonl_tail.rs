@@ -0,0 +1,401 @@
+//! Incremental JSONL reading for `host.fs.tailJsonl`.
+//!
+//! Session transcripts grow to gigabytes, so each call only parses what was
+//! appended since the last call with the same checkpoint key. Per-file byte
+//! offsets live in `<pluginDataDir>/tail/<key>.json`.
+//!
+//! A file is re-read from the start when it shrank below its offset
+//! (truncation) or its first bytes no longer match what was consumed (replaced
+//! in place). A file renamed away by log rotation keeps its offset if it still
+//! matches the glob, so records are not returned twice.
+
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::fs_read;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Bytes consumed per call; the rest is picked up by the next call.
+pub const MAX_BYTES_PER_CALL: u64 = 64 * 1024 * 1024;
+pub const MAX_RECORDS_PER_CALL: usize = 100_000;
+/// Longer lines are skipped and counted in `skipped`.
+pub const MAX_LINE_BYTES: usize = 4 * 1024 * 1024;
+/// Prefix fingerprinted to notice a file replaced under the same name.
+const HEAD_BYTES: u64 = 256;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Checkpoint {
+    files: BTreeMap<String, FileCheckpoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileCheckpoint {
+    offset: u64,
+    head_len: u64,
+    head_hash: String,
+    #[serde(default)]
+    file_id: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TailRecord {
+    pub path: String,
+    pub record: serde_json::Value,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TailResult {
+    pub records: Vec<TailRecord>,
+    /// Lines that were not valid JSON or exceeded `MAX_LINE_BYTES`.
+    pub skipped: usize,
+    /// True when a per-call limit was hit and more data is waiting.
+    pub more: bool,
+}
+
+/// Offsets past the records `read` returned, written by `save`.
+pub struct PendingCheckpoint {
+    path: PathBuf,
+    data_dir: PathBuf,
+    next: Checkpoint,
+}
+
+impl PendingCheckpoint {
+    pub fn save(self) -> Result<(), String> {
+        let json = serde_json::to_string(&self.next).map_err(|e| e.to_string())?;
+        fs_atomic::write(&self.path, json.as_bytes(), WriteOpts::default(), &self.data_dir)
+    }
+}
+
+/// `pattern` is already `~`-expanded.
+pub fn tail(pattern: &str, checkpoint_key: &str, data_dir: &Path) -> Result<TailResult, String> {
+    let (result, checkpoint) = read(pattern, checkpoint_key, data_dir)?;
+    checkpoint.save()?;
+    Ok(result)
+}
+
+/// Like `tail`, but the checkpoint only moves once the caller saves it, so
+/// callers can persist what they made of the records first.
+pub fn read(
+    pattern: &str,
+    checkpoint_key: &str,
+    data_dir: &Path,
+) -> Result<(TailResult, PendingCheckpoint), String> {
+    let checkpoint_path = checkpoint_path(data_dir, checkpoint_key)?;
+    let previous: Checkpoint = match std::fs::read_to_string(&checkpoint_path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_default(),
+        Err(_) => Checkpoint::default(),
+    };
+
+    let mut next = Checkpoint::default();
+    let mut result = TailResult::default();
+    let mut budget = MAX_BYTES_PER_CALL;
+
+    for path in fs_read::glob(pattern)? {
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let file_id = file_id(&meta);
+        let known = previous
+            .files
+            .get(&path)
+            .filter(|cp| cp.file_id.is_none() || cp.file_id == file_id)
+            .or_else(|| rotated_from(&previous, &path, file_id));
+        let mut start = match known {
+            Some(cp) if cp.offset <= meta.len() && head_matches(Path::new(&path), cp) => cp.offset,
+            _ => 0,
+        };
+
+        // A file deleted mid-scan is simply dropped from the checkpoint.
+        if start < meta.len() && !result.more {
+            match read_records(&path, start, &mut budget, &mut result) {
+                Ok(offset) => start = offset,
+                Err(_) => continue,
+            }
+        }
+        let Ok((head_len, head_hash)) = head(Path::new(&path), start.min(HEAD_BYTES)) else {
+            continue;
+        };
+        next.files.insert(
+            path,
+            FileCheckpoint {
+                offset: start,
+                head_len,
+                head_hash,
+                file_id,
+            },
+        );
+    }
+
+    let checkpoint = PendingCheckpoint {
+        path: checkpoint_path,
+        data_dir: data_dir.to_path_buf(),
+        next,
+    };
+    Ok((result, checkpoint))
+}
+
+/// Reads complete lines from `start`, returning the new offset. A trailing
+/// line without a newline is still being written and is left for later.
+fn read_records(
+    path: &str,
+    start: u64,
+    budget: &mut u64,
+    result: &mut TailResult,
+) -> Result<u64, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(file);
+    let mut offset = start;
+    let mut line = Vec::new();
+
+    loop {
+        if *budget == 0 || result.records.len() >= MAX_RECORDS_PER_CALL {
+            result.more = true;
+            break;
+        }
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE_BYTES as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        if line.last() != Some(&b'\n') {
+            if line.len() <= MAX_LINE_BYTES {
+                break;
+            }
+            // Until its newline arrives the offset stays at the line start,
+            // so the rest of the line is never parsed as a record of its own.
+            let Some(rest) = skip_line(&mut reader).map_err(|e| e.to_string())? else {
+                break;
+            };
+            offset += (read + rest) as u64;
+            *budget = budget.saturating_sub((read + rest) as u64);
+            result.skipped += 1;
+            continue;
+        }
+        offset += read as u64;
+        *budget = budget.saturating_sub(read as u64);
+
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(text) {
+            Ok(record) => result.records.push(TailRecord {
+                path: path.to_string(),
+                record,
+            }),
+            Err(_) => result.skipped += 1,
+        }
+    }
+    Ok(offset)
+}
+
+/// Skips past the next newline and returns the bytes skipped, or `None` if
+/// the file ends first.
+fn skip_line(reader: &mut impl BufRead) -> std::io::Result<Option<usize>> {
+    let mut skipped = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        match buf.iter().position(|byte| *byte == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(Some(skipped + end + 1));
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+                skipped += len;
+            }
+        }
+    }
+}
+
+/// The checkpoint of a file that moved to `path`, e.g. `session.jsonl`
+/// renamed to `session.jsonl.1`. Its old path is gone or holds a new file.
+fn rotated_from<'a>(
+    previous: &'a Checkpoint,
+    path: &str,
+    file_id: Option<(u64, u64)>,
+) -> Option<&'a FileCheckpoint> {
+    let file_id = file_id?;
+    previous.files.iter().find_map(|(old_path, cp)| {
+        let moved = old_path != path
+            && cp.file_id == Some(file_id)
+            && std::fs::metadata(old_path)
+                .ok()
+                .and_then(|meta| self::file_id(&meta))
+                != Some(file_id);
+        moved.then_some(cp)
+    })
+}
+
+fn head_matches(path: &Path, cp: &FileCheckpoint) -> bool {
+    head(path, cp.head_len).is_ok_and(|(len, hash)| len == cp.head_len && hash == cp.head_hash)
+}
+
+fn head(path: &Path, len: u64) -> Result<(u64, String), String> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(len).read_to_end(&mut buf))
+        .map_err(|e| e.to_string())?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, &buf);
+    let hash = digest.as_ref()[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((buf.len() as u64, hash))
+}
+
+#[cfg(unix)]
+fn file_id(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn checkpoint_path(data_dir: &Path, key: &str) -> Result<PathBuf, String> {
+    let valid = !key.is_empty()
+        && key.len() <= 64
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !key.starts_with('.');
+    if !valid {
+        return Err(format!("invalid checkpoint key: {}", key));
+    }
+    let dir = data_dir.join("tail");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("{}.json", key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn ids(result: &TailResult) -> Vec<i64> {
+        result
+            .records
+            .iter()
+            .map(|r| r.record["id"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn returns_only_new_complete_lines() {
+        let root = TempDir::new("tail-append");
+        let data_dir = root.join("data");
+        let log = root.join("a.jsonl");
+        let pattern = format!("{}/*.jsonl", root.display());
+
+        append(&log, "{\"id\":1}\nnot json\n\n{\"id\":2}\n{\"id\":");
+        let first = tail(&pattern, "usage", &data_dir).unwrap();
+        assert_eq!(ids(&first), vec![1, 2]);
+        assert_eq!(first.skipped, 1);
+        assert!(!first.more);
+        assert_eq!(first.records[0].path, log.to_string_lossy());
+
+        assert!(tail(&pattern, "usage", &data_dir).unwrap().records.is_empty());
+
+        append(&log, "3}\n{\"id\":4}\n");
+        assert_eq!(ids(&tail(&pattern, "usage", &data_dir).unwrap()), vec![3, 4]);
+
+        // Records stay unread until their checkpoint is saved.
+        append(&log, "{\"id\":5}\n");
+        let (unsaved, checkpoint) = read(&pattern, "usage", &data_dir).unwrap();
+        assert_eq!(ids(&unsaved), vec![5]);
+        drop(checkpoint);
+        let (again, checkpoint) = read(&pattern, "usage", &data_dir).unwrap();
+        assert_eq!(ids(&again), vec![5]);
+        checkpoint.save().unwrap();
+        assert!(tail(&pattern, "usage", &data_dir).unwrap().records.is_empty());
+
+        // Keys are independent.
+        assert_eq!(ids(&tail(&pattern, "other", &data_dir).unwrap()), vec![1, 2, 3, 4, 5]);
+        assert!(tail(&pattern, "../escape", &data_dir).is_err());
+    }
+
+    #[test]
+    fn oversized_line_is_skipped_once_complete() {
+        let root = TempDir::new("tail-oversized");
+        let data_dir = root.join("data");
+        let log = root.join("a.jsonl");
+        let pattern = format!("{}/*.jsonl", root.display());
+
+        append(&log, &format!("{{\"id\":1}}\n{{\"pad\":\"{}", "x".repeat(MAX_LINE_BYTES)));
+        let first = tail(&pattern, "usage", &data_dir).unwrap();
+        assert_eq!(ids(&first), vec![1]);
+        assert_eq!(first.skipped, 0);
+
+        append(&log, "\", \"id\": 9}\n{\"id\":2}\n");
+        let second = tail(&pattern, "usage", &data_dir).unwrap();
+        assert_eq!(ids(&second), vec![2]);
+        assert_eq!(second.skipped, 1);
+    }
+
+    #[test]
+    fn restarts_after_truncation_or_replacement() {
+        let root = TempDir::new("tail-truncate");
+        let data_dir = root.join("data");
+        let log = root.join("a.jsonl");
+        let pattern = format!("{}/*.jsonl", root.display());
+
+        append(&log, "{\"id\":1}\n{\"id\":2}\n");
+        tail(&pattern, "k", &data_dir).unwrap();
+
+        std::fs::write(&log, "{\"id\":9}\n").unwrap();
+        assert_eq!(ids(&tail(&pattern, "k", &data_dir).unwrap()), vec![9]);
+
+        // Same length or longer, different content.
+        std::fs::write(&log, "{\"id\":7}\n{\"id\":8}\n").unwrap();
+        assert_eq!(ids(&tail(&pattern, "k", &data_dir).unwrap()), vec![7, 8]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rotated_files_keep_their_offset() {
+        let root = TempDir::new("tail-rotate");
+        let data_dir = root.join("data");
+        let log = root.join("session.jsonl");
+        let pattern = format!("{}/session.jsonl*", root.display());
+
+        append(&log, "{\"id\":1}\n");
+        tail(&pattern, "k", &data_dir).unwrap();
+
+        let rotated = root.join("session.jsonl.1");
+        std::fs::rename(&log, &rotated).unwrap();
+        append(&rotated, "{\"id\":2}\n");
+        append(&log, "{\"id\":3}\n");
+        let mut got = ids(&tail(&pattern, "k", &data_dir).unwrap());
+        got.sort();
+        assert_eq!(got, vec![2, 3]);
+    }
+}
@@ -0,0 +1,259 @@
+//! Per-plugin key-value storage for `host.store`.
+//!
+//! All plugins share one SQLite database in the app data dir; rows are keyed
+//! by plugin id so a plugin only ever sees its own keys. Values are JSON text.
+//! Expired rows are hidden on read and purged on write. Rows belonging to
+//! plugins that are no longer installed are removed at startup.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DB_FILE: &str = "plugin_store.sqlite3";
+pub const MAX_KEY_BYTES: usize = 256;
+pub const MAX_VALUE_BYTES: usize = 1024 * 1024;
+pub const MAX_KEYS_PER_PLUGIN: usize = 10_000;
+
+/// Probes run in parallel and all write to the same file.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreEntry {
+    pub key: String,
+    /// JSON text as stored.
+    pub value: String,
+    pub expires_at: Option<i64>,
+    pub updated_at: i64,
+}
+
+pub struct PluginStore {
+    path: PathBuf,
+    plugin_id: String,
+}
+
+impl PluginStore {
+    pub fn new(app_data_dir: &Path, plugin_id: &str) -> Self {
+        Self {
+            path: app_data_dir.join(DB_FILE),
+            plugin_id: plugin_id.to_string(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let conn = open(&self.path)?;
+        conn.query_row(
+            "SELECT value FROM kv WHERE plugin_id = ?1 AND key = ?2 \
+             AND (expires_at IS NULL OR expires_at > ?3)",
+            params![self.plugin_id, key, now_ms()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    /// `value` is JSON text. `ttl` of `None` keeps the entry until deleted.
+    pub fn set(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), String> {
+        if key.is_empty() || key.len() > MAX_KEY_BYTES {
+            return Err(format!("store key must be 1-{} bytes", MAX_KEY_BYTES));
+        }
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(format!("store value is larger than {} bytes", MAX_VALUE_BYTES));
+        }
+        let now = now_ms();
+        let expires_at = ttl.map(|ttl| now.saturating_add(ttl.as_millis() as i64));
+
+        let mut conn = open(&self.path)?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM kv WHERE plugin_id = ?1 AND expires_at IS NOT NULL AND expires_at <= ?2",
+            params![self.plugin_id, now],
+        )
+        .map_err(|e| e.to_string())?;
+        let (count, exists): (usize, bool) = tx
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(key = ?2), 0) > 0 FROM kv WHERE plugin_id = ?1",
+                params![self.plugin_id, key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists && count >= MAX_KEYS_PER_PLUGIN {
+            return Err(format!("store is full ({} keys)", MAX_KEYS_PER_PLUGIN));
+        }
+        tx.execute(
+            "INSERT INTO kv (plugin_id, key, value, expires_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5) \
+             ON CONFLICT (plugin_id, key) DO UPDATE SET \
+             value = excluded.value, expires_at = excluded.expires_at, updated_at = excluded.updated_at",
+            params![self.plugin_id, key, value, expires_at, now],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Returns whether the key existed.
+    pub fn delete(&self, key: &str) -> Result<bool, String> {
+        let conn = open(&self.path)?;
+        let removed = conn
+            .execute(
+                "DELETE FROM kv WHERE plugin_id = ?1 AND key = ?2",
+                params![self.plugin_id, key],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(removed > 0)
+    }
+
+    /// Live keys starting with `prefix`, sorted.
+    pub fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .map(|entry| entry.key)
+            .filter(|key| key.starts_with(prefix))
+            .collect())
+    }
+
+    /// Live entries, sorted by key.
+    pub fn entries(&self) -> Result<Vec<StoreEntry>, String> {
+        let conn = open(&self.path)?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT key, value, expires_at, updated_at FROM kv WHERE plugin_id = ?1 \
+                 AND (expires_at IS NULL OR expires_at > ?2) ORDER BY key",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![self.plugin_id, now_ms()], |row| {
+                Ok(StoreEntry {
+                    key: row.get(0)?,
+                    value: row.get(1)?,
+                    expires_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Deletes every entry. Returns rows removed.
+    pub fn clear(&self) -> Result<usize, String> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let conn = open(&self.path)?;
+        conn.execute("DELETE FROM kv WHERE plugin_id = ?1", params![self.plugin_id])
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Drops every row whose plugin is not in `installed`. Returns rows removed.
+pub fn prune_uninstalled(app_data_dir: &Path, installed: &[String]) -> Result<usize, String> {
+    let path = app_data_dir.join(DB_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let conn = open(&path)?;
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT plugin_id FROM kv")
+        .map_err(|e| e.to_string())?;
+    let stored: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut removed = 0;
+    for plugin_id in stored.iter().filter(|id| !installed.contains(id)) {
+        removed += conn
+            .execute("DELETE FROM kv WHERE plugin_id = ?1", params![plugin_id])
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(removed)
+}
+
+fn open(path: &Path) -> Result<Connection, String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         CREATE TABLE IF NOT EXISTS kv (
+             plugin_id TEXT NOT NULL,
+             key TEXT NOT NULL,
+             value TEXT NOT NULL,
+             expires_at INTEGER,
+             updated_at INTEGER NOT NULL,
+             PRIMARY KEY (plugin_id, key)
+         );",
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn stores_are_namespaced_by_plugin() {
+        let dir = TempDir::new("kv-namespace");
+        let claude = PluginStore::new(&dir, "claude");
+        let codex = PluginStore::new(&dir, "codex");
+
+        claude.set("session", "{\"n\":1}", None).unwrap();
+        claude.set("session", "{\"n\":2}", None).unwrap();
+        claude.set("usage:today", "5", None).unwrap();
+        codex.set("session", "\"other\"", None).unwrap();
+
+        assert_eq!(claude.get("session").unwrap().as_deref(), Some("{\"n\":2}"));
+        assert_eq!(codex.get("session").unwrap().as_deref(), Some("\"other\""));
+        assert_eq!(claude.list("").unwrap(), vec!["session", "usage:today"]);
+        assert_eq!(claude.list("usage:").unwrap(), vec!["usage:today"]);
+
+        assert!(claude.delete("session").unwrap());
+        assert!(!claude.delete("session").unwrap());
+        assert_eq!(claude.get("session").unwrap(), None);
+        assert!(codex.get("session").unwrap().is_some());
+
+        assert!(claude.set("", "1", None).is_err());
+        assert!(claude.set(&"k".repeat(MAX_KEY_BYTES + 1), "1", None).is_err());
+    }
+
+    #[test]
+    fn expired_entries_are_hidden() {
+        let dir = TempDir::new("kv-ttl");
+        let store = PluginStore::new(&dir, "claude");
+        store.set("short", "1", Some(Duration::from_millis(1))).unwrap();
+        store.set("long", "2", Some(Duration::from_secs(3600))).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert_eq!(store.get("short").unwrap(), None);
+        assert_eq!(store.get("long").unwrap().as_deref(), Some("2"));
+        let entries = store.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].expires_at.is_some());
+    }
+
+    #[test]
+    fn prune_removes_uninstalled_plugins() {
+        let dir = TempDir::new("kv-prune");
+        assert_eq!(prune_uninstalled(&dir, &[]).unwrap(), 0);
+        PluginStore::new(&dir, "claude").set("a", "1", None).unwrap();
+        PluginStore::new(&dir, "gone").set("a", "1", None).unwrap();
+        PluginStore::new(&dir, "gone").set("b", "1", None).unwrap();
+
+        assert_eq!(prune_uninstalled(&dir, &["claude".to_string()]).unwrap(), 2);
+        assert_eq!(PluginStore::new(&dir, "gone").list("").unwrap().len(), 0);
+        assert_eq!(PluginStore::new(&dir, "claude").list("").unwrap(), vec!["a"]);
+    }
+}
@@ -0,0 +1,246 @@
+//! Language-server discovery on Linux without `ps` or `lsof`.
+//!
+//! Processes come from `/proc/<pid>/cmdline`, which keeps the exact argv (so
+//! paths like "Windsurf - Next" survive). Listening ports come from
+//! `/proc/net/tcp{,6}`: each LISTEN socket's inode is matched against the
+//! `socket:[inode]` links in `/proc/<pid>/fd`.
+//!
+//! Everything takes the proc root as a parameter so tests can use a fake tree.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// `st` value of a listening socket in `/proc/net/tcp`.
+const TCP_LISTEN: &str = "0A";
+
+/// Kernel `USER_HZ`, the unit of `starttime` in `/proc/<pid>/stat`. It is 100
+/// on every mainstream architecture.
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LsProcess {
+    pub pid: i32,
+    pub argv: Vec<String>,
+    /// Unix start time in ms, when the platform exposes it.
+    pub started_at_ms: Option<u64>,
+}
+
+impl LsProcess {
+    /// Space-joined argv, for substring matching and logging.
+    pub fn command(&self) -> String {
+        self.argv.join(" ")
+    }
+}
+
+/// All processes with a readable, non-empty cmdline, in pid order.
+/// Returns `None` if the proc root itself cannot be read.
+pub fn list_processes(proc_root: &Path) -> Option<Vec<LsProcess>> {
+    let entries = std::fs::read_dir(proc_root).ok()?;
+    let boot_ms = std::fs::read_to_string(proc_root.join("stat"))
+        .ok()
+        .and_then(|text| parse_boot_time_ms(&text));
+    let mut out = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        // Processes can exit between read_dir and here; skip them quietly.
+        let Ok(raw) = std::fs::read(entry.path().join("cmdline")) else {
+            continue;
+        };
+        let argv = parse_cmdline(&raw);
+        if argv.is_empty() {
+            continue;
+        }
+        let started_at_ms = std::fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|stat| parse_start_ticks(&stat))
+            .zip(boot_ms)
+            .map(|(ticks, boot_ms)| boot_ms + ticks * 1000 / CLOCK_TICKS_PER_SEC);
+        out.push(LsProcess {
+            pid,
+            argv,
+            started_at_ms,
+        });
+    }
+    out.sort_by_key(|process| process.pid);
+    Some(out)
+}
+
+/// Split a NUL-terminated `/proc/<pid>/cmdline` into argv. Empty arguments
+/// are kept; only the element after the final NUL is dropped.
+pub fn parse_cmdline(raw: &[u8]) -> Vec<String> {
+    let mut argv: Vec<String> = raw
+        .split(|byte| *byte == 0)
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    if argv.last().is_some_and(String::is_empty) {
+        argv.pop();
+    }
+    argv
+}
+
+/// `btime` line of `/proc/stat`: boot time in Unix seconds, returned as ms.
+pub fn parse_boot_time_ms(text: &str) -> Option<u64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|secs| secs * 1000)
+}
+
+/// Field 22 (`starttime`, clock ticks after boot) of `/proc/<pid>/stat`.
+/// The command name in field 2 may contain spaces and parentheses, so fields
+/// are counted from the last `)`.
+pub fn parse_start_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // `rest` starts at field 3 (state), so starttime is its 20th field.
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Ports the process is listening on (IPv4 and IPv6), sorted.
+/// Returns `None` if neither `/proc/net/tcp` nor `/proc/net/tcp6` is readable.
+pub fn listening_ports(proc_root: &Path, pid: i32) -> Option<Vec<i32>> {
+    let mut listening = HashMap::new();
+    let mut readable = false;
+    for table in ["net/tcp", "net/tcp6"] {
+        if let Ok(text) = std::fs::read_to_string(proc_root.join(table)) {
+            readable = true;
+            listening.extend(parse_listening_inodes(&text));
+        }
+    }
+    if !readable {
+        return None;
+    }
+
+    let mut ports = BTreeSet::new();
+    let fd_dir = proc_root.join(pid.to_string()).join("fd");
+    if let Ok(entries) = std::fs::read_dir(fd_dir) {
+        for entry in entries.flatten() {
+            let Ok(target) = std::fs::read_link(entry.path()) else {
+                continue;
+            };
+            if let Some(port) = socket_inode(&target.to_string_lossy())
+                .and_then(|inode| listening.get(&inode))
+            {
+                ports.insert(*port);
+            }
+        }
+    }
+    Some(ports.into_iter().collect())
+}
+
+/// Map inode -> local port for every LISTEN row of a `/proc/net/tcp{,6}` table.
+pub fn parse_listening_inodes(text: &str) -> HashMap<u64, i32> {
+    let mut out = HashMap::new();
+    // Columns: sl local_address rem_address st tx:rx tr:when retrnsmt uid timeout inode
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || fields[3] != TCP_LISTEN {
+            continue;
+        }
+        let Some(port) = fields[1]
+            .rsplit_once(':')
+            .and_then(|(_, hex)| i32::from_str_radix(hex, 16).ok())
+        else {
+            continue;
+        };
+        if let Ok(inode) = fields[9].parse::<u64>()
+            && inode != 0
+            && port > 0
+        {
+            out.insert(inode, port);
+        }
+    }
+    out
+}
+
+/// `socket:[12345]` -> `12345`.
+fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::plugin_engine::test_util::TempDir;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:A1B2 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:0050 0100007F:C350 01 00000000:00000000 00:00000000 00000000  1000        0 4343 1 0000000000000000 20 4 30 10 -1
+";
+    const TCP6: &str = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 5151 1 0000000000000000 100 0 0 10 0
+";
+
+    #[test]
+    fn parse_cmdline_keeps_spaces_and_empty_arguments() {
+        let raw = b"/opt/Windsurf - Next/language_server\0--ide_name\0windsurf-next\0";
+        assert_eq!(
+            parse_cmdline(raw),
+            vec![
+                "/opt/Windsurf - Next/language_server".to_string(),
+                "--ide_name".to_string(),
+                "windsurf-next".to_string(),
+            ]
+        );
+        assert_eq!(
+            parse_cmdline(b"server\0--token\0\0--port\0"),
+            vec!["server", "--token", "", "--port"]
+        );
+        assert!(parse_cmdline(b"").is_empty());
+    }
+
+    #[test]
+    fn parse_start_time_handles_spaces_in_comm() {
+        let stat = "1234 (language server) S 1 1234 1234 0 -1 4194560 100 0 0 0 5 3 0 0 20 0 12 0 4500 123456 789";
+        assert_eq!(parse_start_ticks(stat), Some(4500));
+        assert_eq!(parse_boot_time_ms("cpu 1 2 3\nbtime 1700000000\n"), Some(1_700_000_000_000));
+        assert_eq!(parse_start_ticks("garbage"), None);
+    }
+
+    #[test]
+    fn parse_listening_inodes_skips_non_listen_rows() {
+        let inodes = parse_listening_inodes(TCP);
+        assert_eq!(inodes.get(&4242), Some(&0xA1B2));
+        assert!(!inodes.contains_key(&4343));
+        assert_eq!(parse_listening_inodes(TCP6).get(&5151), Some(&8080));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fake_proc_tree_resolves_processes_and_ports() {
+        let root = TempDir::new("procfs-tree");
+        let proc_dir = root.join("1234");
+        std::fs::create_dir_all(proc_dir.join("fd")).expect("fd dir");
+        std::fs::create_dir_all(root.join("net")).expect("net dir");
+        std::fs::write(root.join("net/tcp"), TCP).expect("tcp");
+        std::fs::write(root.join("net/tcp6"), TCP6).expect("tcp6");
+        std::fs::write(proc_dir.join("cmdline"), b"ls\0--csrf_token\0abc\0").expect("cmdline");
+        std::fs::write(root.join("stat"), "btime 1700000000\n").expect("stat");
+        std::fs::write(
+            proc_dir.join("stat"),
+            "1234 (ls) S 1 1234 1234 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 250 0 0",
+        )
+        .expect("pid stat");
+        std::os::unix::fs::symlink("socket:[4242]", proc_dir.join("fd/3")).expect("fd 3");
+        std::os::unix::fs::symlink("socket:[4343]", proc_dir.join("fd/4")).expect("fd 4");
+        std::os::unix::fs::symlink("socket:[5151]", proc_dir.join("fd/5")).expect("fd 5");
+        std::os::unix::fs::symlink("/dev/null", proc_dir.join("fd/0")).expect("fd 0");
+
+        let processes = list_processes(&root).expect("processes");
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].pid, 1234);
+        assert_eq!(processes[0].command(), "ls --csrf_token abc");
+        assert_eq!(processes[0].started_at_ms, Some(1_700_000_002_500));
+        assert_eq!(listening_ports(&root, 1234), Some(vec![8080, 0xA1B2]));
+        assert_eq!(listening_ports(&root.join("missing"), 1234), None);
+    }
+}
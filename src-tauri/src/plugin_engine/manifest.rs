@@ -1,6 +1,13 @@
+use crate::plugin_engine::declarative::{self, DeclarativeSpec};
+use crate::plugin_engine::native::NativeProvider;
+use crate::plugin_engine::oauth::{CredentialSource, LoginConfig, OAuthConfig};
+use crate::plugin_engine::plugin_settings::{self, SettingField};
+use crate::plugin_engine::stdlib;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +21,23 @@ pub struct ManifestLine {
     pub primary_order: Option<u32>,
 }
 
+/// Host capabilities a plugin must declare before it can use them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginPermissions {
+    /// Exact argv lists `host.exec` may run, e.g. `["gh", "auth", "token"]`.
+    #[serde(default)]
+    pub exec: Vec<Vec<String>>,
+    /// Databases `host.sqlite.exec` may write to (`~` allowed). Queries stay
+    /// read-only and need no declaration.
+    #[serde(default)]
+    pub sqlite_write: Vec<String>,
+    /// Environment variables `host.env.get` and declarative `{{env.NAME}}`
+    /// templates may read, beyond the built-in allowlist.
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginManifest {
@@ -21,10 +45,27 @@ pub struct PluginManifest {
     pub id: String,
     pub name: String,
     pub version: String,
+    /// Entry script; empty for declarative plugins.
+    #[serde(default)]
     pub entry: String,
     pub icon: String,
     pub brand_color: Option<String>,
     pub lines: Vec<ManifestLine>,
+    #[serde(default)]
+    pub permissions: PluginPermissions,
+    /// Token refresh handled by the host; enables `host.oauth`.
+    #[serde(default)]
+    pub oauth: Option<OAuthConfig>,
+    /// User-entered configuration, passed to the probe as `ctx.config`.
+    #[serde(default)]
+    pub settings: Vec<SettingField>,
+    /// Requests and line mappings evaluated by the host instead of a script.
+    #[serde(default)]
+    pub declarative: Option<DeclarativeSpec>,
+    /// Pinned major version of each `openusage:<name>` module the entry
+    /// script requires (see `stdlib`).
+    #[serde(default)]
+    pub stdlib: BTreeMap<String, u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +74,12 @@ pub struct LoadedPlugin {
     pub plugin_dir: PathBuf,
     pub entry_script: String,
     pub icon_data_url: String,
+    /// Id of the plugin this is an added account of (see `instances`).
+    pub instance_of: Option<String>,
+    /// Set for providers compiled into the app (see `native`).
+    pub native: Option<NativeProvider>,
+    /// The module bytes when `entry` is a `.wasm` file (see `wasm`).
+    pub entry_wasm: Option<Arc<[u8]>>,
 }
 
 pub fn load_plugins_from_dir(plugins_dir: &std::path::Path) -> Vec<LoadedPlugin> {
@@ -79,37 +126,107 @@ fn load_single_plugin(
         }
     }
 
-    if manifest.entry.trim().is_empty() {
-        return Err("plugin entry field cannot be empty".into());
-    }
-    if Path::new(&manifest.entry).is_absolute() {
-        return Err("plugin entry must be a relative path".into());
+    for argv in manifest.permissions.exec.iter() {
+        if argv.first().is_none_or(|program| program.trim().is_empty()) {
+            return Err("plugin exec permission must start with a program name".into());
+        }
     }
 
-    let entry_path = plugin_dir.join(&manifest.entry);
-    let canonical_plugin_dir = plugin_dir.canonicalize()?;
-    let canonical_entry_path = entry_path.canonicalize()?;
-    if !canonical_entry_path.starts_with(&canonical_plugin_dir) {
-        return Err("plugin entry must remain within plugin directory".into());
-    }
-    if !canonical_entry_path.is_file() {
-        return Err("plugin entry must be a file".into());
+    if let Some(oauth) = &manifest.oauth {
+        let source = match &oauth.source {
+            CredentialSource::File { path } => path,
+            CredentialSource::Keychain { service } => service,
+        };
+        if oauth.token_url.trim().is_empty()
+            || oauth.client_id.trim().is_empty()
+            || source.trim().is_empty()
+        {
+            return Err("plugin oauth needs tokenUrl, clientId and a credential source".into());
+        }
+        let login_url = match &oauth.login {
+            Some(LoginConfig::Pkce { authorize_url, .. }) => Some(authorize_url),
+            Some(LoginConfig::DeviceCode { device_authorization_url }) => {
+                Some(device_authorization_url)
+            }
+            None => None,
+        };
+        if login_url.is_some_and(|url| url.trim().is_empty()) {
+            return Err("plugin oauth.login needs its authorization URL".into());
+        }
     }
 
-    let entry_script = std::fs::read_to_string(&canonical_entry_path)?;
+    plugin_settings::validate_schema(&manifest.settings)?;
+    stdlib::validate(&manifest.stdlib)?;
 
     let icon_file = plugin_dir.join(&manifest.icon);
     let icon_bytes = std::fs::read(&icon_file)?;
     let icon_data_url = format!("data:image/svg+xml;base64,{}", STANDARD.encode(&icon_bytes));
 
+    if let Some(spec) = &manifest.declarative {
+        if !manifest.entry.trim().is_empty() {
+            return Err("declarative plugins cannot also have an entry script".into());
+        }
+        declarative::validate(spec, &manifest.permissions.env)?;
+        return Ok(LoadedPlugin {
+            manifest,
+            plugin_dir: plugin_dir.to_path_buf(),
+            entry_script: String::new(),
+            icon_data_url,
+            instance_of: None,
+            native: None,
+            entry_wasm: None,
+        });
+    }
+
+    if manifest.entry.trim().is_empty() {
+        return Err("plugin entry field cannot be empty".into());
+    }
+    let canonical_entry_path =
+        resolve_plugin_file(plugin_dir, Path::new(&manifest.entry), "plugin entry")?;
+
+    let (entry_script, entry_wasm) = if manifest.entry.ends_with(".wasm") {
+        let bytes = std::fs::read(&canonical_entry_path)?;
+        (String::new(), Some(Arc::from(bytes)))
+    } else {
+        (std::fs::read_to_string(&canonical_entry_path)?, None)
+    };
+
     Ok(LoadedPlugin {
         manifest,
         plugin_dir: plugin_dir.to_path_buf(),
         entry_script,
         icon_data_url,
+        instance_of: None,
+        native: None,
+        entry_wasm,
     })
 }
 
+/// Resolves a path relative to the plugin directory, requiring a file that is
+/// still inside the directory once `..` and symlinks are resolved. `what`
+/// names the path in errors. Used for `entry` and for `require()`.
+pub(crate) fn resolve_plugin_file(
+    plugin_dir: &Path,
+    relative: &Path,
+    what: &str,
+) -> Result<PathBuf, String> {
+    if relative.is_absolute() {
+        return Err(format!("{} must be a relative path", what));
+    }
+    let canonical_plugin_dir = plugin_dir.canonicalize().map_err(|e| e.to_string())?;
+    let canonical_path = plugin_dir.join(relative).canonicalize().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} not found", what),
+        _ => e.to_string(),
+    })?;
+    if !canonical_path.starts_with(&canonical_plugin_dir) {
+        return Err(format!("{} must remain within plugin directory", what));
+    }
+    if !canonical_path.is_file() {
+        return Err(format!("{} must be a file", what));
+    }
+    Ok(canonical_path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,4 +316,71 @@ mod tests {
 
         assert_eq!(labels, vec!["First", "Second", "Third"]);
     }
+
+    #[test]
+    fn permissions_default_to_none_and_parse_exec() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": []
+            }
+            "#,
+        );
+        assert!(manifest.permissions.exec.is_empty());
+
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "permissions": { "exec": [["gh", "auth", "token"]] }
+            }
+            "#,
+        );
+        assert_eq!(manifest.permissions.exec, vec![vec!["gh", "auth", "token"]]);
+    }
+
+    #[test]
+    fn oauth_section_parses_with_defaults() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "oauth": {
+                "tokenUrl": "https://auth.example.com/token",
+                "clientId": "cli",
+                "source": { "type": "keychain", "service": "Example-credentials" },
+                "fields": { "accessToken": "oauth.access", "expiresAtUnit": "milliseconds" },
+                "login": { "type": "deviceCode", "deviceAuthorizationUrl": "https://auth.example.com/device" }
+              }
+            }
+            "#,
+        );
+        let oauth = manifest.oauth.expect("oauth");
+        assert!(matches!(oauth.source, CredentialSource::Keychain { ref service } if service == "Example-credentials"));
+        assert_eq!(oauth.fields.access_token, "oauth.access");
+        assert_eq!(oauth.fields.refresh_token, "refresh_token");
+        assert_eq!(oauth.refresh_buffer_secs, 300);
+        assert_eq!(oauth.login.map(|login| login.kind()), Some("deviceCode"));
+    }
 }
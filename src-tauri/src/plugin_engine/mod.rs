@@ -1,6 +1,30 @@
+pub mod credential_store;
+pub mod crypto;
+pub mod declarative;
+pub mod exec;
+pub mod fs_atomic;
+pub mod fs_read;
 pub mod host_api;
+pub mod http_cache;
+pub mod instances;
+pub mod jsonl_tail;
+pub mod kv_store;
+pub mod ls_procfs;
 pub mod manifest;
+pub mod modules;
+pub mod native;
+pub mod oauth;
+pub mod oauth_login;
+pub mod plugin_settings;
+pub mod pricing;
 pub mod runtime;
+pub mod stdlib;
+#[cfg(test)]
+mod test_harness;
+#[cfg(test)]
+mod test_util;
+pub mod vcr;
+pub mod wasm;
 
 use manifest::LoadedPlugin;
 use std::path::{Path, PathBuf};
@@ -11,7 +35,7 @@ pub fn initialize_plugins(
 ) -> (PathBuf, Vec<LoadedPlugin>) {
     if let Some(dev_dir) = find_dev_plugins_dir() {
         if !is_dir_empty(&dev_dir) {
-            let plugins = manifest::load_plugins_from_dir(&dev_dir);
+            let plugins = with_native(manifest::load_plugins_from_dir(&dev_dir));
             return (dev_dir, plugins);
         }
     }
@@ -26,10 +50,16 @@ pub fn initialize_plugins(
         copy_dir_recursive(&bundled_dir, &install_dir);
     }
 
-    let plugins = manifest::load_plugins_from_dir(&install_dir);
+    let plugins = with_native(manifest::load_plugins_from_dir(&install_dir));
     (install_dir, plugins)
 }
 
+fn with_native(mut plugins: Vec<LoadedPlugin>) -> Vec<LoadedPlugin> {
+    plugins.extend(native::load(&plugins));
+    plugins.sort_by(|a, b| a.manifest.id.cmp(&b.manifest.id));
+    plugins
+}
+
 fn find_dev_plugins_dir() -> Option<PathBuf> {
     let cwd = std::env::current_dir().ok()?;
     let direct = cwd.join("plugins");
@@ -0,0 +1,234 @@
+//! CommonJS-style `require()` for plugins split over several files.
+//!
+//! Plugin files are required by relative path, resolved from the requiring
+//! file and checked with `manifest::resolve_plugin_file`, so a module can
+//! never load anything outside its plugin directory. Modules are read when
+//! first required and cached for the rest of the probe; every probe starts
+//! from a fresh QuickJS runtime, so edits are picked up on the next refresh.
+//!
+//! `openusage:<name>` loads a module shipped with the app (see `stdlib`) at
+//! the major version pinned in the manifest.
+
+use crate::plugin_engine::manifest::{self, LoadedPlugin};
+use crate::plugin_engine::stdlib;
+use rquickjs::context::EvalOptions;
+use rquickjs::{Ctx, Function, Object};
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+const STDLIB_PREFIX: &str = "openusage:";
+
+/// Installs `require` for the entry script. Module ids are paths relative to
+/// the plugin directory, e.g. `lib/auth.js`, or `openusage:<name>@<major>`.
+pub fn inject_require<'js>(ctx: &Ctx<'js>, plugin: &LoadedPlugin) -> rquickjs::Result<()> {
+    let dir = plugin.plugin_dir.clone();
+    let pins = plugin.manifest.stdlib.clone();
+    let resolve = Function::new(
+        ctx.clone(),
+        move |ctx_inner: Ctx<'_>, from: String, specifier: String| -> rquickjs::Result<String> {
+            resolve(&dir, &pins, &from, &specifier).map_err(|e| throw(&ctx_inner, &e))
+        },
+    )?;
+
+    let dir = plugin.plugin_dir.clone();
+    let pid = plugin.manifest.id.clone();
+    let compile = Function::new(
+        ctx.clone(),
+        move |ctx_inner: Ctx<'js>, id: String| -> rquickjs::Result<Function<'js>> {
+            compile(&ctx_inner, &dir, &id).map_err(|e| {
+                log::error!("[plugin:{}] {}", pid, e);
+                throw(&ctx_inner, &e)
+            })
+        },
+    )?;
+
+    let install: Function = ctx.eval(
+        r#"
+        (function (resolve, compile, entry) {
+            var cache = {};
+            function requireFrom(from) {
+                return function require(specifier) {
+                    var id = resolve(from, String(specifier));
+                    var cached = cache[id];
+                    if (cached) return cached.exports;
+                    var module = { id: id, exports: {} };
+                    // Cached before it runs so circular requires see partial exports, as in Node.
+                    cache[id] = module;
+                    try {
+                        compile(id).call(module.exports, module, module.exports, requireFrom(id));
+                    } catch (e) {
+                        delete cache[id];
+                        throw e;
+                    }
+                    return module.exports;
+                };
+            }
+            globalThis.require = requireFrom(entry);
+        })
+        "#,
+    )?;
+    let entry = normalize(Path::new(&plugin.manifest.entry)).unwrap_or_default();
+    install.call::<_, ()>((resolve, compile, entry))
+}
+
+/// Turns `specifier`, as required from module `from`, into a module id.
+/// A missing extension means `.js`.
+fn resolve(
+    plugin_dir: &Path,
+    pins: &BTreeMap<String, u32>,
+    from: &str,
+    specifier: &str,
+) -> Result<String, String> {
+    if let Some(name) = specifier.strip_prefix(STDLIB_PREFIX) {
+        return match pins.get(name) {
+            Some(major) => Ok(format!("{}{}@{}", STDLIB_PREFIX, name, major)),
+            None => Err(format!(
+                "require(\"{}\"): add \"{}\" to \"stdlib\" in plugin.json",
+                specifier, name
+            )),
+        };
+    }
+    if from.starts_with(STDLIB_PREFIX) {
+        return Err(format!("require(\"{}\") from {}: not allowed", specifier, from));
+    }
+    if !specifier.starts_with("./") && !specifier.starts_with("../") {
+        return Err(format!(
+            "require(\"{}\"): only relative paths inside the plugin or openusage: modules can be required",
+            specifier
+        ));
+    }
+    let base = Path::new(from).parent().unwrap_or(Path::new(""));
+    let mut relative = base.join(specifier);
+    if relative.extension().is_none() {
+        relative.set_extension("js");
+    }
+    let what = format!("require(\"{}\") from {}", specifier, from);
+    let relative = normalize(&relative)
+        .ok_or_else(|| format!("{} must remain within plugin directory", what))?;
+    manifest::resolve_plugin_file(plugin_dir, Path::new(&relative), &what)?;
+    Ok(relative)
+}
+
+/// Reads module `id` and compiles it into `function (module, exports, require)`.
+/// The body starts on the wrapper's first line, so error lines match the file.
+fn compile<'js>(ctx: &Ctx<'js>, plugin_dir: &Path, id: &str) -> Result<Function<'js>, String> {
+    let source = match id.strip_prefix(STDLIB_PREFIX) {
+        Some(pinned) => pinned
+            .split_once('@')
+            .and_then(|(name, major)| stdlib::source(name, major.parse().ok()?))
+            .map(str::to_string)
+            .ok_or_else(|| format!("{}: no such module", id))?,
+        None => {
+            let path = manifest::resolve_plugin_file(plugin_dir, Path::new(id), id)?;
+            std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", id, e))?
+        }
+    };
+    let mut options = EvalOptions::default();
+    options.strict = false;
+    options.filename = Some(id.to_string());
+    let wrapped = format!("(function (module, exports, require) {{{}\n}})", source);
+    ctx.eval_with_options(wrapped, options).map_err(|_| {
+        let exc = ctx.catch();
+        let err = exc.as_object();
+        let message = err
+            .and_then(|err: &Object| err.get::<_, String>("message").ok())
+            .unwrap_or_else(|| "failed to compile".to_string());
+        // QuickJS only reports the position in the stack: "    at lib/auth.js:12:5".
+        let stack = err
+            .and_then(|err: &Object| err.get::<_, String>("stack").ok())
+            .unwrap_or_default();
+        let line = stack
+            .split(&format!("{}:", id))
+            .nth(1)
+            .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+            .filter(|line| !line.is_empty());
+        match line {
+            Some(line) => format!("{}:{}: SyntaxError: {}", id, line, message),
+            None => format!("{}: SyntaxError: {}", id, message),
+        }
+    })
+}
+
+/// Lexically resolves `.` and `..` into a `/`-separated relative path, or
+/// `None` if it climbs above the plugin directory.
+fn normalize(path: &Path) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Throws `message` as a string, which is how plugin errors reach the UI.
+fn throw(ctx: &Ctx<'_>, message: &str) -> rquickjs::Error {
+    match rquickjs::String::from_str(ctx.clone(), message) {
+        Ok(value) => ctx.throw(value.into_value()),
+        Err(err) => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    #[test]
+    fn resolve_stays_inside_plugin_dir() {
+        let root = TempDir::new("modules");
+        let plugin_dir = root.join("demo");
+        std::fs::create_dir_all(plugin_dir.join("lib")).unwrap();
+        std::fs::write(plugin_dir.join("lib/auth.js"), "").unwrap();
+        std::fs::write(plugin_dir.join("util.js"), "").unwrap();
+        std::fs::write(root.join("secret.js"), "").unwrap();
+        let pins = BTreeMap::from([("vscdb".to_string(), 1)]);
+
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "plugin.js", "./lib/auth").unwrap(),
+            "lib/auth.js"
+        );
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "lib/auth.js", "../util.js").unwrap(),
+            "util.js"
+        );
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "plugin.js", "../secret.js").unwrap_err(),
+            "require(\"../secret.js\") from plugin.js must remain within plugin directory"
+        );
+        assert!(
+            resolve(&plugin_dir, &pins, "plugin.js", "fs")
+                .unwrap_err()
+                .contains("only relative paths")
+        );
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "plugin.js", "./missing").unwrap_err(),
+            "require(\"./missing\") from plugin.js not found"
+        );
+
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "lib/auth.js", "openusage:vscdb").unwrap(),
+            "openusage:vscdb@1"
+        );
+        assert_eq!(
+            resolve(&plugin_dir, &pins, "plugin.js", "openusage:oauth").unwrap_err(),
+            "require(\"openusage:oauth\"): add \"oauth\" to \"stdlib\" in plugin.json"
+        );
+        assert!(resolve(&plugin_dir, &pins, "openusage:vscdb@1", "../secret.js").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("secret.js"), plugin_dir.join("link.js")).unwrap();
+            assert!(
+                resolve(&plugin_dir, &pins, "plugin.js", "./link.js")
+                    .unwrap_err()
+                    .ends_with("must remain within plugin directory")
+            );
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Claude Code spend estimated from its local session transcripts.
+//!
+//! Transcripts under `~/.claude/projects/*/*.jsonl` are read incrementally
+//! with `jsonl_tail`, priced with the shared pricing table and summed into
+//! per-day totals kept in the plugin data dir, so each probe only parses what
+//! was appended since the last one. Days are UTC.
+
+use super::{Probe, ProbeContext, Provider};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::jsonl_tail;
+use crate::plugin_engine::pricing::{PriceTable, Usage};
+use crate::plugin_engine::runtime::MetricLine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+const MANIFEST: &str = r##"{
+  "schemaVersion": 1,
+  "id": "claude-logs",
+  "name": "Claude Code (local)",
+  "version": "0.0.1",
+  "icon": "icon.svg",
+  "brandColor": "#DE7356",
+  "lines": [
+    { "type": "text", "label": "Today", "scope": "overview" },
+    { "type": "text", "label": "Last 7 days", "scope": "overview" },
+    { "type": "text", "label": "Last 30 days", "scope": "detail" },
+    { "type": "text", "label": "Unpriced", "scope": "detail" }
+  ],
+  "settings": [
+    {
+      "key": "projectsDir",
+      "label": "Projects folder",
+      "type": "path",
+      "default": "~/.claude/projects",
+      "description": "Where Claude Code keeps session transcripts"
+    }
+  ]
+}"##;
+
+const DAILY_FILE: &str = "daily.json";
+/// Days of totals kept; older days are dropped.
+const KEEP_DAYS: i64 = 90;
+/// `jsonl_tail` calls per probe; a large backlog is finished by later probes.
+const MAX_TAIL_CALLS: usize = 16;
+
+pub struct ClaudeLogs;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayTotal {
+    cost: f64,
+    tokens: f64,
+    /// Messages from models missing in the pricing table.
+    unpriced: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Daily {
+    days: BTreeMap<String, DayTotal>,
+    /// `message.id:requestId` of every counted message, mapped to its day,
+    /// since resumed sessions repeat earlier messages in a new file. Pruned
+    /// with `days`.
+    #[serde(default)]
+    seen_ids: HashMap<String, String>,
+}
+
+impl Provider for ClaudeLogs {
+    fn manifest_json(&self) -> &'static str {
+        MANIFEST
+    }
+
+    fn icon_svg(&self) -> &'static str {
+        include_str!("claude_logs.svg")
+    }
+
+    fn probe(&self, ctx: &ProbeContext) -> Result<Probe, String> {
+        let projects = ctx.expand_path(ctx.config_str("projectsDir").unwrap_or("~/.claude/projects"));
+        if !Path::new(&projects).is_dir() {
+            return Err(format!(
+                "No Claude Code logs in `{}`. Set the projects folder in settings.",
+                projects
+            ));
+        }
+
+        let daily_path = ctx.plugin_data_dir.join(DAILY_FILE);
+        let mut daily: Daily = std::fs::read_to_string(&daily_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let (prices, warning) = PriceTable::load(ctx.app_data_dir);
+        if let Some(warning) = warning {
+            log::warn!("[plugin:{}] {}", ctx.plugin_id, warning);
+        }
+
+        let today = time::OffsetDateTime::now_utc().date();
+        let day = |days_ago: i64| (today - time::Duration::days(days_ago)).to_string();
+        let oldest_kept = day(KEEP_DAYS);
+
+        let pattern = format!("{}/*/*.jsonl", projects.trim_end_matches('/'));
+        for _ in 0..MAX_TAIL_CALLS {
+            let (result, checkpoint) = jsonl_tail::read(&pattern, "projects", &ctx.plugin_data_dir)?;
+            for record in &result.records {
+                add_record(&mut daily, &prices, &record.record);
+            }
+            // Totals are saved before the offsets that skip past their records;
+            // if the checkpoint is lost, `seen_ids` keeps the re-read ones out.
+            daily.days.retain(|date, _| *date >= oldest_kept);
+            daily.seen_ids.retain(|_, date| *date >= oldest_kept);
+            let json = serde_json::to_string(&daily).map_err(|e| e.to_string())?;
+            fs_atomic::write(&daily_path, json.as_bytes(), WriteOpts::default(), &ctx.plugin_data_dir)?;
+            checkpoint.save()?;
+            if !result.more {
+                break;
+            }
+        }
+
+        let since = |days_ago: i64| {
+            let first = day(days_ago);
+            daily
+                .days
+                .range(first..)
+                .fold(DayTotal::default(), |mut sum, (_, total)| {
+                    sum.cost += total.cost;
+                    sum.tokens += total.tokens;
+                    sum.unpriced += total.unpriced;
+                    sum
+                })
+        };
+        let month = since(29);
+        let mut lines = vec![
+            total_line("Today", &since(0)),
+            total_line("Last 7 days", &since(6)),
+            total_line("Last 30 days", &month),
+        ];
+        if month.unpriced > 0 {
+            lines.push(MetricLine::Text {
+                label: "Unpriced".to_string(),
+                value: match month.unpriced {
+                    1 => "1 message".to_string(),
+                    n => format!("{} messages", n),
+                },
+                color: None,
+                subtitle: Some("Models missing from the pricing table".to_string()),
+            });
+        }
+        Ok(Probe { plan: None, lines })
+    }
+}
+
+fn add_record(daily: &mut Daily, prices: &PriceTable, record: &Value) {
+    let Some(message) = record.get("message") else { return };
+    let Some(usage) = message.get("usage") else { return };
+    let Some(date) = record
+        .get("timestamp")
+        .and_then(Value::as_str)
+        .and_then(|ts| ts.get(..10))
+    else {
+        return;
+    };
+
+    let id = format!(
+        "{}:{}",
+        message.get("id").and_then(Value::as_str).unwrap_or_default(),
+        record.get("requestId").and_then(Value::as_str).unwrap_or_default()
+    );
+    if id != ":" && daily.seen_ids.insert(id, date.to_string()).is_some() {
+        return;
+    }
+
+    let tokens = |key: &str| usage.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+    let usage = Usage {
+        model: message
+            .get("model")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        input_tokens: tokens("input_tokens"),
+        output_tokens: tokens("output_tokens"),
+        cache_read_tokens: tokens("cache_read_input_tokens"),
+        cache_write_tokens: tokens("cache_creation_input_tokens"),
+    };
+    let total = daily.days.entry(date.to_string()).or_default();
+    total.tokens +=
+        usage.input_tokens + usage.output_tokens + usage.cache_read_tokens + usage.cache_write_tokens;
+    match prices.cost(&usage) {
+        Some(cost) => total.cost += cost,
+        None => total.unpriced += 1,
+    }
+}
+
+fn total_line(label: &str, total: &DayTotal) -> MetricLine {
+    MetricLine::Text {
+        label: label.to_string(),
+        value: format!("${:.2}", total.cost),
+        color: None,
+        subtitle: Some(format!("{} tokens", compact(total.tokens))),
+    }
+}
+
+fn compact(n: f64) -> String {
+    if n >= 1e9 {
+        format!("{:.1}B", n / 1e9)
+    } else if n >= 1e6 {
+        format!("{:.1}M", n / 1e6)
+    } else if n >= 1e3 {
+        format!("{:.1}K", n / 1e3)
+    } else {
+        format!("{}", n as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+    use serde_json::{json, Map};
+
+    fn entry(timestamp: &str, id: &str, model: &str, input: u64, output: u64) -> String {
+        json!({
+            "type": "assistant",
+            "timestamp": timestamp,
+            "requestId": format!("req_{}", id),
+            "message": {
+                "id": format!("msg_{}", id),
+                "model": model,
+                "usage": { "input_tokens": input, "output_tokens": output, "cache_read_input_tokens": 0 }
+            }
+        })
+        .to_string()
+    }
+
+    fn value(probe: &Probe, label: &str) -> String {
+        probe
+            .lines
+            .iter()
+            .find_map(|line| match line {
+                MetricLine::Text { label: l, value, .. } if l == label => Some(value.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("missing line {}", label))
+    }
+
+    #[test]
+    fn sums_priced_usage_per_day_without_double_counting() {
+        let root = TempDir::new("claude-logs-sum");
+        let projects = root.join("projects");
+        let app_data = root.join("app_data");
+        let data_dir = app_data.join("plugins_data").join("claude-logs");
+        std::fs::create_dir_all(projects.join("-work-repo")).unwrap();
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::write(
+            app_data.join("pricing.json"),
+            r#"{"version": "test", "models": {"claude-test": {"input": 3, "output": 15}}}"#,
+        )
+        .unwrap();
+
+        let now = time::OffsetDateTime::now_utc();
+        let today = format!("{}T10:00:00.000Z", now.date());
+        let old = format!("{}T10:00:00.000Z", now.date() - time::Duration::days(10));
+        let session = projects.join("-work-repo").join("a.jsonl");
+        std::fs::write(
+            &session,
+            [
+                entry(&old, "1", "claude-test", 1_000_000, 0),
+                entry(&today, "2", "claude-test", 0, 1_000_000),
+                entry(&today, "3", "mystery-model", 10, 10),
+                r#"{"type": "user", "message": {"role": "user"}}"#.to_string(),
+            ]
+            .join("\n")
+                + "\n",
+        )
+        .unwrap();
+        // A resumed session repeats messages 2 and 1 in another file; the older
+        // day's message comes after a newer one.
+        std::fs::write(
+            projects.join("-work-repo").join("b.jsonl"),
+            entry(&today, "2", "claude-test", 0, 1_000_000) + "\n" + &entry(&old, "1", "claude-test", 1_000_000, 0) + "\n",
+        )
+        .unwrap();
+
+        let config: Map<String, Value> =
+            serde_json::from_value(json!({ "projectsDir": projects.to_string_lossy() })).unwrap();
+        let ctx = ProbeContext {
+            plugin_id: "claude-logs",
+            config: &config,
+            app_data_dir: &app_data,
+            plugin_data_dir: data_dir.clone(),
+            home_dir: None,
+        };
+
+        let probe = ClaudeLogs.probe(&ctx).expect("probe");
+        assert_eq!(value(&probe, "Today"), "$15.00");
+        assert_eq!(value(&probe, "Last 7 days"), "$15.00");
+        assert_eq!(value(&probe, "Last 30 days"), "$18.00");
+        assert_eq!(value(&probe, "Unpriced"), "1 message");
+
+        // Only appended lines are read on the next probe.
+        let mut file = std::fs::OpenOptions::new().append(true).open(&session).unwrap();
+        std::io::Write::write_all(&mut file, (entry(&today, "4", "claude-test", 1_000_000, 0) + "\n").as_bytes()).unwrap();
+        std::fs::write(
+            projects.join("-work-repo").join("c.jsonl"),
+            entry(&old, "1", "claude-test", 1_000_000, 0) + "\n",
+        )
+        .unwrap();
+        let probe = ClaudeLogs.probe(&ctx).expect("probe");
+        assert_eq!(value(&probe, "Today"), "$18.00");
+        assert_eq!(value(&probe, "Last 30 days"), "$21.00");
+
+        let missing: Map<String, Value> = serde_json::from_value(json!({ "projectsDir": "/nonexistent/projects" })).unwrap();
+        let err = ClaudeLogs
+            .probe(&ProbeContext { config: &missing, ..ctx })
+            .unwrap_err();
+        assert!(err.starts_with("No Claude Code logs"));
+    }
+}
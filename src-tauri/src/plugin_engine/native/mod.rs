@@ -0,0 +1,125 @@
+//! Providers compiled into the app, for work that is awkward or slow in JS.
+//!
+//! A native provider ships a `plugin.json`-format manifest (without `entry`)
+//! and an SVG icon, and is loaded as a `LoadedPlugin` next to the script
+//! plugins. Listing, settings, instances and probe batches treat it like any
+//! other plugin; only `runtime` calls `Provider::probe` instead of QuickJS.
+
+mod claude_logs;
+
+use crate::plugin_engine::host_api;
+use crate::plugin_engine::manifest::{LoadedPlugin, PluginManifest};
+use crate::plugin_engine::plugin_settings;
+use crate::plugin_engine::runtime::MetricLine;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub trait Provider: Send + Sync {
+    /// The manifest, in the same JSON format as `plugin.json`.
+    fn manifest_json(&self) -> &'static str;
+    fn icon_svg(&self) -> &'static str;
+    /// Returns the plan label and lines, or a user-facing error message.
+    fn probe(&self, ctx: &ProbeContext) -> Result<Probe, String>;
+}
+
+/// What a native probe gets instead of the JS `ctx`.
+pub struct ProbeContext<'a> {
+    /// Instance id for added accounts, otherwise the manifest id.
+    pub plugin_id: &'a str,
+    pub config: &'a Map<String, Value>,
+    pub app_data_dir: &'a Path,
+    /// `<appDataDir>/plugins_data/<pluginId>`, created before the probe runs.
+    pub plugin_data_dir: PathBuf,
+    pub home_dir: Option<&'a Path>,
+}
+
+impl ProbeContext<'_> {
+    /// Expands a leading `~` like `host.fs` does.
+    pub fn expand_path(&self, path: &str) -> String {
+        host_api::expand_path(path, self.home_dir)
+    }
+
+    pub fn config_str(&self, key: &str) -> Option<&str> {
+        self.config.get(key).and_then(Value::as_str)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Probe {
+    pub plan: Option<String>,
+    pub lines: Vec<MetricLine>,
+}
+
+/// A native provider attached to its `LoadedPlugin`.
+#[derive(Clone)]
+pub struct NativeProvider(pub Arc<dyn Provider>);
+
+impl std::fmt::Debug for NativeProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NativeProvider")
+    }
+}
+
+fn builtin() -> Vec<Arc<dyn Provider>> {
+    vec![Arc::new(claude_logs::ClaudeLogs)]
+}
+
+/// Loads the compiled-in providers, leaving out ids already taken by `plugins`.
+pub fn load(plugins: &[LoadedPlugin]) -> Vec<LoadedPlugin> {
+    builtin()
+        .into_iter()
+        .filter_map(|provider| match loaded_plugin(provider) {
+            Ok(plugin) if plugins.iter().any(|p| p.manifest.id == plugin.manifest.id) => {
+                log::warn!(
+                    "native provider {} is shadowed by a plugin with the same id",
+                    plugin.manifest.id
+                );
+                None
+            }
+            Ok(plugin) => Some(plugin),
+            Err(err) => {
+                log::error!("failed to load native provider: {}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn loaded_plugin(provider: Arc<dyn Provider>) -> Result<LoadedPlugin, String> {
+    let manifest: PluginManifest =
+        serde_json::from_str(provider.manifest_json()).map_err(|e| e.to_string())?;
+    plugin_settings::validate_schema(&manifest.settings)?;
+    if !manifest.entry.is_empty() || manifest.declarative.is_some() {
+        return Err(format!("native provider {} cannot declare entry or declarative", manifest.id));
+    }
+    Ok(LoadedPlugin {
+        icon_data_url: format!(
+            "data:image/svg+xml;base64,{}",
+            STANDARD.encode(provider.icon_svg())
+        ),
+        manifest,
+        plugin_dir: PathBuf::new(),
+        entry_script: String::new(),
+        instance_of: None,
+        native: Some(NativeProvider(provider)),
+        entry_wasm: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_providers_load() {
+        let loaded = load(&[]);
+        assert_eq!(loaded.len(), builtin().len());
+        assert!(loaded.iter().all(|plugin| plugin.native.is_some()));
+
+        // A plugin on disk with the same id wins.
+        let shadowed = load(&loaded[..1]);
+        assert_eq!(shadowed.len(), loaded.len() - 1);
+    }
+}
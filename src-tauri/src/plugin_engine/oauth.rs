@@ -0,0 +1,862 @@
+//! OAuth token management for `host.oauth`.
+//!
+//! A plugin declares its token endpoint, client id and where the provider's
+//! CLI keeps credentials (a JSON file or a keychain item) under `oauth` in its
+//! manifest. The host then owns the refresh-token grant:
+//!
+//! - tokens are refreshed shortly before they expire, or on demand after a 401
+//! - concurrent probes sharing a credential source refresh it once; the others
+//!   wait and reuse the result
+//! - new tokens are written back to the source, preserving every other field
+//! - nothing token-bearing is logged
+//!
+//! Plugins that also declare `oauth.login` can be signed in from the app (see
+//! `oauth_login`); those tokens live in the credential store and are preferred
+//! over the CLI's own source unless the CLI's token expires later.
+
+use crate::plugin_engine::credential_store::{CredentialError, CredentialStore};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::host_api::HttpHost;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_REFRESH_BUFFER_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthConfig {
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub body_format: BodyFormat,
+    /// Refresh this long before `expiresAt`.
+    #[serde(default = "default_refresh_buffer_secs")]
+    pub refresh_buffer_secs: u64,
+    pub source: CredentialSource,
+    #[serde(default)]
+    pub fields: TokenFields,
+    /// How the app signs the user in when the CLI never did.
+    #[serde(default)]
+    pub login: Option<LoginConfig>,
+}
+
+fn default_refresh_buffer_secs() -> u64 {
+    DEFAULT_REFRESH_BUFFER_SECS
+}
+
+/// In-app sign-in flow, run by `oauth_login`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LoginConfig {
+    #[serde(rename_all = "camelCase")]
+    Pkce {
+        authorize_url: String,
+        /// 0 picks a free port; set it when the provider only allows a fixed redirect URI.
+        #[serde(default)]
+        redirect_port: u16,
+        #[serde(default = "default_redirect_path")]
+        redirect_path: String,
+        /// Added to the authorize URL, e.g. `{"prompt": "consent"}`.
+        #[serde(default)]
+        extra_params: BTreeMap<String, String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    DeviceCode { device_authorization_url: String },
+}
+
+fn default_redirect_path() -> String {
+    "/callback".to_string()
+}
+
+impl LoginConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Pkce { .. } => "pkce",
+            Self::DeviceCode { .. } => "deviceCode",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    #[default]
+    Form,
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CredentialSource {
+    /// JSON file; `~` is expanded.
+    File { path: String },
+    /// Keychain item holding JSON.
+    Keychain { service: String },
+}
+
+/// Dotted JSON paths into the stored credentials, e.g. `claudeAiOauth.accessToken`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TokenFields {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: Option<String>,
+    pub expires_at_unit: TimeUnit,
+    pub id_token: Option<String>,
+}
+
+impl Default for TokenFields {
+    fn default() -> Self {
+        Self {
+            access_token: "access_token".to_string(),
+            refresh_token: "refresh_token".to_string(),
+            expires_at: Some("expires_at".to_string()),
+            expires_at_unit: TimeUnit::Seconds,
+            id_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeUnit {
+    #[default]
+    Seconds,
+    Milliseconds,
+}
+
+impl TimeUnit {
+    fn now(self) -> f64 {
+        let ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as f64;
+        match self {
+            Self::Seconds => ms / 1000.0,
+            Self::Milliseconds => ms,
+        }
+    }
+
+    fn scale_secs(self, secs: f64) -> f64 {
+        match self {
+            Self::Seconds => secs,
+            Self::Milliseconds => secs * 1000.0,
+        }
+    }
+}
+
+/// Where credentials live once `~` has been expanded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceLocation {
+    File(PathBuf),
+    Keychain(String),
+}
+
+/// Credential store item holding tokens from an in-app login.
+pub fn login_service(plugin_id: &str) -> String {
+    format!("openusage:{}:oauth", plugin_id)
+}
+
+pub struct TokenRequest {
+    pub url: String,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+/// Sends a token request, returning status and body.
+pub type Transport<'a> = &'a dyn Fn(&TokenRequest) -> Result<(u16, String), String>;
+
+/// POSTs token requests through the plugin's HTTP host, so they get its
+/// URL overrides, VCR recording and retries. Only answers that mean the
+/// server never handled the request (429, 503) are retried, since a refresh
+/// token may rotate on use.
+pub(crate) fn http_transport(
+    http: Arc<HttpHost>,
+) -> impl Fn(&TokenRequest) -> Result<(u16, String), String> + Send + Sync + 'static {
+    move |req: &TokenRequest| {
+        let req_json = serde_json::json!({
+            "method": "POST",
+            "url": req.url,
+            "headers": {
+                "Content-Type": req.content_type,
+                "Accept": "application/json",
+            },
+            "bodyText": req.body,
+            "timeoutMs": 15_000,
+            "retry": { "statuses": [429, 503], "idempotentOnly": false },
+        });
+        let resp: Value = serde_json::from_str(&http.request(&req_json.to_string())?)
+            .map_err(|e| format!("invalid token response: {}", e))?;
+        let status = resp.get("status").and_then(Value::as_u64).unwrap_or(0) as u16;
+        let body = resp.get("bodyText").and_then(Value::as_str).unwrap_or("").to_string();
+        Ok((status, body))
+    }
+}
+
+/// Encodes `params` in the config's body format.
+pub(crate) fn token_request(config: &OAuthConfig, url: &str, params: &[(&str, &str)]) -> TokenRequest {
+    let (content_type, body) = match config.body_format {
+        BodyFormat::Form => (
+            "application/x-www-form-urlencoded",
+            params
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, form_encode(value)))
+                .collect::<Vec<_>>()
+                .join("&"),
+        ),
+        BodyFormat::Json => {
+            let map: serde_json::Map<String, Value> = params
+                .iter()
+                .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+                .collect();
+            ("application/json", Value::Object(map).to_string())
+        }
+    };
+    TokenRequest {
+        url: url.to_string(),
+        content_type,
+        body,
+    }
+}
+
+/// Copies a token response into `creds` at the configured paths. Returns the
+/// new access token, or `None` if the response has none.
+///
+/// Other response fields the credentials already hold next to the access
+/// token (`scope`, `token_type`, ...) are updated too, so the source keeps
+/// the shape its CLI wrote.
+pub(crate) fn apply_tokens(fields: &TokenFields, creds: &mut Value, body: &Value) -> Option<String> {
+    let access_token = body.get("access_token").and_then(Value::as_str)?.to_string();
+    let siblings = match fields.access_token.rsplit_once('.') {
+        Some((parent, _)) => get_path_mut(creds, parent),
+        None => Some(&mut *creds),
+    };
+    if let (Some(response), Some(siblings)) = (body.as_object(), siblings.and_then(Value::as_object_mut)) {
+        for (key, value) in response {
+            if !matches!(key.as_str(), "access_token" | "refresh_token" | "expires_in" | "id_token")
+                && siblings.contains_key(key)
+            {
+                siblings.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    set_path(creds, &fields.access_token, Value::String(access_token.clone()));
+    if let Some(rotated) = body.get("refresh_token").and_then(Value::as_str) {
+        set_path(creds, &fields.refresh_token, Value::String(rotated.to_string()));
+    }
+    if let (Some(path), Some(expires_in)) = (&fields.expires_at, body.get("expires_in").and_then(number)) {
+        let unit = fields.expires_at_unit;
+        let expires_at = (unit.now() + unit.scale_secs(expires_in)).floor();
+        set_path(creds, path, serde_json::json!(expires_at as i64));
+    }
+    if let (Some(path), Some(id_token)) = (
+        &fields.id_token,
+        body.get("id_token").and_then(Value::as_str),
+    ) {
+        set_path(creds, path, Value::String(id_token.to_string()));
+    }
+    Some(access_token)
+}
+
+enum RefreshError {
+    /// The server refused the refresh token; the user has to log in again.
+    Rejected(String),
+    /// Network or server trouble; the current token may still work.
+    Failed(String),
+}
+
+static REFRESH_LOCKS: LazyLock<Mutex<HashMap<String, Arc<Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn flight_lock(key: &str) -> Arc<Mutex<()>> {
+    let mut locks = REFRESH_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(locks.entry(key.to_string()).or_default())
+}
+
+pub struct OAuthManager {
+    plugin_id: String,
+    config: OAuthConfig,
+    location: SourceLocation,
+    /// Tokens from an in-app login, read when `location` has none.
+    login_location: Option<SourceLocation>,
+    data_dir: PathBuf,
+    store: Arc<dyn CredentialStore>,
+}
+
+impl OAuthManager {
+    /// `data_dir` holds backup files for file sources.
+    pub fn new(
+        plugin_id: &str,
+        config: OAuthConfig,
+        location: SourceLocation,
+        data_dir: PathBuf,
+        store: Arc<dyn CredentialStore>,
+    ) -> Self {
+        let login_location = config
+            .login
+            .as_ref()
+            .map(|_| SourceLocation::Keychain(login_service(plugin_id)));
+        Self {
+            plugin_id: plugin_id.to_string(),
+            config,
+            location,
+            login_location,
+            data_dir,
+            store,
+        }
+    }
+
+    /// The stored credentials, or `None` if the user never logged in.
+    pub fn credentials(&self) -> Result<Option<Value>, String> {
+        Ok(self.load()?.map(|(_, creds)| creds))
+    }
+
+    /// The in-app login wins over the CLI's source unless the CLI holds a
+    /// token that expires later, e.g. because the user signed in there again.
+    fn load(&self) -> Result<Option<(&SourceLocation, Value)>, String> {
+        let mut best: Option<(&SourceLocation, Value)> = None;
+        for location in self.login_location.iter().chain(std::iter::once(&self.location)) {
+            let Some(creds) = self.read(location)? else {
+                continue;
+            };
+            let better = match &best {
+                None => true,
+                Some((_, current)) if !self.has_token(current) => self.has_token(&creds),
+                Some((_, current)) => {
+                    self.has_token(&creds)
+                        && match (self.expires_at(current), self.expires_at(&creds)) {
+                            (Some(current), Some(candidate)) => candidate > current,
+                            _ => false,
+                        }
+                }
+            };
+            if better {
+                best = Some((location, creds));
+            }
+        }
+        Ok(best)
+    }
+
+    fn has_token(&self, creds: &Value) -> bool {
+        let fields = &self.config.fields;
+        self.field(creds, &fields.access_token).is_some()
+            || self.field(creds, &fields.refresh_token).is_some()
+    }
+
+    fn expires_at(&self, creds: &Value) -> Option<f64> {
+        self.config
+            .fields
+            .expires_at
+            .as_deref()
+            .and_then(|path| get_path(creds, path))
+            .and_then(number)
+    }
+
+    fn read(&self, location: &SourceLocation) -> Result<Option<Value>, String> {
+        let text = match location {
+            SourceLocation::File(path) => match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(format!("oauth credentials read failed: {}", e)),
+            },
+            SourceLocation::Keychain(service) => match self.store.read(service) {
+                Ok(text) => text,
+                Err(CredentialError::NotFound(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            },
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("oauth credentials are not valid JSON: {}", e))
+    }
+
+    /// A usable access token, refreshing first if it is about to expire.
+    /// Falls back to the current token when the refresh fails for reasons
+    /// other than the server rejecting it.
+    pub fn access_token(&self, transport: Transport) -> Result<Option<String>, String> {
+        let Some((_, creds)) = self.load()? else {
+            return Ok(None);
+        };
+        if !self.needs_refresh(&creds) {
+            return Ok(self.field(&creds, &self.config.fields.access_token));
+        }
+
+        let lock = flight_lock(&self.lock_key());
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        // Another probe may have refreshed while we waited.
+        let Some((location, creds)) = self.load()? else {
+            return Ok(None);
+        };
+        let current = self.field(&creds, &self.config.fields.access_token);
+        if !self.needs_refresh(&creds) {
+            return Ok(current);
+        }
+        if self.field(&creds, &self.config.fields.refresh_token).is_none() {
+            log::warn!("[plugin:{}] oauth refresh skipped: no refresh token", self.plugin_id);
+            return Ok(current);
+        }
+        match self.refresh_locked(location, creds, transport) {
+            Ok(token) => Ok(Some(token)),
+            Err(RefreshError::Rejected(e)) => Err(e),
+            Err(RefreshError::Failed(e)) => match current {
+                Some(token) => {
+                    log::warn!(
+                        "[plugin:{}] {}; using the current token",
+                        self.plugin_id,
+                        e
+                    );
+                    Ok(Some(token))
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Refreshes unconditionally, e.g. after a 401. Pass the token that was
+    /// rejected: if another probe has already replaced it, that newer token is
+    /// returned without a second refresh.
+    pub fn refresh(&self, rejected_token: Option<&str>, transport: Transport) -> Result<String, String> {
+        let lock = flight_lock(&self.lock_key());
+        let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (location, creds) = self
+            .load()?
+            .ok_or_else(|| "oauth credentials not found".to_string())?;
+        if let (Some(rejected), Some(current)) = (
+            rejected_token,
+            self.field(&creds, &self.config.fields.access_token),
+        ) && rejected != current
+        {
+            return Ok(current);
+        }
+        self.refresh_locked(location, creds, transport).map_err(|e| match e {
+            RefreshError::Rejected(e) | RefreshError::Failed(e) => e,
+        })
+    }
+
+    fn refresh_locked(
+        &self,
+        location: &SourceLocation,
+        mut creds: Value,
+        transport: Transport,
+    ) -> Result<String, RefreshError> {
+        let fields = &self.config.fields;
+        let refresh_token = self
+            .field(&creds, &fields.refresh_token)
+            .ok_or_else(|| RefreshError::Rejected("oauth refresh token missing".to_string()))?;
+
+        log::info!("[plugin:{}] oauth refreshing access token", self.plugin_id);
+        let mut params = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            params.push(("client_secret", secret));
+        }
+        if let Some(scope) = &self.config.scope {
+            params.push(("scope", scope));
+        }
+        let request = token_request(&self.config, &self.config.token_url, &params);
+        let (status, body) = transport(&request)
+            .map_err(|e| RefreshError::Failed(format!("oauth refresh failed: {}", e)))?;
+        let parsed: Option<Value> = serde_json::from_str(&body).ok();
+
+        if matches!(status, 400 | 401 | 403) {
+            let code = parsed.as_ref().and_then(error_code);
+            let message = match code {
+                Some(code) => format!("oauth refresh rejected (HTTP {}): {}", status, code),
+                None => format!("oauth refresh rejected (HTTP {})", status),
+            };
+            log::warn!("[plugin:{}] {}", self.plugin_id, message);
+            return Err(RefreshError::Rejected(message));
+        }
+        if !(200..300).contains(&status) {
+            return Err(RefreshError::Failed(format!(
+                "oauth refresh failed: HTTP {}",
+                status
+            )));
+        }
+        let body = parsed
+            .ok_or_else(|| RefreshError::Failed("oauth refresh response is not JSON".to_string()))?;
+        let access_token = apply_tokens(fields, &mut creds, &body).ok_or_else(|| {
+            RefreshError::Failed("oauth refresh response missing access_token".to_string())
+        })?;
+        let expires_in = body.get("expires_in").and_then(number);
+
+        match self.save(location, &creds) {
+            Ok(()) => log::info!(
+                "[plugin:{}] oauth refresh succeeded{}",
+                self.plugin_id,
+                expires_in
+                    .map(|secs| format!(" (expires in {}s)", secs))
+                    .unwrap_or_default()
+            ),
+            // The new token still works for this probe; the next one refreshes again.
+            Err(e) => log::warn!(
+                "[plugin:{}] oauth refresh succeeded but saving failed: {}",
+                self.plugin_id,
+                e
+            ),
+        }
+        Ok(access_token)
+    }
+
+    fn save(&self, location: &SourceLocation, creds: &Value) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(creds).map_err(|e| e.to_string())?;
+        match location {
+            SourceLocation::File(path) => fs_atomic::write(
+                path,
+                text.as_bytes(),
+                WriteOpts {
+                    lock: true,
+                    backup: true,
+                },
+                &self.data_dir,
+            ),
+            SourceLocation::Keychain(service) => Ok(self.store.write(service, &text)?),
+        }
+    }
+
+    fn needs_refresh(&self, creds: &Value) -> bool {
+        let fields = &self.config.fields;
+        if self.field(creds, &fields.access_token).is_none() {
+            return true;
+        }
+        // With no expiry path configured, rely on the plugin calling `refresh`
+        // after a 401. A configured but missing expiry is treated as expired.
+        if fields.expires_at.is_none() {
+            return false;
+        }
+        let Some(expires_at) = self.expires_at(creds) else {
+            return true;
+        };
+        let unit = fields.expires_at_unit;
+        unit.now() + unit.scale_secs(self.config.refresh_buffer_secs as f64) >= expires_at
+    }
+
+    fn field(&self, creds: &Value, path: &str) -> Option<String> {
+        get_path(creds, path)
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    }
+
+    fn lock_key(&self) -> String {
+        match &self.location {
+            SourceLocation::File(path) => format!("file:{}", path.display()),
+            SourceLocation::Keychain(service) => format!("keychain:{}", service),
+        }
+    }
+}
+
+fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn get_path_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(value, |value, key| value.get_mut(key))
+}
+
+fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let mut current = value;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        if !current.is_object() {
+            *current = Value::Object(serde_json::Map::new());
+        }
+        let map = current.as_object_mut().expect("object");
+        if keys.peek().is_none() {
+            map.insert(key.to_string(), new_value);
+            return;
+        }
+        current = map.entry(key.to_string()).or_insert(Value::Null);
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// `error` as a string, or `error.code` / `error.type` when it is an object.
+pub(crate) fn error_code(body: &Value) -> Option<String> {
+    let error = body.get("error")?;
+    error
+        .as_str()
+        .or_else(|| error.get("code").and_then(Value::as_str))
+        .or_else(|| error.get("type").and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+pub(crate) fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::credential_store::MemoryStore;
+    use crate::plugin_engine::test_util::TempDir;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(source: CredentialSource, fields: TokenFields) -> OAuthConfig {
+        OAuthConfig {
+            token_url: "https://auth.example.com/oauth/token".to_string(),
+            client_id: "client id".to_string(),
+            client_secret: None,
+            scope: None,
+            body_format: BodyFormat::Form,
+            refresh_buffer_secs: 300,
+            source,
+            fields,
+            login: None,
+        }
+    }
+
+    fn keychain_manager(data_dir: &Path, service: &str, creds: &str) -> OAuthManager {
+        let store = Arc::new(MemoryStore::new(HashMap::from([(
+            service.to_string(),
+            creds.to_string(),
+        )])));
+        OAuthManager::new(
+            "test",
+            config(
+                CredentialSource::Keychain {
+                    service: service.to_string(),
+                },
+                TokenFields::default(),
+            ),
+            SourceLocation::Keychain(service.to_string()),
+            data_dir.to_path_buf(),
+            store,
+        )
+    }
+
+    fn ok_transport(token: &'static str) -> impl Fn(&TokenRequest) -> Result<(u16, String), String> {
+        move |_req: &TokenRequest| {
+            Ok((
+                200,
+                format!(
+                    r#"{{"access_token":"{}","refresh_token":"rotated","expires_in":3600}}"#,
+                    token
+                ),
+            ))
+        }
+    }
+
+    #[test]
+    fn refreshes_expired_file_credentials_and_writes_them_back() {
+        let dir = TempDir::new("oauth-file");
+        let path = dir.join("creds.json");
+        std::fs::write(
+            &path,
+            r#"{"claudeAiOauth":{"accessToken":"old","refreshToken":"r1","expiresAt":1,"scopes":["a"]},"other":true}"#,
+        )
+        .unwrap();
+        let fields = TokenFields {
+            access_token: "claudeAiOauth.accessToken".to_string(),
+            refresh_token: "claudeAiOauth.refreshToken".to_string(),
+            expires_at: Some("claudeAiOauth.expiresAt".to_string()),
+            expires_at_unit: TimeUnit::Milliseconds,
+            id_token: None,
+        };
+        let manager = OAuthManager::new(
+            "test",
+            config(CredentialSource::File { path: String::new() }, fields),
+            SourceLocation::File(path.clone()),
+            dir.join("data"),
+            Arc::new(MemoryStore::new(HashMap::new())),
+        );
+
+        let seen = Mutex::new(Vec::new());
+        let transport = |req: &TokenRequest| {
+            seen.lock().unwrap().push((req.content_type, req.body.clone()));
+            ok_transport("new")(req)
+        };
+        assert_eq!(manager.access_token(&transport).unwrap().as_deref(), Some("new"));
+        assert_eq!(
+            seen.lock().unwrap()[0],
+            (
+                "application/x-www-form-urlencoded",
+                "grant_type=refresh_token&refresh_token=r1&client_id=client%20id".to_string()
+            )
+        );
+
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["claudeAiOauth"]["accessToken"], "new");
+        assert_eq!(saved["claudeAiOauth"]["refreshToken"], "rotated");
+        assert_eq!(saved["claudeAiOauth"]["scopes"][0], "a");
+        assert_eq!(saved["other"], true);
+        let expires_at = saved["claudeAiOauth"]["expiresAt"].as_f64().unwrap();
+        assert!(expires_at > TimeUnit::Milliseconds.now() + 3_000_000.0);
+
+        // Fresh now: no second request.
+        assert_eq!(manager.access_token(&transport).unwrap().as_deref(), Some("new"));
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn missing_expiry_refreshes_and_keeps_the_sources_fields() {
+        let dir = TempDir::new("oauth-no-expiry");
+        let manager = keychain_manager(
+            &dir,
+            "svc-no-expiry",
+            r#"{"access_token":"old","refresh_token":"r","scope":"kimi-code","token_type":"Bearer"}"#,
+        );
+        let transport = |_req: &TokenRequest| {
+            Ok((
+                200,
+                r#"{"access_token":"new","expires_in":60,"scope":"kimi-code usage","token_type":"bearer","session":"s"}"#
+                    .to_string(),
+            ))
+        };
+
+        assert_eq!(manager.access_token(&transport).unwrap().as_deref(), Some("new"));
+        let saved = manager.credentials().unwrap().unwrap();
+        assert_eq!(saved["scope"], "kimi-code usage");
+        assert_eq!(saved["token_type"], "bearer");
+        assert_eq!(saved["refresh_token"], "r");
+        assert!(saved["expires_at"].is_number());
+        assert!(saved.get("session").is_none());
+    }
+
+    #[test]
+    fn forced_refresh_reuses_a_token_that_was_already_replaced() {
+        let dir = TempDir::new("oauth-forced");
+        let far = TimeUnit::Seconds.now() + 86_400.0;
+        let manager = keychain_manager(
+            &dir,
+            "svc-forced",
+            &format!(r#"{{"access_token":"current","refresh_token":"r","expires_at":{}}}"#, far),
+        );
+        let calls = AtomicUsize::new(0);
+        let transport = |req: &TokenRequest| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            ok_transport("fresh")(req)
+        };
+
+        assert_eq!(manager.access_token(&transport).unwrap().as_deref(), Some("current"));
+        assert_eq!(manager.refresh(Some("stale"), &transport).unwrap(), "current");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(manager.refresh(Some("current"), &transport).unwrap(), "fresh");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.credentials().unwrap().unwrap()["refresh_token"], "rotated");
+    }
+
+    #[test]
+    fn rejected_refresh_errors_but_outages_fall_back_to_current_token() {
+        let dir = TempDir::new("oauth-errors");
+        let manager = keychain_manager(
+            &dir,
+            "svc-errors",
+            r#"{"access_token":"current","refresh_token":"r","expires_at":1}"#,
+        );
+        let rejected = |_: &TokenRequest| Ok((400, r#"{"error":"invalid_grant"}"#.to_string()));
+        assert_eq!(
+            manager.access_token(&rejected).unwrap_err(),
+            "oauth refresh rejected (HTTP 400): invalid_grant"
+        );
+
+        let outage = |_: &TokenRequest| Ok((503, String::new()));
+        assert_eq!(manager.access_token(&outage).unwrap().as_deref(), Some("current"));
+        assert_eq!(
+            manager.refresh(None, &outage).unwrap_err(),
+            "oauth refresh failed: HTTP 503"
+        );
+
+        let missing = keychain_manager(&dir, "svc-missing", "{}");
+        missing.store.delete("svc-missing").unwrap();
+        assert_eq!(missing.access_token(&outage).unwrap(), None);
+    }
+
+    #[test]
+    fn in_app_login_wins_unless_the_cli_token_expires_later() {
+        let now = TimeUnit::Seconds.now();
+        let creds = |token: &str, expires_at: f64| {
+            format!(r#"{{"access_token":"{}","refresh_token":"r","expires_at":{}}}"#, token, expires_at)
+        };
+        let dir = TempDir::new("oauth-login");
+        let manager_with = |cli: String, login: String| {
+            let mut config = config(
+                CredentialSource::Keychain {
+                    service: "svc-cli".to_string(),
+                },
+                TokenFields::default(),
+            );
+            config.login = Some(LoginConfig::DeviceCode {
+                device_authorization_url: "https://auth.example.com/device".to_string(),
+            });
+            let store = Arc::new(MemoryStore::new(HashMap::from([
+                ("svc-cli".to_string(), cli),
+                (login_service("test"), login),
+            ])));
+            OAuthManager::new(
+                "test",
+                config,
+                SourceLocation::Keychain("svc-cli".to_string()),
+                dir.to_path_buf(),
+                store,
+            )
+        };
+        let no_refresh = |_: &TokenRequest| Err("unexpected refresh".to_string());
+
+        let manager = manager_with(creds("cli", now + 3600.0), creds("app", now + 7200.0));
+        assert_eq!(manager.access_token(&no_refresh).unwrap().as_deref(), Some("app"));
+
+        // Signing in to the CLI again gives it the later expiry.
+        let manager = manager_with(creds("cli", now + 7200.0), creds("app", now + 3600.0));
+        assert_eq!(manager.access_token(&no_refresh).unwrap().as_deref(), Some("cli"));
+
+        let manager = manager_with(creds("cli", now + 3600.0), "{}".to_string());
+        assert_eq!(manager.access_token(&no_refresh).unwrap().as_deref(), Some("cli"));
+    }
+
+    #[test]
+    fn concurrent_probes_refresh_once() {
+        let dir = TempDir::new("oauth-concurrent");
+        let manager = Arc::new(keychain_manager(
+            &dir,
+            "svc-concurrent",
+            r#"{"access_token":"old","refresh_token":"r","expires_at":1}"#,
+        ));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let manager = Arc::clone(&manager);
+                let calls = Arc::clone(&calls);
+                std::thread::spawn(move || {
+                    let transport = |req: &TokenRequest| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(50));
+                        ok_transport("new")(req)
+                    };
+                    manager.access_token(&transport).unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().as_deref(), Some("new"));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,480 @@
+//! In-app sign-in for plugins that declare `oauth.login`.
+//!
+//! Two flows are supported:
+//!
+//! - `pkce`: authorization code with PKCE (RFC 7636). The browser is sent to
+//!   the provider and redirected back to a one-shot listener on 127.0.0.1.
+//! - `deviceCode`: device authorization grant (RFC 8628). The user enters a
+//!   short code on the provider's site while the app polls for tokens.
+//!
+//! Tokens are written to the credential store under `oauth::login_service`,
+//! laid out with the manifest's `fields`, where `host.oauth` picks them up.
+
+use crate::plugin_engine::credential_store::CredentialStore;
+use crate::plugin_engine::crypto;
+use crate::plugin_engine::oauth::{self, LoginConfig, OAuthConfig, Transport};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// RFC 8628 default when the server does not send `interval`.
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+const DEFAULT_DEVICE_EXPIRES_SECS: u64 = 15 * 60;
+
+/// What the user has to do next; the app opens the URL and shows any code.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LoginPrompt {
+    #[serde(rename_all = "camelCase")]
+    OpenBrowser { url: String },
+    #[serde(rename_all = "camelCase")]
+    DeviceCode {
+        user_code: String,
+        verification_uri: String,
+        verification_uri_complete: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct LoginOptions {
+    /// Gives up if the user has not finished by then.
+    pub timeout: Duration,
+    /// Floor for the device-code polling interval.
+    pub min_poll_interval: Duration,
+}
+
+impl Default for LoginOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5 * 60),
+            min_poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Runs the plugin's login flow and stores the resulting tokens.
+pub fn login(
+    plugin_id: &str,
+    config: &OAuthConfig,
+    store: &dyn CredentialStore,
+    transport: Transport,
+    prompt: &dyn Fn(LoginPrompt),
+    options: &LoginOptions,
+) -> Result<(), String> {
+    let login = config
+        .login
+        .as_ref()
+        .ok_or_else(|| "plugin does not declare oauth.login".to_string())?;
+    log::info!("[plugin:{}] oauth login started ({})", plugin_id, login.kind());
+    let body = match login {
+        LoginConfig::Pkce {
+            authorize_url,
+            redirect_port,
+            redirect_path,
+            extra_params,
+        } => {
+            let redirect = PkceRedirect {
+                authorize_url,
+                port: *redirect_port,
+                path: redirect_path,
+                extra_params,
+            };
+            pkce_flow(config, &redirect, transport, prompt, options)?
+        }
+        LoginConfig::DeviceCode {
+            device_authorization_url,
+        } => device_flow(config, device_authorization_url, transport, prompt, options)?,
+    };
+
+    let mut creds = Value::Object(serde_json::Map::new());
+    oauth::apply_tokens(&config.fields, &mut creds, &body)
+        .ok_or_else(|| "oauth login response missing access_token".to_string())?;
+    let text = serde_json::to_string_pretty(&creds).map_err(|e| e.to_string())?;
+    store.write(&oauth::login_service(plugin_id), &text)?;
+    log::info!("[plugin:{}] oauth login succeeded", plugin_id);
+    Ok(())
+}
+
+struct PkceRedirect<'a> {
+    authorize_url: &'a str,
+    port: u16,
+    path: &'a str,
+    extra_params: &'a BTreeMap<String, String>,
+}
+
+fn pkce_flow(
+    config: &OAuthConfig,
+    redirect: &PkceRedirect,
+    transport: Transport,
+    prompt: &dyn Fn(LoginPrompt),
+    options: &LoginOptions,
+) -> Result<Value, String> {
+    let listener = TcpListener::bind(("127.0.0.1", redirect.port))
+        .map_err(|e| format!("oauth login could not listen on port {}: {}", redirect.port, e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let redirect_uri = format!("http://127.0.0.1:{}{}", port, redirect.path);
+    let (verifier, challenge) = crypto::pkce_pair()?;
+    let state = crypto::hex_encode(&crypto::random_bytes(16)?);
+
+    let mut params = vec![
+        ("response_type", "code"),
+        ("client_id", config.client_id.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+        ("state", state.as_str()),
+    ];
+    if let Some(scope) = &config.scope {
+        params.push(("scope", scope));
+    }
+    params.extend(redirect.extra_params.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, oauth::form_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let separator = if redirect.authorize_url.contains('?') { '&' } else { '?' };
+    prompt(LoginPrompt::OpenBrowser {
+        url: format!("{}{}{}", redirect.authorize_url, separator, query),
+    });
+
+    let code = wait_for_code(&listener, redirect.path, &state, options.timeout)?;
+    let mut params = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", verifier.as_str()),
+    ];
+    if let Some(secret) = &config.client_secret {
+        params.push(("client_secret", secret));
+    }
+    let (status, body) = transport(&oauth::token_request(config, &config.token_url, &params))
+        .map_err(|e| format!("oauth login failed: {}", e))?;
+    token_response(status, &body)
+}
+
+/// Serves the loopback redirect until it carries our `state`.
+fn wait_for_code(
+    listener: &TcpListener,
+    path: &str,
+    state: &str,
+    timeout: Duration,
+) -> Result<String, String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(e) => return Err(format!("oauth login listener failed: {}", e)),
+        };
+        let Some(target) = read_request_target(&stream) else {
+            continue;
+        };
+        let (request_path, query) = target.split_once('?').unwrap_or((&target, ""));
+        if request_path != path {
+            respond(stream, 404, "Not found.");
+            continue;
+        }
+        let params = parse_query(query);
+        if params.get("state").map(String::as_str) != Some(state) {
+            // A stale tab or another site; keep waiting for the real redirect.
+            respond(stream, 400, "This sign-in link has expired. Start again from OpenUsage.");
+            continue;
+        }
+        if let Some(error) = params.get("error") {
+            respond(stream, 400, "Sign-in was cancelled. You can close this tab.");
+            return Err(format!("oauth login denied: {}", error));
+        }
+        let Some(code) = params.get("code") else {
+            respond(stream, 400, "Sign-in response had no code.");
+            return Err("oauth login redirect missing code".to_string());
+        };
+        respond(stream, 200, "Signed in to OpenUsage. You can close this tab.");
+        return Ok(code.clone());
+    }
+    Err("oauth login timed out".to_string())
+}
+
+fn read_request_target(stream: &TcpStream) -> Option<String> {
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    (parts.next()? == "GET").then_some(())?;
+    parts.next().map(str::to_string)
+}
+
+fn respond(mut stream: TcpStream, status: u16, message: &str) {
+    let body = format!(
+        "<!doctype html><html><body style=\"font-family:sans-serif\"><p>{}</p></body></html>",
+        message
+    );
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        if status == 200 { "OK" } else { "Error" },
+        body.len(),
+        body
+    );
+    let _ = stream.flush();
+}
+
+fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (form_decode(key), form_decode(value))
+        })
+        .collect()
+}
+
+fn form_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let hex = |byte: u8| (byte as char).to_digit(16);
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| Some(hex(*bytes.get(i + 1)?)? * 16 + hex(*bytes.get(i + 2)?)?))
+            .flatten();
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                out.push(byte as u8);
+                i += 2;
+            }
+            (b'+', None) => out.push(b' '),
+            (byte, None) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn device_flow(
+    config: &OAuthConfig,
+    device_authorization_url: &str,
+    transport: Transport,
+    prompt: &dyn Fn(LoginPrompt),
+    options: &LoginOptions,
+) -> Result<Value, String> {
+    let mut params = vec![("client_id", config.client_id.as_str())];
+    if let Some(scope) = &config.scope {
+        params.push(("scope", scope));
+    }
+    let (status, body) = transport(&oauth::token_request(config, device_authorization_url, &params))
+        .map_err(|e| format!("oauth login failed: {}", e))?;
+    let device = token_response(status, &body)?;
+    let text = |key: &str| device.get(key).and_then(Value::as_str).map(str::to_string);
+    let device_code = text("device_code")
+        .ok_or_else(|| "oauth device authorization missing device_code".to_string())?;
+    let user_code = text("user_code")
+        .ok_or_else(|| "oauth device authorization missing user_code".to_string())?;
+    // Google and a few others still use the draft name `verification_url`.
+    let verification_uri = text("verification_uri")
+        .or_else(|| text("verification_url"))
+        .ok_or_else(|| "oauth device authorization missing verification_uri".to_string())?;
+    let seconds = |key: &str, default: u64| device.get(key).and_then(Value::as_u64).unwrap_or(default);
+    let mut interval = Duration::from_secs(seconds("interval", DEFAULT_POLL_INTERVAL_SECS))
+        .max(options.min_poll_interval);
+    let expires_in = Duration::from_secs(seconds("expires_in", DEFAULT_DEVICE_EXPIRES_SECS));
+
+    prompt(LoginPrompt::DeviceCode {
+        user_code,
+        verification_uri,
+        verification_uri_complete: text("verification_uri_complete"),
+    });
+
+    let mut params = vec![
+        ("grant_type", DEVICE_GRANT),
+        ("device_code", device_code.as_str()),
+        ("client_id", config.client_id.as_str()),
+    ];
+    if let Some(secret) = &config.client_secret {
+        params.push(("client_secret", secret));
+    }
+    let request = oauth::token_request(config, &config.token_url, &params);
+    let deadline = Instant::now() + expires_in.min(options.timeout);
+    loop {
+        std::thread::sleep(interval);
+        if Instant::now() >= deadline {
+            return Err("oauth login timed out".to_string());
+        }
+        let (status, body) =
+            transport(&request).map_err(|e| format!("oauth login failed: {}", e))?;
+        if (200..300).contains(&status) {
+            return token_response(status, &body);
+        }
+        let code = serde_json::from_str::<Value>(&body)
+            .ok()
+            .as_ref()
+            .and_then(oauth::error_code);
+        match code.as_deref() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += Duration::from_secs(5),
+            Some("access_denied") => return Err("oauth login denied: access_denied".to_string()),
+            Some("expired_token") => return Err("oauth login timed out".to_string()),
+            _ => return token_response(status, &body),
+        }
+    }
+}
+
+fn token_response(status: u16, body: &str) -> Result<Value, String> {
+    let parsed: Option<Value> = serde_json::from_str(body).ok();
+    if !(200..300).contains(&status) {
+        return Err(match parsed.as_ref().and_then(oauth::error_code) {
+            Some(code) => format!("oauth login failed (HTTP {}): {}", status, code),
+            None => format!("oauth login failed (HTTP {})", status),
+        });
+    }
+    parsed.ok_or_else(|| "oauth login response is not JSON".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::credential_store::MemoryStore;
+    use crate::plugin_engine::oauth::{BodyFormat, CredentialSource, TokenFields, TokenRequest};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn config(login: LoginConfig) -> OAuthConfig {
+        OAuthConfig {
+            token_url: "https://auth.example.com/token".to_string(),
+            client_id: "cli".to_string(),
+            client_secret: None,
+            scope: Some("usage read".to_string()),
+            body_format: BodyFormat::Form,
+            refresh_buffer_secs: 300,
+            source: CredentialSource::File {
+                path: "~/.example/creds.json".to_string(),
+            },
+            fields: TokenFields::default(),
+            login: Some(login),
+        }
+    }
+
+    fn stored(store: &MemoryStore, plugin_id: &str) -> Value {
+        serde_json::from_str(&store.read(&oauth::login_service(plugin_id)).unwrap()).unwrap()
+    }
+
+    fn get(url: &str) {
+        let rest = url.strip_prefix("http://").unwrap();
+        let (host, path) = rest.split_at(rest.find('/').unwrap());
+        let mut stream = TcpStream::connect(host).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host).unwrap();
+        let mut response = String::new();
+        let _ = std::io::Read::read_to_string(&mut stream, &mut response);
+    }
+
+    #[test]
+    fn pkce_login_exchanges_the_loopback_code() {
+        let config = config(LoginConfig::Pkce {
+            authorize_url: "https://auth.example.com/authorize".to_string(),
+            redirect_port: 0,
+            redirect_path: "/callback".to_string(),
+            extra_params: BTreeMap::new(),
+        });
+        let challenge = Mutex::new(String::new());
+        let browser = |prompt: LoginPrompt| {
+            let LoginPrompt::OpenBrowser { url } = prompt else {
+                panic!("expected browser prompt");
+            };
+            let params = parse_query(url.split_once('?').unwrap().1);
+            assert_eq!(params["scope"], "usage read");
+            assert_eq!(params["code_challenge_method"], "S256");
+            *challenge.lock().unwrap() = params["code_challenge"].clone();
+            let redirect = params["redirect_uri"].clone();
+            let state = params["state"].clone();
+            std::thread::spawn(move || {
+                get(&format!("{}/../favicon.ico", redirect));
+                get(&format!("{}?code=wrong&state=stale", redirect));
+                get(&format!("{}?code=the%2Fcode&state={}", redirect, state));
+            });
+        };
+        let transport = |req: &TokenRequest| {
+            let params = parse_query(&req.body);
+            assert_eq!(params["grant_type"], "authorization_code");
+            assert_eq!(params["code"], "the/code");
+            let verifier = params["code_verifier"].as_bytes();
+            assert_eq!(
+                crypto::Encoding::Base64Url.encode(&crypto::sha256(verifier)),
+                *challenge.lock().unwrap()
+            );
+            Ok((200, r#"{"access_token":"a1","refresh_token":"r1","expires_in":60}"#.to_string()))
+        };
+
+        let store = MemoryStore::new(HashMap::new());
+        login("example", &config, &store, &transport, &browser, &LoginOptions::default()).unwrap();
+
+        let creds = stored(&store, "example");
+        assert_eq!(creds["access_token"], "a1");
+        assert_eq!(creds["refresh_token"], "r1");
+        assert!(creds["expires_at"].is_number());
+    }
+
+    #[test]
+    fn device_login_polls_until_authorized() {
+        let config = config(LoginConfig::DeviceCode {
+            device_authorization_url: "https://auth.example.com/device".to_string(),
+        });
+        let polls = Mutex::new(0);
+        let transport = |req: &TokenRequest| {
+            let params = parse_query(&req.body);
+            if req.url.ends_with("/device") {
+                assert_eq!(params["scope"], "usage read");
+                return Ok((200, r#"{"device_code":"d1","user_code":"ABCD-EFGH","verification_url":"https://example.com/activate","interval":0}"#.to_string()));
+            }
+            assert_eq!(params["grant_type"], DEVICE_GRANT);
+            assert_eq!(params["device_code"], "d1");
+            let mut polls = polls.lock().unwrap();
+            *polls += 1;
+            if *polls < 3 {
+                return Ok((400, r#"{"error":"authorization_pending"}"#.to_string()));
+            }
+            Ok((200, r#"{"access_token":"a2","refresh_token":"r2"}"#.to_string()))
+        };
+        let prompts = Mutex::new(Vec::new());
+        let options = LoginOptions {
+            min_poll_interval: Duration::ZERO,
+            ..LoginOptions::default()
+        };
+
+        let store = MemoryStore::new(HashMap::new());
+        login("example", &config, &store, &transport, &|p| prompts.lock().unwrap().push(p), &options)
+            .unwrap();
+
+        assert_eq!(*polls.lock().unwrap(), 3);
+        assert_eq!(
+            prompts.lock().unwrap()[0],
+            LoginPrompt::DeviceCode {
+                user_code: "ABCD-EFGH".to_string(),
+                verification_uri: "https://example.com/activate".to_string(),
+                verification_uri_complete: None,
+            }
+        );
+        assert_eq!(stored(&store, "example")["access_token"], "a2");
+
+        let denied = |req: &TokenRequest| {
+            if req.url.ends_with("/device") {
+                return Ok((200, r#"{"device_code":"d","user_code":"u","verification_uri":"v","interval":0}"#.to_string()));
+            }
+            Ok((400, r#"{"error":"access_denied"}"#.to_string()))
+        };
+        let err = login("denied", &config, &store, &denied, &|_| {}, &options).unwrap_err();
+        assert_eq!(err, "oauth login denied: access_denied");
+        assert!(store.read(&oauth::login_service("denied")).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! User configuration for plugins that declare `settings` in their manifest.
+//!
+//! Plain values live in `<appDataDir>/plugin_settings/<pluginId>.json`. Secrets
+//! go to the credential store as `openusage:<pluginId>:setting:<key>` and are
+//! never written to disk in the clear. `config` resolves both, plus defaults,
+//! into the object a probe sees as `ctx.config`.
+
+use crate::plugin_engine::credential_store::{CredentialError, CredentialStore};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const SETTINGS_DIR: &str = "plugin_settings";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingField {
+    pub key: String,
+    pub label: String,
+    #[serde(rename = "type")]
+    pub kind: SettingKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default)]
+    pub required: bool,
+    /// Allowed values of an `enum` field.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Regex a `string` or `secret` value must match in full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingKind {
+    String,
+    Secret,
+    Number,
+    Enum,
+    Path,
+}
+
+/// Stored values for the settings form. Secrets are reported as set or not,
+/// never by value.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsView {
+    pub values: Map<String, Value>,
+    pub secrets_set: Vec<String>,
+}
+
+/// Checks a manifest's `settings` before the plugin is loaded.
+pub fn validate_schema(fields: &[SettingField]) -> Result<(), String> {
+    let mut keys = HashSet::new();
+    for field in fields {
+        let valid_key = !field.key.is_empty()
+            && field
+                .key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(format!("setting key '{}' must be alphanumeric", field.key));
+        }
+        if !keys.insert(field.key.as_str()) {
+            return Err(format!("setting key '{}' is declared twice", field.key));
+        }
+        if field.kind == SettingKind::Enum && field.options.is_empty() {
+            return Err(format!("enum setting '{}' needs options", field.key));
+        }
+        if let Some(pattern) = &field.pattern {
+            regex_lite::Regex::new(pattern)
+                .map_err(|e| format!("setting '{}' has an invalid pattern: {}", field.key, e))?;
+        }
+        match (&field.default, field.kind) {
+            (Some(_), SettingKind::Secret) => {
+                return Err(format!("secret setting '{}' cannot have a default", field.key));
+            }
+            (Some(default), _) => {
+                check(field, default)
+                    .map_err(|e| format!("setting '{}' has an invalid default: {}", field.key, e))?;
+            }
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Validates one value against its field, returning it normalized.
+fn check(field: &SettingField, value: &Value) -> Result<Value, String> {
+    match field.kind {
+        SettingKind::Number => {
+            let n = match value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            }
+            .filter(|n: &f64| n.is_finite())
+            .ok_or_else(|| format!("{} must be a number", field.label))?;
+            if field.min.is_some_and(|min| n < min) || field.max.is_some_and(|max| n > max) {
+                return Err(format!(
+                    "{} must be between {} and {}",
+                    field.label,
+                    field.min.map(|v| v.to_string()).unwrap_or_else(|| "-∞".to_string()),
+                    field.max.map(|v| v.to_string()).unwrap_or_else(|| "∞".to_string())
+                ));
+            }
+            Ok(serde_json::json!(n))
+        }
+        SettingKind::Enum => match value.as_str() {
+            Some(s) if field.options.iter().any(|option| option == s) => Ok(value.clone()),
+            _ => Err(format!("{} must be one of: {}", field.label, field.options.join(", "))),
+        },
+        SettingKind::String | SettingKind::Secret | SettingKind::Path => {
+            let s = value
+                .as_str()
+                .ok_or_else(|| format!("{} must be text", field.label))?;
+            if let Some(pattern) = &field.pattern {
+                let re = regex_lite::Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|e| e.to_string())?;
+                if !re.is_match(s) {
+                    return Err(format!("{} has an invalid format", field.label));
+                }
+            }
+            Ok(value.clone())
+        }
+    }
+}
+
+/// Replaces `{config.<key>}` entries of a manifest list (such as
+/// `permissions.sqliteWrite`) with that setting's value, dropping unset ones.
+/// Other entries are kept as they are.
+pub fn resolve_config_refs(entries: &[String], config: &Map<String, Value>) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            match entry
+                .strip_prefix("{config.")
+                .and_then(|rest| rest.strip_suffix('}'))
+            {
+                Some(key) => config
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .filter(|value| !value.trim().is_empty())
+                    .map(str::to_string),
+                None => Some(entry.clone()),
+            }
+        })
+        .collect()
+}
+
+fn is_unset(value: &Value) -> bool {
+    value.is_null() || value.as_str().is_some_and(|s| s.trim().is_empty())
+}
+
+pub struct PluginSettings {
+    path: PathBuf,
+    data_dir: PathBuf,
+    plugin_id: String,
+    store: Arc<dyn CredentialStore>,
+}
+
+impl PluginSettings {
+    pub fn new(app_data_dir: &Path, plugin_id: &str, store: Arc<dyn CredentialStore>) -> Self {
+        let data_dir = app_data_dir.join(SETTINGS_DIR);
+        Self {
+            path: data_dir.join(format!("{}.json", plugin_id)),
+            data_dir,
+            plugin_id: plugin_id.to_string(),
+            store,
+        }
+    }
+
+    pub fn view(&self, fields: &[SettingField]) -> Result<SettingsView, String> {
+        let stored = self.stored()?;
+        let mut view = SettingsView::default();
+        for field in fields {
+            if field.kind == SettingKind::Secret {
+                if self.secret(&field.key)?.is_some() {
+                    view.secrets_set.push(field.key.clone());
+                }
+            } else if let Some(value) = stored.get(&field.key) {
+                view.values.insert(field.key.clone(), value.clone());
+            }
+        }
+        Ok(view)
+    }
+
+    /// Applies `changes`; `null` or an empty string clears a value. Keys not
+    /// in `changes` keep their current value.
+    pub fn update(&self, fields: &[SettingField], changes: &Map<String, Value>) -> Result<(), String> {
+        let mut stored = self.stored()?;
+        let mut secrets = Vec::new();
+        for (key, value) in changes {
+            let field = fields
+                .iter()
+                .find(|field| &field.key == key)
+                .ok_or_else(|| format!("unknown setting: {}", key))?;
+            let value = if is_unset(value) {
+                if field.required && field.default.is_none() {
+                    return Err(format!("{} is required", field.label));
+                }
+                None
+            } else {
+                Some(check(field, value)?)
+            };
+            if field.kind == SettingKind::Secret {
+                secrets.push((key, value));
+            } else {
+                match value {
+                    Some(value) => stored.insert(key.clone(), value),
+                    None => stored.remove(key),
+                };
+            }
+        }
+
+        // Everything is validated before anything is written.
+        for (key, value) in secrets {
+            let service = self.secret_service(key);
+            match value.as_ref().and_then(Value::as_str) {
+                Some(secret) => self.store.write(&service, secret)?,
+                None => match self.store.delete(&service) {
+                    Ok(()) | Err(CredentialError::NotFound(_)) => {}
+                    Err(e) => return Err(e.into()),
+                },
+            }
+        }
+        let text = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&self.data_dir).map_err(|e| e.to_string())?;
+        fs_atomic::write(&self.path, text.as_bytes(), WriteOpts::default(), &self.data_dir)?;
+        log::info!("[plugin:{}] settings updated", self.plugin_id);
+        Ok(())
+    }
+
+    /// Every declared key, resolved to its stored value, its default or `null`.
+    /// Fails with a user-facing message if a required setting is missing.
+    pub fn config(&self, fields: &[SettingField]) -> Result<Map<String, Value>, String> {
+        let stored = if fields.is_empty() {
+            Map::new()
+        } else {
+            self.stored()?
+        };
+        let mut config = Map::new();
+        for field in fields {
+            let value = if field.kind == SettingKind::Secret {
+                self.secret(&field.key)?.map(Value::String)
+            } else {
+                // The schema may have changed since the value was saved.
+                stored.get(&field.key).and_then(|value| match check(field, value) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        log::warn!("[plugin:{}] ignoring stored setting: {}", self.plugin_id, e);
+                        None
+                    }
+                })
+            }
+            .or_else(|| field.default.clone());
+            if value.is_none() && field.required {
+                return Err(format!("{} is not set. Add it in the plugin's settings.", field.label));
+            }
+            config.insert(field.key.clone(), value.unwrap_or(Value::Null));
+        }
+        Ok(config)
+    }
+
+    /// Deletes every stored value and secret.
+    pub fn clear(&self, fields: &[SettingField]) -> Result<(), String> {
+        for field in fields.iter().filter(|field| field.kind == SettingKind::Secret) {
+            match self.store.delete(&self.secret_service(&field.key)) {
+                Ok(()) | Err(CredentialError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    fn stored(&self) -> Result<Map<String, Value>, String> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| format!("invalid {}: {}", self.path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn secret(&self, key: &str) -> Result<Option<String>, String> {
+        match self.store.read(&self.secret_service(key)) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(CredentialError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn secret_service(&self, key: &str) -> String {
+        format!("openusage:{}:setting:{}", self.plugin_id, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::credential_store::MemoryStore;
+    use crate::plugin_engine::test_util::TempDir;
+    use std::collections::HashMap;
+
+    fn fields() -> Vec<SettingField> {
+        serde_json::from_str(
+            r#"[
+                {"key": "apiKey", "label": "API key", "type": "secret", "required": true, "pattern": "sk-[a-z0-9]+"},
+                {"key": "org", "label": "Organization", "type": "string"},
+                {"key": "limit", "label": "Monthly limit", "type": "number", "default": 100, "min": 1},
+                {"key": "region", "label": "Region", "type": "enum", "options": ["us", "eu"], "default": "us"},
+                {"key": "logs", "label": "Log folder", "type": "path", "default": "~/.tool/logs"}
+            ]"#,
+        )
+        .expect("fields")
+    }
+
+    fn changes(json: &str) -> Map<String, Value> {
+        serde_json::from_str(json).expect("changes")
+    }
+
+    #[test]
+    fn schema_validation_catches_bad_declarations() {
+        assert!(validate_schema(&fields()).is_ok());
+        let bad = |json: &str| {
+            let fields: Vec<SettingField> = serde_json::from_str(json).expect("json");
+            validate_schema(&fields).unwrap_err()
+        };
+        assert!(bad(r#"[{"key": "a b", "label": "A", "type": "string"}]"#).contains("alphanumeric"));
+        assert!(bad(r#"[{"key": "a", "label": "A", "type": "string"}, {"key": "a", "label": "B", "type": "path"}]"#)
+            .contains("twice"));
+        assert!(bad(r#"[{"key": "e", "label": "E", "type": "enum"}]"#).contains("needs options"));
+        assert!(bad(r#"[{"key": "s", "label": "S", "type": "secret", "default": "x"}]"#).contains("default"));
+        assert!(bad(r#"[{"key": "n", "label": "N", "type": "number", "default": "many"}]"#)
+            .contains("invalid default"));
+    }
+
+    #[test]
+    fn config_resolves_defaults_and_requires_secrets() {
+        let dir = TempDir::new("settings-config");
+        let store = Arc::new(MemoryStore::new(HashMap::new()));
+        let settings = PluginSettings::new(&dir, "openai", store.clone());
+        let fields = fields();
+
+        assert_eq!(
+            settings.config(&fields).unwrap_err(),
+            "API key is not set. Add it in the plugin's settings."
+        );
+
+        settings
+            .update(&fields, &changes(r#"{"apiKey": "sk-abc123", "org": "acme", "limit": "250"}"#))
+            .unwrap();
+        let config = settings.config(&fields).unwrap();
+        assert_eq!(config["apiKey"], "sk-abc123");
+        assert_eq!(config["org"], "acme");
+        assert_eq!(config["limit"], 250.0);
+        assert_eq!(config["region"], "us");
+        assert_eq!(config["logs"], "~/.tool/logs");
+
+        // The secret is in the credential store, not the settings file.
+        let on_disk = std::fs::read_to_string(dir.join(SETTINGS_DIR).join("openai.json")).unwrap();
+        assert!(!on_disk.contains("sk-abc123"));
+        assert_eq!(store.read("openusage:openai:setting:apiKey").unwrap(), "sk-abc123");
+
+        let view = settings.view(&fields).unwrap();
+        assert_eq!(view.secrets_set, vec!["apiKey"]);
+        assert_eq!(view.values.get("org"), Some(&Value::from("acme")));
+        assert!(!view.values.contains_key("apiKey"));
+
+        assert_eq!(
+            resolve_config_refs(
+                &["{config.logs}".to_string(), "{config.org}".to_string(), "~/fixed.db".to_string()],
+                &settings.config(&fields).unwrap()
+            ),
+            vec!["~/.tool/logs", "acme", "~/fixed.db"]
+        );
+
+        settings.update(&fields, &changes(r#"{"org": ""}"#)).unwrap();
+        let config = settings.config(&fields).unwrap();
+        assert_eq!(config["org"], Value::Null);
+        assert_eq!(resolve_config_refs(&["{config.org}".to_string()], &config), Vec::<String>::new());
+    }
+
+    #[test]
+    fn invalid_updates_change_nothing() {
+        let dir = TempDir::new("settings-invalid");
+        let store = Arc::new(MemoryStore::new(HashMap::new()));
+        let settings = PluginSettings::new(&dir, "openai", store.clone());
+        let fields = fields();
+
+        for (json, error) in [
+            (r#"{"apiKey": "sk-new", "limit": 0}"#, "Monthly limit must be between 1 and ∞"),
+            (r#"{"apiKey": "pk-123"}"#, "API key has an invalid format"),
+            (r#"{"region": "apac"}"#, "Region must be one of: us, eu"),
+            (r#"{"apiKey": null}"#, "API key is required"),
+            (r#"{"nope": 1}"#, "unknown setting: nope"),
+        ] {
+            assert_eq!(settings.update(&fields, &changes(json)).unwrap_err(), error);
+        }
+        assert!(store.read("openusage:openai:setting:apiKey").is_err());
+        assert!(!dir.join(SETTINGS_DIR).join("openai.json").exists());
+    }
+}
@@ -0,0 +1,199 @@
+//! Token-to-cost pricing for `ctx.pricing`.
+//!
+//! Prices are USD per million tokens, from the bundled `pricing_table.json`.
+//! Users can add or replace models with `<appDataDir>/pricing.json`, which uses
+//! the same format; each model listed there replaces the bundled entry whole.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+const BUNDLED_TABLE: &str = include_str!("pricing_table.json");
+pub const OVERRIDE_FILE: &str = "pricing.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Falls back to `input` when a model has no separate cache price.
+    #[serde(default)]
+    pub cache_read: Option<f64>,
+    #[serde(default)]
+    pub cache_write: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceTable {
+    pub version: String,
+    pub models: HashMap<String, ModelPrice>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub model: String,
+    /// Uncached input tokens.
+    #[serde(default)]
+    pub input_tokens: f64,
+    #[serde(default)]
+    pub output_tokens: f64,
+    #[serde(default)]
+    pub cache_read_tokens: f64,
+    #[serde(default)]
+    pub cache_write_tokens: f64,
+}
+
+impl PriceTable {
+    pub fn bundled() -> Self {
+        serde_json::from_str(BUNDLED_TABLE).expect("bundled pricing table is valid")
+    }
+
+    /// Bundled prices with the user's overrides applied. A broken override
+    /// file is reported and ignored rather than disabling pricing.
+    pub fn load(app_data_dir: &Path) -> (Self, Option<String>) {
+        let mut table = Self::bundled();
+        let path = app_data_dir.join(OVERRIDE_FILE);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return (table, None);
+        };
+        match serde_json::from_str::<PriceTable>(&text) {
+            Ok(overrides) => {
+                table.version = format!("{}+{}", table.version, overrides.version);
+                table.models.extend(
+                    overrides
+                        .models
+                        .into_iter()
+                        .map(|(model, price)| (model.to_lowercase(), price)),
+                );
+                (table, None)
+            }
+            Err(e) => (table, Some(format!("invalid {}: {}", path.display(), e))),
+        }
+    }
+
+    /// Exact id first, then the id with version suffixes stripped: a date
+    /// stamp (`-20250929`, `-2024-07-18`), `@version` or `:tag`. Any other
+    /// suffix is a different model (`o3-mini` is not `o3`), so it stays
+    /// unknown. Provider prefixes (`anthropic/`, `models/`) are ignored.
+    pub fn lookup(&self, model: &str) -> Option<(&str, &ModelPrice)> {
+        let model = model.trim().to_lowercase();
+        let mut model = model.rsplit('/').next().unwrap_or_default();
+        loop {
+            if let Some((id, price)) = self.models.get_key_value(model) {
+                return Some((id.as_str(), price));
+            }
+            model = strip_version_suffix(model)?;
+        }
+    }
+
+    /// USD cost, or `None` for an unknown model.
+    pub fn cost(&self, usage: &Usage) -> Option<f64> {
+        let (_, price) = self.lookup(&usage.model)?;
+        let total = usage.input_tokens * price.input
+            + usage.output_tokens * price.output
+            + usage.cache_read_tokens * price.cache_read.unwrap_or(price.input)
+            + usage.cache_write_tokens * price.cache_write.unwrap_or(price.input);
+        Some(total / 1_000_000.0)
+    }
+}
+
+/// `model` without its last version suffix, or `None` if it has none.
+fn strip_version_suffix(model: &str) -> Option<&str> {
+    if let Some((base, _)) = model.rsplit_once(['@', ':']) {
+        return Some(base);
+    }
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let (base, stamp) = model.rsplit_once('-')?;
+    if stamp.len() == 8 && digits(stamp) {
+        return Some(base);
+    }
+    // `-YYYY-MM-DD` splits into three dash-separated parts.
+    let (base, month) = base.rsplit_once('-')?;
+    let (base, year) = base.rsplit_once('-')?;
+    (year.len() == 4 && month.len() == 2 && stamp.len() == 2 && digits(year) && digits(month) && digits(stamp))
+        .then_some(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    fn usage(model: &str, input: f64, output: f64, cache_read: f64, cache_write: f64) -> Usage {
+        Usage {
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: output,
+            cache_read_tokens: cache_read,
+            cache_write_tokens: cache_write,
+        }
+    }
+
+    #[test]
+    fn lookup_resolves_dated_and_prefixed_ids() {
+        let table = PriceTable::bundled();
+        let id = |model: &str| table.lookup(model).map(|(id, _)| id.to_string());
+        assert_eq!(id("claude-sonnet-4-5-20250929").as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(id("claude-sonnet-4-20250514").as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(id("claude-opus-4-1@20250805").as_deref(), Some("claude-opus-4-1"));
+        assert_eq!(id("models/gemini-2.5-flash-lite").as_deref(), Some("gemini-2.5-flash-lite"));
+        assert_eq!(id("GPT-4o-mini-2024-07-18").as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(id("gpt-5-codex").as_deref(), Some("gpt-5-codex"));
+        assert_eq!(id("gpt-50"), None);
+        assert_eq!(id("unknown-model"), None);
+    }
+
+    #[test]
+    fn lookup_does_not_price_variants_as_their_base_model() {
+        let table = PriceTable::bundled();
+        let id = |model: &str| table.lookup(model).map(|(id, _)| id.to_string());
+        assert_eq!(id("o3-mini"), None);
+        assert_eq!(id("gpt-5-pro"), None);
+        assert_eq!(id("gpt-5-pro-2025-10-06"), None);
+        assert_eq!(id("claude-opus-4-6"), None);
+        assert_eq!(id("claude-opus-4-6-20260101"), None);
+        assert_eq!(id("o3:latest").as_deref(), Some("o3"));
+        assert_eq!(id("o3-2025-04-16").as_deref(), Some("o3"));
+        assert_eq!(id("claude-opus-4-2025"), None);
+    }
+
+    #[test]
+    fn cost_sums_all_token_kinds() {
+        let table = PriceTable::bundled();
+        let cost = table
+            .cost(&usage("claude-sonnet-4-5", 1_000_000.0, 100_000.0, 2_000_000.0, 400_000.0))
+            .unwrap();
+        // 3 + 1.5 + 0.6 + 1.5
+        assert!((cost - 6.6).abs() < 1e-9);
+
+        // Missing cache-write price falls back to the input price.
+        let cost = table.cost(&usage("gemini-2.5-pro", 0.0, 0.0, 0.0, 1_000_000.0)).unwrap();
+        assert!((cost - 1.25).abs() < 1e-9);
+        assert_eq!(table.cost(&usage("mystery", 1.0, 1.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn overrides_replace_and_add_models() {
+        let dir = TempDir::new("pricing");
+        let (table, warning) = PriceTable::load(&dir);
+        assert!(warning.is_none());
+        assert_eq!(table.version, PriceTable::bundled().version);
+
+        std::fs::write(
+            dir.join(OVERRIDE_FILE),
+            r#"{"version":"local","models":{"GPT-5":{"input":1,"output":2},"my-model":{"input":4,"output":8}}}"#,
+        )
+        .unwrap();
+        let (table, warning) = PriceTable::load(&dir);
+        assert!(warning.is_none());
+        assert!(table.version.ends_with("+local"));
+        assert_eq!(table.lookup("gpt-5").unwrap().1.cache_read, None);
+        assert_eq!(table.cost(&usage("my-model-20260101", 1_000_000.0, 0.0, 0.0, 0.0)), Some(4.0));
+
+        std::fs::write(dir.join(OVERRIDE_FILE), "{").unwrap();
+        let (table, warning) = PriceTable::load(&dir);
+        assert!(warning.is_some());
+        assert_eq!(table.lookup("gpt-5").unwrap().1.input, 1.25);
+    }
+}
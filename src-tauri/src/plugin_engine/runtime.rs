@@ -1,10 +1,16 @@
+use crate::plugin_engine::declarative;
 use crate::plugin_engine::host_api;
 use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::modules;
+use crate::plugin_engine::native;
+use crate::plugin_engine::plugin_settings::{self, PluginSettings};
+use crate::plugin_engine::vcr::HttpVcr;
+use crate::plugin_engine::wasm;
 use rquickjs::{Array, Context, Ctx, Error, Object, Promise, Runtime, Value};
-use serde::Serialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum ProgressFormat {
     Percent,
@@ -54,6 +60,16 @@ pub fn run_probe(
     plugin: &LoadedPlugin,
     app_data_dir: &PathBuf,
     app_version: &str,
+) -> PluginOutput {
+    run_probe_with_options(plugin, app_data_dir, app_version, &host_api::HostOptions::from_env())
+}
+
+/// Like `run_probe`, with explicit host overrides (VCR, fake endpoints, home dir).
+pub fn run_probe_with_options(
+    plugin: &LoadedPlugin,
+    app_data_dir: &PathBuf,
+    app_version: &str,
+    options: &host_api::HostOptions,
 ) -> PluginOutput {
     let fallback = error_output(plugin, "runtime error".to_string());
 
@@ -68,24 +84,88 @@ pub fn run_probe(
     };
 
     let plugin_id = plugin.manifest.id.clone();
-    let display_name = plugin.manifest.name.clone();
     let entry_script = plugin.entry_script.clone();
-    let icon_url = plugin.icon_data_url.clone();
     let app_data = app_data_dir.clone();
 
+    let settings = PluginSettings::new(
+        app_data_dir,
+        &plugin_id,
+        options.credential_store(app_data_dir),
+    );
+    let config = match settings.config(&plugin.manifest.settings) {
+        Ok(config) => config,
+        Err(msg) => return error_output(plugin, msg),
+    };
+    if let Some(native) = &plugin.native {
+        return run_native(plugin, native, &config, app_data_dir, options);
+    }
+    if let Some(spec) = &plugin.manifest.declarative {
+        return run_declarative(plugin, spec, &config, app_data_dir, options);
+    }
+
+    let mut permissions = plugin.manifest.permissions.clone();
+    permissions.sqlite_write =
+        plugin_settings::resolve_config_refs(&permissions.sqlite_write, &config);
+
+    if let Some(module) = &plugin.entry_wasm {
+        let json = match wasm::probe(
+            module,
+            &plugin_id,
+            &permissions,
+            &config,
+            app_data_dir,
+            app_version,
+            options,
+        ) {
+            Ok(json) => json,
+            Err(msg) => return error_output(plugin, msg),
+        };
+        // Parsed into a JS object so the result is checked exactly like a script's.
+        return ctx.with(|ctx| {
+            match ctx.json_parse(json).ok().and_then(|value| value.into_object()) {
+                Some(result) => match result.get::<_, String>("error") {
+                    Ok(msg) => error_output(plugin, msg),
+                    Err(_) => output_from_result(plugin, &result),
+                },
+                None => error_output(plugin, "probe() returned non-object".to_string()),
+            }
+        });
+    }
+
     ctx.with(|ctx| {
-        if host_api::inject_host_api(&ctx, &plugin_id, &app_data, app_version).is_err() {
+        if host_api::inject_host_api(
+            &ctx,
+            &plugin_id,
+            &app_data,
+            app_version,
+            &permissions,
+            plugin.manifest.oauth.as_ref(),
+            options,
+        )
+        .is_err() {
             return error_output(plugin, "host api injection failed".to_string());
         }
+        if host_api::inject_config(&ctx, &config).is_err() {
+            return error_output(plugin, "config injection failed".to_string());
+        }
         if host_api::patch_http_wrapper(&ctx).is_err() {
             return error_output(plugin, "http wrapper patch failed".to_string());
         }
         if host_api::patch_ls_wrapper(&ctx).is_err() {
             return error_output(plugin, "ls wrapper patch failed".to_string());
         }
+        if host_api::patch_exec_wrapper(&ctx).is_err() {
+            return error_output(plugin, "exec wrapper patch failed".to_string());
+        }
+        if host_api::patch_fs_wrapper(&ctx).is_err() {
+            return error_output(plugin, "fs wrapper patch failed".to_string());
+        }
         if host_api::inject_utils(&ctx).is_err() {
             return error_output(plugin, "utils injection failed".to_string());
         }
+        if modules::inject_require(&ctx, plugin).is_err() {
+            return error_output(plugin, "require injection failed".to_string());
+        }
 
         if ctx.eval::<(), _>(entry_script.as_bytes()).is_err() {
             return error_output(plugin, "script eval failed".to_string());
@@ -129,22 +209,27 @@ pub fn run_probe(
             }
         };
 
-        let plan: Option<String> = result.get::<_, String>("plan").ok().filter(|s| !s.is_empty());
+        output_from_result(plugin, &result)
+    })
+}
 
-        let lines = match parse_lines(&result) {
-            Ok(lines) if !lines.is_empty() => lines,
-            Ok(_) => vec![error_line("no lines returned".to_string())],
-            Err(msg) => vec![error_line(msg)],
-        };
+/// Builds the output from the object `probe()` resolved to.
+fn output_from_result(plugin: &LoadedPlugin, result: &Object) -> PluginOutput {
+    let plan: Option<String> = result.get::<_, String>("plan").ok().filter(|s| !s.is_empty());
 
-        PluginOutput {
-            provider_id: plugin_id,
-            display_name,
-            plan,
-            lines,
-            icon_url,
-        }
-    })
+    let lines = match parse_lines(result) {
+        Ok(lines) if !lines.is_empty() => lines,
+        Ok(_) => vec![error_line("no lines returned".to_string())],
+        Err(msg) => vec![error_line(msg)],
+    };
+
+    PluginOutput {
+        provider_id: plugin.manifest.id.clone(),
+        display_name: plugin.manifest.name.clone(),
+        plan,
+        lines,
+        icon_url: plugin.icon_data_url.clone(),
+    }
 }
 
 fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
@@ -411,6 +496,69 @@ fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
     Ok(out)
 }
 
+fn run_declarative(
+    plugin: &LoadedPlugin,
+    spec: &declarative::DeclarativeSpec,
+    config: &serde_json::Map<String, serde_json::Value>,
+    app_data_dir: &Path,
+    options: &host_api::HostOptions,
+) -> PluginOutput {
+    let declared = &plugin.manifest.permissions.env;
+    let env = |name: &str| host_api::read_env(name, declared, options.env.as_ref());
+    let inputs = declarative::Inputs { config, env: &env };
+    let plugin_id = &plugin.manifest.id;
+    let http = host_api::HttpHost::new(
+        plugin_id,
+        &app_data_dir.join("plugins_data").join(plugin_id),
+        options.vcr.as_ref().map(|mode| HttpVcr::new(mode, plugin_id)),
+        options.url_overrides.clone(),
+    );
+    let fetch = declarative::http_fetch(http);
+    match declarative::evaluate(&plugin.manifest.id, spec, &inputs, &fetch) {
+        Ok((plan, lines)) => PluginOutput {
+            provider_id: plugin.manifest.id.clone(),
+            display_name: plugin.manifest.name.clone(),
+            plan,
+            lines,
+            icon_url: plugin.icon_data_url.clone(),
+        },
+        Err(message) => error_output(plugin, message),
+    }
+}
+
+fn run_native(
+    plugin: &LoadedPlugin,
+    native: &native::NativeProvider,
+    config: &serde_json::Map<String, serde_json::Value>,
+    app_data_dir: &Path,
+    options: &host_api::HostOptions,
+) -> PluginOutput {
+    let plugin_data_dir = app_data_dir.join("plugins_data").join(&plugin.manifest.id);
+    if let Err(err) = std::fs::create_dir_all(&plugin_data_dir) {
+        return error_output(plugin, err.to_string());
+    }
+    let probe_ctx = native::ProbeContext {
+        plugin_id: &plugin.manifest.id,
+        config,
+        app_data_dir,
+        plugin_data_dir,
+        home_dir: options.home_dir.as_deref(),
+    };
+    match native.0.probe(&probe_ctx) {
+        Ok(probe) => PluginOutput {
+            provider_id: plugin.manifest.id.clone(),
+            display_name: plugin.manifest.name.clone(),
+            plan: probe.plan,
+            lines: probe.lines,
+            icon_url: plugin.icon_data_url.clone(),
+        },
+        Err(message) => {
+            log::warn!("[plugin:{}] probe failed: {}", plugin.manifest.id, message);
+            error_output(plugin, message)
+        }
+    }
+}
+
 fn error_output(plugin: &LoadedPlugin, message: String) -> PluginOutput {
     PluginOutput {
         provider_id: plugin.manifest.id.clone(),
@@ -449,9 +597,10 @@ fn error_line(message: String) -> MetricLine {
 mod tests {
     use super::*;
     use crate::plugin_engine::manifest::{LoadedPlugin, PluginManifest};
+    use crate::plugin_engine::test_util::TempDir;
+    use crate::plugin_engine::vcr::VcrMode;
     use serde_json::Value as JsonValue;
     use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
 
     fn test_plugin(entry_script: &str) -> LoadedPlugin {
         LoadedPlugin {
@@ -464,19 +613,23 @@ mod tests {
                 icon: "icon.svg".to_string(),
                 brand_color: None,
                 lines: vec![],
+                permissions: Default::default(),
+                oauth: None,
+                settings: vec![],
+                declarative: None,
+                stdlib: Default::default(),
             },
             plugin_dir: PathBuf::from("."),
             entry_script: entry_script.to_string(),
             icon_data_url: "data:image/svg+xml;base64,".to_string(),
+            instance_of: None,
+            native: None,
+            entry_wasm: None,
         }
     }
 
-    fn temp_app_dir(label: &str) -> PathBuf {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos();
-        std::env::temp_dir().join(format!("openusage-test-{}-{}", label, nanos))
+    fn temp_app_dir(label: &str) -> TempDir {
+        TempDir::new(&format!("test-{}", label))
     }
 
     fn error_text(output: PluginOutput) -> String {
@@ -516,6 +669,205 @@ mod tests {
         assert_eq!(error_text(output), "boom");
     }
 
+    #[test]
+    fn run_probe_with_options_replays_recorded_http() {
+        let fixtures = temp_app_dir("vcr-fixtures");
+        std::fs::write(
+            fixtures.join("test.json"),
+            r#"{"interactions": [
+                {"request": {"method": "GET", "url": "https://api.example.com/usage"},
+                 "response": {"status": 401, "bodyText": ""}},
+                {"request": {"method": "GET", "url": "https://api.example.com/usage"},
+                 "response": {"status": 200, "bodyText": "{\"used\": 42}"}}
+            ]}"#,
+        )
+        .expect("write cassette");
+
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    var resp = ctx.util.retryOnceOnAuth({
+                        request: function () {
+                            return ctx.host.http.request({ url: "https://api.example.com/usage" });
+                        },
+                        refresh: function () { return "fresh"; }
+                    });
+                    var used = JSON.parse(resp.bodyText).used;
+                    return { lines: [ctx.line.progress({ label: "Usage", used: used, limit: 100, format: { kind: "percent" } })] };
+                }
+            };
+            "#,
+        );
+        let options = host_api::HostOptions {
+            vcr: Some(VcrMode::Replay(fixtures.to_path_buf())),
+            ..Default::default()
+        };
+        let output = run_probe_with_options(&plugin, &temp_app_dir("vcr"), "0.0.0", &options);
+        match output.lines.first() {
+            Some(MetricLine::Progress { used, .. }) => assert_eq!(*used, 42.0),
+            other => panic!("expected progress line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_passes_settings_as_config() {
+        let mut plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return { lines: [ctx.line.text({ label: ctx.config.region, value: ctx.config.token })] };
+                }
+            };
+            "#,
+        );
+        plugin.manifest.settings = serde_json::from_str(
+            r#"[
+                {"key": "token", "label": "Access token", "type": "secret", "required": true},
+                {"key": "region", "label": "Region", "type": "enum", "options": ["us", "eu"], "default": "eu"}
+            ]"#,
+        )
+        .expect("settings");
+
+        let no_secrets = host_api::HostOptions {
+            keychain: Some(std::collections::HashMap::new()),
+            ..Default::default()
+        };
+        let output = run_probe_with_options(&plugin, &temp_app_dir("config-missing"), "0.0.0", &no_secrets);
+        assert_eq!(
+            error_text(output),
+            "Access token is not set. Add it in the plugin's settings."
+        );
+
+        let options = host_api::HostOptions {
+            keychain: Some(std::collections::HashMap::from([(
+                "openusage:test:setting:token".to_string(),
+                "tok-1".to_string(),
+            )])),
+            ..Default::default()
+        };
+        let output = run_probe_with_options(&plugin, &temp_app_dir("config"), "0.0.0", &options);
+        match output.lines.first() {
+            Some(MetricLine::Text { label, value, .. }) => {
+                assert_eq!(label, "eu");
+                assert_eq!(value, "tok-1");
+            }
+            other => panic!("expected text line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_hides_other_plugins_secret_settings() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    var kc = ctx.host.keychain;
+                    var read = "";
+                    try { read = kc.readGenericPassword("openusage:openai:setting:apiKey"); } catch (e) { read = String(e); }
+                    var write = "";
+                    try { kc.writeGenericPassword("openusage:openai:setting:apiKey", "x"); } catch (e) { write = String(e); }
+                    var listed = kc.listGenericPasswords("").join(",");
+                    return { lines: [ctx.line.text({ label: read + "|" + write, value: listed })] };
+                }
+            };
+            "#,
+        );
+        let options = host_api::HostOptions {
+            keychain: Some(std::collections::HashMap::from([
+                ("openusage:openai:setting:apiKey".to_string(), "sk-openai".to_string()),
+                ("gh:github.com".to_string(), "gho".to_string()),
+            ])),
+            ..Default::default()
+        };
+        let output = run_probe_with_options(&plugin, &temp_app_dir("keychain-scope"), "0.0.0", &options);
+        let reserved = "Error: keychain service names starting with \"openusage:\" are reserved";
+        match output.lines.first() {
+            Some(MetricLine::Text { label, value, .. }) => {
+                assert_eq!(label, &format!("{}|{}", reserved, reserved));
+                assert_eq!(value, "gh:github.com");
+            }
+            other => panic!("expected text line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_probe_runs_wasm_entry() {
+        let module = |result: &str| {
+            let text = format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "openusage_alloc") (param i32) (result i32) (i32.const 4096))
+                    (data (i32.const 0) "{}")
+                    (func (export "openusage_probe") (param i32 i32) (result i64) (i64.const {})))"#,
+                result.replace('"', "\\\""),
+                result.len()
+            );
+            Some(std::sync::Arc::from(wat::parse_str(text).expect("wat")))
+        };
+        let mut plugin = test_plugin("");
+        plugin.manifest.entry = "plugin.wasm".to_string();
+
+        plugin.entry_wasm = module(
+            r#"{"plan":"Pro","lines":[{"type":"progress","label":"Quota","used":40,"limit":100,"format":{"kind":"percent"}}]}"#,
+        );
+        let output = run_probe(&plugin, &temp_app_dir("wasm"), "0.0.0");
+        assert_eq!(output.plan.as_deref(), Some("Pro"));
+        assert!(matches!(output.lines.first(), Some(MetricLine::Progress { used, .. }) if *used == 40.0));
+
+        plugin.entry_wasm = module(r#"{"error":"Token expired. Sign in again."}"#);
+        let output = run_probe(&plugin, &temp_app_dir("wasm-error"), "0.0.0");
+        assert_eq!(error_text(output), "Token expired. Sign in again.");
+    }
+
+    #[test]
+    fn run_probe_requires_modules_from_plugin_dir() {
+        let plugin_dir = temp_app_dir("require-plugin");
+        std::fs::create_dir_all(plugin_dir.join("lib")).expect("lib dir");
+        std::fs::write(
+            plugin_dir.join("lib/auth.js"),
+            "var util = require(\"../util\");\nexports.token = function () { return util.prefix + \"-tok\"; };\n",
+        )
+        .expect("auth.js");
+        std::fs::write(
+            plugin_dir.join("util.js"),
+            "globalThis.__loads = (globalThis.__loads || 0) + 1;\nmodule.exports = { prefix: \"abc\" };\n",
+        )
+        .expect("util.js");
+        std::fs::write(plugin_dir.join("lib/broken.js"), "var ok = 1;\nvar = ;\n").expect("broken.js");
+
+        let mut plugin = test_plugin(
+            r#"
+            var auth = require("./lib/auth.js");
+            require("./util");
+            globalThis.__openusage_plugin = {
+                probe(ctx) {
+                    return { lines: [ctx.line.text({ label: auth.token(), value: String(globalThis.__loads) })] };
+                }
+            };
+            "#,
+        );
+        plugin.plugin_dir = plugin_dir.to_path_buf();
+        let output = run_probe(&plugin, &temp_app_dir("require"), "0.0.0");
+        match output.lines.first() {
+            Some(MetricLine::Text { label, value, .. }) => {
+                assert_eq!(label, "abc-tok");
+                assert_eq!(value, "1");
+            }
+            other => panic!("expected text line, got {:?}", other),
+        }
+
+        plugin.entry_script = r#"
+            globalThis.__openusage_plugin = {
+                probe() { require("./lib/broken.js"); }
+            };
+        "#
+        .to_string();
+        let output = run_probe(&plugin, &temp_app_dir("require-broken"), "0.0.0");
+        let message = error_text(output);
+        assert!(message.starts_with("lib/broken.js:2: SyntaxError:"), "{}", message);
+    }
+
     #[test]
     fn progress_resets_at_serializes_as_resets_at_camelcase() {
         let line = MetricLine::Progress {
@@ -0,0 +1,35 @@
+// openusage:connect v1 — unary Connect-RPC calls over JSON.
+
+// POSTs `opts.body` (default `{}`) to `url` and returns the raw response.
+// Options: token (sent as a bearer token), headers, timeoutMs (default
+// 10000), cache and dangerouslyIgnoreTls, passed through to the request.
+function post(ctx, url, opts) {
+  opts = opts || {}
+  var headers = {}
+  if (opts.token) headers.Authorization = "Bearer " + opts.token
+  headers["Content-Type"] = "application/json"
+  headers["Connect-Protocol-Version"] = "1"
+  if (opts.headers) {
+    for (var name in opts.headers) headers[name] = opts.headers[name]
+  }
+  var req = {
+    method: "POST",
+    url: url,
+    headers: headers,
+    bodyText: JSON.stringify(opts.body === undefined ? {} : opts.body),
+    timeoutMs: opts.timeoutMs || 10000,
+  }
+  if (opts.cache) req.cache = opts.cache
+  if (opts.dangerouslyIgnoreTls) req.dangerouslyIgnoreTls = true
+  return ctx.util.request(req)
+}
+
+// Like `post`, returning `{ resp, json }` where json is null unless the
+// call succeeded with a JSON body.
+function postJson(ctx, url, opts) {
+  var resp = post(ctx, url, opts)
+  var ok = resp.status >= 200 && resp.status < 300
+  return { resp: resp, json: ok ? ctx.util.tryParseJson(resp.bodyText) : null }
+}
+
+module.exports = { post: post, postJson: postJson }
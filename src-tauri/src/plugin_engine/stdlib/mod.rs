@@ -0,0 +1,65 @@
+//! Shared modules shipped with the app, required as `openusage:<name>`.
+//!
+//! A plugin pins the major version of each module it uses in the manifest
+//! (`"stdlib": { "vscdb": 1 }`). A breaking change ships as a new file next
+//! to the old one, so pinned plugins keep getting the version they were
+//! written against.
+
+use std::collections::BTreeMap;
+
+/// Every shipped `(name, major, source)`.
+const MODULES: &[(&str, u32, &str)] = &[
+    ("connect", 1, include_str!("connect.v1.js")),
+    ("oauth", 1, include_str!("oauth.v1.js")),
+    ("vscdb", 1, include_str!("vscdb.v1.js")),
+];
+
+pub fn source(name: &str, major: u32) -> Option<&'static str> {
+    MODULES
+        .iter()
+        .find(|(n, m, _)| *n == name && *m == major)
+        .map(|(_, _, source)| *source)
+}
+
+/// Checks that every pinned module exists at the pinned major version.
+pub fn validate(pins: &BTreeMap<String, u32>) -> Result<(), String> {
+    for (name, major) in pins {
+        if source(name, *major).is_some() {
+            continue;
+        }
+        let available: Vec<String> = MODULES
+            .iter()
+            .filter(|(n, _, _)| n == name)
+            .map(|(_, m, _)| m.to_string())
+            .collect();
+        if available.is_empty() {
+            return Err(format!("unknown stdlib module \"{}\"", name));
+        }
+        return Err(format!(
+            "stdlib module \"{}\" has no major version {} (available: {})",
+            name,
+            major,
+            available.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_unknown_modules_and_versions() {
+        let pins = |name: &str, major: u32| BTreeMap::from([(name.to_string(), major)]);
+        assert!(validate(&pins("vscdb", 1)).is_ok());
+        assert_eq!(
+            validate(&pins("vscdb", 2)).unwrap_err(),
+            "stdlib module \"vscdb\" has no major version 2 (available: 1)"
+        );
+        assert_eq!(
+            validate(&pins("left-pad", 1)).unwrap_err(),
+            "unknown stdlib module \"left-pad\""
+        );
+    }
+}
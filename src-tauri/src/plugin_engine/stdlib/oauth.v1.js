@@ -0,0 +1,27 @@
+// openusage:oauth v1 — access token expiry checks for plugins that manage
+// their own credentials. Refreshing is left to `host.oauth`, which saves the
+// new tokens and refreshes each credential source once at a time.
+
+// `exp` of a JWT in milliseconds, or null when the token has none.
+function jwtExpiryMs(ctx, token) {
+  var payload = token ? ctx.jwt.decodePayload(token) : null
+  if (!payload || typeof payload.exp !== "number") return null
+  return payload.exp * 1000
+}
+
+// True when `token` is missing or its JWT expiry is within `opts.bufferMs`
+// (default 5 minutes) of `opts.nowMs` (default now).
+function needsRefresh(ctx, token, opts) {
+  opts = opts || {}
+  if (!token) return true
+  return ctx.util.needsRefreshByExpiry({
+    nowMs: opts.nowMs === undefined ? Date.now() : opts.nowMs,
+    expiresAtMs: jwtExpiryMs(ctx, token),
+    bufferMs: opts.bufferMs === undefined ? 5 * 60 * 1000 : opts.bufferMs,
+  })
+}
+
+module.exports = {
+  jwtExpiryMs: jwtExpiryMs,
+  needsRefresh: needsRefresh,
+}
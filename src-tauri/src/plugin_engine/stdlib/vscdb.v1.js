@@ -0,0 +1,68 @@
+// openusage:vscdb v1 — key/value reads and writes on the state.vscdb
+// SQLite store that VS Code-based editors keep in their globalStorage.
+
+var LOCATIONS = {
+  macos: "~/Library/Application Support/{app}/User/globalStorage/state.vscdb",
+  windows: "~/AppData/Roaming/{app}/User/globalStorage/state.vscdb",
+  linux: "~/.config/{app}/User/globalStorage/state.vscdb",
+}
+
+// Default database of the editor whose user data folder is named `app`
+// (e.g. "Cursor"), for the platform the app runs on.
+function defaultPath(ctx, app) {
+  var platform = String((ctx && ctx.app && ctx.app.platform) || "").toLowerCase()
+  var template = LOCATIONS[platform] || LOCATIONS.macos
+  return template.replace("{app}", app)
+}
+
+// `ctx.config.stateDb` when the user set one, else `defaultPath`.
+function statePath(ctx, app) {
+  if (ctx && ctx.config && ctx.config.stateDb) return ctx.config.stateDb
+  return defaultPath(ctx, app)
+}
+
+// The stored string for `key`, or null when it is missing or unreadable.
+function read(ctx, dbPath, key) {
+  try {
+    var rows = ctx.util.tryParseJson(
+      ctx.host.sqlite.query(dbPath, "SELECT value FROM ItemTable WHERE key = ? LIMIT 1;", [key])
+    )
+    if (!Array.isArray(rows)) {
+      throw new Error("sqlite returned invalid json")
+    }
+    if (rows.length > 0 && rows[0].value) {
+      return rows[0].value
+    }
+  } catch (e) {
+    ctx.host.log.warn("sqlite read failed for " + key + ": " + String(e))
+  }
+  return null
+}
+
+function readJson(ctx, dbPath, key) {
+  return ctx.util.tryParseJson(read(ctx, dbPath, key))
+}
+
+// Stores `value` as a string; needs the database in `permissions.sqliteWrite`.
+// Returns false instead of throwing when the write fails.
+function write(ctx, dbPath, key, value) {
+  try {
+    ctx.host.sqlite.exec(
+      dbPath,
+      "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?, ?);",
+      [key, String(value)]
+    )
+    return true
+  } catch (e) {
+    ctx.host.log.warn("sqlite write failed for " + key + ": " + String(e))
+    return false
+  }
+}
+
+module.exports = {
+  defaultPath: defaultPath,
+  statePath: statePath,
+  read: read,
+  readJson: readJson,
+  write: write,
+}
@@ -0,0 +1,952 @@
+//! End-to-end tests for the bundled plugins.
+//!
+//! Each test runs a real `plugins/<id>/plugin.js` through `run_probe_with_options`
+//! with a throwaway home directory, fake credentials and every `https://` URL
+//! rewritten to a local fake server. Routes are keyed by the provider's real URL,
+//! so fixtures read like the plugin source.
+
+use crate::plugin_engine::credential_store::{CredentialStore, MemoryStore};
+use crate::plugin_engine::host_api::{HostOptions, HttpHost};
+use crate::plugin_engine::manifest::{self, LoadedPlugin};
+use crate::plugin_engine::oauth;
+use crate::plugin_engine::oauth_login::{self, LoginOptions, LoginPrompt};
+use crate::plugin_engine::runtime::{self, MetricLine, PluginOutput};
+use crate::plugin_engine::test_util::TempDir;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone)]
+struct FakeResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl FakeResponse {
+    fn json(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RecordedRequest {
+    /// The original URL the plugin asked for, e.g. `https://api.example.com/x`.
+    url: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+impl RecordedRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+type Routes = HashMap<(String, String), VecDeque<FakeResponse>>;
+
+/// Minimal HTTP/1.1 server. Each route serves its responses in order and
+/// keeps repeating the last one; unknown routes get a 404.
+struct FakeServer {
+    base_url: String,
+    routes: Arc<Mutex<Routes>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FakeServer {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind fake server");
+        listener.set_nonblocking(true).expect("nonblocking listener");
+        let base_url = format!("http://{}", listener.local_addr().expect("addr"));
+        let routes: Arc<Mutex<Routes>> = Arc::default();
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (thread_routes, thread_requests, thread_stop) =
+            (Arc::clone(&routes), Arc::clone(&requests), Arc::clone(&stop));
+        let handle = std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => serve(stream, &thread_routes, &thread_requests),
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(std::time::Duration::from_millis(5));
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Self {
+            base_url,
+            routes,
+            requests,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    fn route(&self, method: &str, url: &str, responses: Vec<FakeResponse>) {
+        self.routes
+            .lock()
+            .expect("routes")
+            .insert((method.to_string(), url.to_string()), responses.into());
+    }
+
+    fn requests_to(&self, url: &str) -> Vec<RecordedRequest> {
+        self.requests
+            .lock()
+            .expect("requests")
+            .iter()
+            .filter(|req| req.url.split('?').next() == Some(url))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve(stream: TcpStream, routes: &Mutex<Routes>, requests: &Mutex<Vec<RecordedRequest>>) {
+    let _ = stream.set_nonblocking(false);
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return,
+    });
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    // Requests arrive as `/<host>/<path>`; turn them back into the real URL.
+    let url = format!("https://{}", target.trim_start_matches('/'));
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+
+    let route_key = (method, url.split('?').next().unwrap_or_default().to_string());
+    let response = {
+        let mut routes = routes.lock().expect("routes");
+        match routes.get_mut(&route_key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        }
+    }
+    .unwrap_or_else(|| FakeResponse::json(404, r#"{"error":"no fake route"}"#));
+
+    requests.lock().expect("requests").push(RecordedRequest {
+        url,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    });
+
+    let mut out = format!("HTTP/1.1 {} Fake\r\n", response.status);
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.body.len(),
+        response.body
+    ));
+    let mut stream = stream;
+    let _ = stream.write_all(out.as_bytes());
+    let _ = stream.flush();
+}
+
+/// A fake machine for one plugin: temp home and app data dirs, an in-memory
+/// keychain and a fake server standing in for every provider endpoint.
+struct Harness {
+    root: TempDir,
+    home: PathBuf,
+    app_data: PathBuf,
+    env: HashMap<String, String>,
+    keychain: HashMap<String, String>,
+    server: FakeServer,
+}
+
+impl Harness {
+    fn new(label: &str) -> Self {
+        let root = TempDir::new(&format!("harness-{}", label));
+        let home = root.join("home");
+        let app_data = root.join("app_data");
+        std::fs::create_dir_all(&home).expect("home dir");
+        std::fs::create_dir_all(&app_data).expect("app data dir");
+        Self {
+            root,
+            home,
+            app_data,
+            env: HashMap::new(),
+            keychain: HashMap::new(),
+            server: FakeServer::start(),
+        }
+    }
+
+    fn write_home_file(&self, relative: &str, contents: &str) -> PathBuf {
+        let path = self.home.join(relative);
+        std::fs::create_dir_all(path.parent().expect("parent")).expect("create parent");
+        std::fs::write(&path, contents).expect("write home file");
+        path
+    }
+
+    fn read_home_file(&self, relative: &str) -> String {
+        std::fs::read_to_string(self.home.join(relative)).expect("read home file")
+    }
+
+    fn plugin_data_dir(&self, plugin_id: &str) -> PathBuf {
+        self.app_data.join("plugins_data").join(plugin_id)
+    }
+
+    fn run(&self, plugin_id: &str) -> PluginOutput {
+        self.run_plugin(&bundled_plugin(plugin_id))
+    }
+
+    fn run_plugin(&self, plugin: &LoadedPlugin) -> PluginOutput {
+        let options = HostOptions {
+            url_overrides: vec![("https://".to_string(), format!("{}/", self.server.base_url))],
+            home_dir: Some(self.home.clone()),
+            env: Some(self.env.clone()),
+            keychain: Some(self.keychain.clone()),
+            exec: Some(Vec::new()),
+            ..Default::default()
+        };
+        runtime::run_probe_with_options(plugin, &self.app_data, "0.0.0-test", &options)
+    }
+}
+
+fn bundled_plugin(plugin_id: &str) -> LoadedPlugin {
+    let plugins_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../plugins");
+    manifest::load_plugins_from_dir(&plugins_dir)
+        .into_iter()
+        .find(|plugin| plugin.manifest.id == plugin_id)
+        .unwrap_or_else(|| panic!("bundled plugin '{}' not found", plugin_id))
+}
+
+fn error_text(output: &PluginOutput) -> Option<&str> {
+    match output.lines.first() {
+        Some(MetricLine::Badge { label, text, .. }) if label == "Error" => Some(text),
+        _ => None,
+    }
+}
+
+fn progress(output: &PluginOutput, wanted: &str) -> (f64, f64) {
+    if let Some(err) = error_text(output) {
+        panic!("probe failed: {}", err);
+    }
+    output
+        .lines
+        .iter()
+        .find_map(|line| match line {
+            MetricLine::Progress { label, used, limit, .. } if label == wanted => {
+                Some((*used, *limit))
+            }
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no progress line '{}' in {:?}", wanted, output.lines))
+}
+
+fn text(output: &PluginOutput, wanted: &str) -> String {
+    output
+        .lines
+        .iter()
+        .find_map(|line| match line {
+            MetricLine::Text { label, value, .. } if label == wanted => Some(value.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("no text line '{}' in {:?}", wanted, output.lines))
+}
+
+fn unsigned_jwt(payload: &str) -> String {
+    use base64::Engine;
+    let encode = |part: &str| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(part);
+    format!("{}.{}.sig", encode(r#"{"alg":"none"}"#), encode(payload))
+}
+
+fn far_future_ms() -> u64 {
+    crate::plugin_engine::http_cache::now_ms() + 24 * 60 * 60 * 1000
+}
+
+// --- Claude -----------------------------------------------------------------
+
+const CLAUDE_USAGE: &str = "https://api.anthropic.com/api/oauth/usage";
+const CLAUDE_REFRESH: &str = "https://platform.claude.com/v1/oauth/token";
+const CLAUDE_CREDS: &str = ".claude/.credentials.json";
+const CLAUDE_USAGE_BODY: &str = r#"{
+    "five_hour": {"utilization": 42, "resets_at": "2030-01-01T00:00:00Z"},
+    "seven_day": {"utilization": 10, "resets_at": "2030-01-07T00:00:00Z"}
+}"#;
+
+fn claude_harness(label: &str) -> Harness {
+    let harness = Harness::new(label);
+    harness.write_home_file(
+        CLAUDE_CREDS,
+        &format!(
+            r#"{{"claudeAiOauth": {{"accessToken": "old-access", "refreshToken": "old-refresh", "expiresAt": {}, "subscriptionType": "pro"}}}}"#,
+            far_future_ms()
+        ),
+    );
+    harness
+}
+
+#[test]
+fn claude_reports_session_and_weekly_usage() {
+    let harness = claude_harness("claude-ok");
+    harness
+        .server
+        .route("GET", CLAUDE_USAGE, vec![FakeResponse::json(200, CLAUDE_USAGE_BODY)]);
+
+    let output = harness.run("claude");
+
+    assert_eq!(output.plan.as_deref(), Some("Pro"));
+    assert_eq!(progress(&output, "Session"), (42.0, 100.0));
+    assert_eq!(progress(&output, "Weekly"), (10.0, 100.0));
+    let requests = harness.server.requests_to(CLAUDE_USAGE);
+    assert_eq!(requests[0].header("authorization"), Some("Bearer old-access"));
+}
+
+#[test]
+fn claude_refreshes_token_after_401_and_persists_it() {
+    let harness = claude_harness("claude-refresh");
+    harness.server.route(
+        "GET",
+        CLAUDE_USAGE,
+        vec![
+            FakeResponse::json(401, r#"{"error":"expired"}"#),
+            FakeResponse::json(200, CLAUDE_USAGE_BODY),
+        ],
+    );
+    harness.server.route(
+        "POST",
+        CLAUDE_REFRESH,
+        vec![FakeResponse::json(
+            200,
+            r#"{"access_token": "new-access", "refresh_token": "new-refresh", "expires_in": 3600}"#,
+        )],
+    );
+
+    let output = harness.run("claude");
+
+    assert_eq!(progress(&output, "Session"), (42.0, 100.0));
+    let refresh = harness.server.requests_to(CLAUDE_REFRESH);
+    assert_eq!(refresh.len(), 1);
+    assert!(refresh[0].body.contains("\"refresh_token\":\"old-refresh\""));
+    let usage = harness.server.requests_to(CLAUDE_USAGE);
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[1].header("authorization"), Some("Bearer new-access"));
+    let saved = harness.read_home_file(CLAUDE_CREDS);
+    assert!(saved.contains("new-access") && saved.contains("new-refresh"));
+}
+
+#[test]
+fn claude_surfaces_rate_limit_and_malformed_json() {
+    let harness = claude_harness("claude-429");
+    harness
+        .server
+        .route("GET", CLAUDE_USAGE, vec![FakeResponse::json(429, r#"{"error":"slow down"}"#)]);
+    assert_eq!(
+        error_text(&harness.run("claude")),
+        Some("Usage request failed (HTTP 429). Try again later.")
+    );
+
+    let harness = claude_harness("claude-malformed");
+    harness
+        .server
+        .route("GET", CLAUDE_USAGE, vec![FakeResponse::json(200, "{not json")]);
+    assert_eq!(
+        error_text(&harness.run("claude")),
+        Some("Usage response invalid. Try again later.")
+    );
+}
+
+// --- Codex ------------------------------------------------------------------
+
+const CODEX_USAGE: &str = "https://chatgpt.com/backend-api/wham/usage";
+const CODEX_REFRESH: &str = "https://auth.openai.com/oauth/token";
+
+fn codex_harness(label: &str, last_refresh: &str) -> Harness {
+    let mut harness = Harness::new(label);
+    let codex_home = harness.home.join("custom-codex");
+    harness.write_home_file(
+        "custom-codex/auth.json",
+        &format!(
+            r#"{{"tokens": {{"access_token": "old-access", "refresh_token": "old-refresh", "account_id": "acct-1"}}, "last_refresh": "{}"}}"#,
+            last_refresh
+        ),
+    );
+    harness
+        .env
+        .insert("CODEX_HOME".to_string(), codex_home.to_string_lossy().to_string());
+    harness
+}
+
+#[test]
+fn codex_reads_codex_home_and_usage_headers() {
+    let now = time::OffsetDateTime::now_utc()
+        .format(&time::format_description::well_known::Rfc3339)
+        .expect("now");
+    let harness = codex_harness("codex-ok", &now);
+    harness.server.route(
+        "GET",
+        CODEX_USAGE,
+        vec![
+            FakeResponse::json(200, r#"{"plan_type": "plus", "rate_limit": {}}"#)
+                .with_header("x-codex-primary-used-percent", "25")
+                .with_header("x-codex-secondary-used-percent", "60"),
+        ],
+    );
+
+    let output = harness.run("codex");
+
+    assert_eq!(progress(&output, "Session"), (25.0, 100.0));
+    assert_eq!(progress(&output, "Weekly"), (60.0, 100.0));
+    assert!(harness.server.requests_to(CODEX_REFRESH).is_empty());
+    let usage = harness.server.requests_to(CODEX_USAGE);
+    assert_eq!(usage[0].header("chatgpt-account-id"), Some("acct-1"));
+}
+
+#[test]
+fn codex_refreshes_stale_token_before_fetching_usage() {
+    let harness = codex_harness("codex-refresh", "2020-01-01T00:00:00Z");
+    harness.server.route(
+        "POST",
+        CODEX_REFRESH,
+        vec![FakeResponse::json(200, r#"{"access_token": "new-access"}"#)],
+    );
+    harness.server.route(
+        "GET",
+        CODEX_USAGE,
+        vec![FakeResponse::json(
+            200,
+            r#"{"rate_limit": {"primary_window": {"used_percent": 5}}}"#,
+        )],
+    );
+
+    let output = harness.run("codex");
+
+    assert_eq!(progress(&output, "Session"), (5.0, 100.0));
+    let refresh = harness.server.requests_to(CODEX_REFRESH);
+    assert!(refresh[0].body.contains("refresh_token=old-refresh"));
+    let usage = harness.server.requests_to(CODEX_USAGE);
+    assert_eq!(usage[0].header("authorization"), Some("Bearer new-access"));
+    assert!(harness.read_home_file("custom-codex/auth.json").contains("new-access"));
+}
+
+// --- Kimi -------------------------------------------------------------------
+
+const KIMI_USAGE: &str = "https://api.kimi.com/coding/v1/usages";
+const KIMI_REFRESH: &str = "https://auth.kimi.com/api/oauth/token";
+const KIMI_CREDS: &str = ".kimi/credentials/kimi-code.json";
+
+fn kimi_harness(label: &str) -> Harness {
+    let harness = Harness::new(label);
+    harness.write_home_file(
+        KIMI_CREDS,
+        &format!(
+            r#"{{"access_token": "old-access", "refresh_token": "old-refresh", "expires_at": {}}}"#,
+            far_future_ms() / 1000
+        ),
+    );
+    harness
+}
+
+#[test]
+fn kimi_reports_session_and_weekly_limits() {
+    let harness = kimi_harness("kimi-ok");
+    harness.server.route(
+        "GET",
+        KIMI_USAGE,
+        vec![FakeResponse::json(
+            200,
+            r#"{
+                "usage": {"limit": 1000, "used": 250},
+                "limits": [{"window": {"duration": 5, "timeUnit": "HOUR"}, "detail": {"limit": 100, "used": 40}}]
+            }"#,
+        )],
+    );
+
+    let output = harness.run("kimi");
+
+    assert_eq!(progress(&output, "Session"), (40.0, 100.0));
+    assert_eq!(progress(&output, "Weekly"), (25.0, 100.0));
+}
+
+#[test]
+fn kimi_refreshes_expiring_token_through_host_oauth() {
+    let harness = kimi_harness("kimi-refresh");
+    harness.write_home_file(
+        KIMI_CREDS,
+        r#"{"access_token": "old-access", "refresh_token": "old-refresh", "expires_at": 1, "scope": "kimi-code"}"#,
+    );
+    harness.server.route(
+        "POST",
+        KIMI_REFRESH,
+        vec![FakeResponse::json(
+            200,
+            r#"{"access_token": "new-access", "refresh_token": "new-refresh", "expires_in": 3600}"#,
+        )],
+    );
+    harness.server.route(
+        "GET",
+        KIMI_USAGE,
+        vec![FakeResponse::json(200, r#"{"usage": {"limit": 100, "used": 10}}"#)],
+    );
+
+    let output = harness.run("kimi");
+
+    assert_eq!(progress(&output, "Weekly"), (10.0, 100.0));
+    let refresh = harness.server.requests_to(KIMI_REFRESH);
+    assert_eq!(refresh.len(), 1);
+    assert!(refresh[0].body.contains("grant_type=refresh_token&refresh_token=old-refresh"));
+    assert_eq!(
+        harness.server.requests_to(KIMI_USAGE)[0].header("authorization"),
+        Some("Bearer new-access")
+    );
+    let saved: serde_json::Value =
+        serde_json::from_str(&harness.read_home_file(KIMI_CREDS)).expect("saved json");
+    assert_eq!(saved["refresh_token"], "new-refresh");
+    assert_eq!(saved["scope"], "kimi-code");
+}
+
+#[test]
+fn kimi_reports_expired_session_when_refresh_is_rejected() {
+    let harness = kimi_harness("kimi-expired");
+    harness
+        .server
+        .route("GET", KIMI_USAGE, vec![FakeResponse::json(401, "{}")]);
+    harness
+        .server
+        .route("POST", KIMI_REFRESH, vec![FakeResponse::json(401, r#"{"error":"invalid_grant"}"#)]);
+
+    let output = harness.run("kimi");
+
+    assert_eq!(
+        error_text(&output),
+        Some("Session expired. Run `kimi login` to authenticate.")
+    );
+    assert_eq!(harness.server.requests_to(KIMI_REFRESH).len(), 1);
+}
+
+#[test]
+fn kimi_device_login_feeds_host_oauth() {
+    let mut harness = Harness::new("kimi-login");
+    harness.server.route(
+        "POST",
+        "https://auth.kimi.com/api/oauth/device_authorization",
+        vec![FakeResponse::json(
+            200,
+            r#"{"device_code": "dev-1", "user_code": "KIMI-1234", "verification_uri": "https://www.kimi.com/code/authorize_device", "interval": 0}"#,
+        )],
+    );
+    harness.server.route(
+        "POST",
+        KIMI_REFRESH,
+        vec![
+            FakeResponse::json(400, r#"{"error": "authorization_pending"}"#),
+            FakeResponse::json(
+                200,
+                r#"{"access_token": "login-access", "refresh_token": "login-refresh", "expires_in": 3600}"#,
+            ),
+        ],
+    );
+    harness.server.route(
+        "GET",
+        KIMI_USAGE,
+        vec![FakeResponse::json(200, r#"{"usage": {"limit": 100, "used": 5}}"#)],
+    );
+
+    let plugin = bundled_plugin("kimi");
+    let config = plugin.manifest.oauth.as_ref().expect("kimi oauth");
+    let store = MemoryStore::new(HashMap::new());
+    let http = HttpHost::new(
+        "kimi",
+        &harness.plugin_data_dir("kimi"),
+        None,
+        vec![("https://".to_string(), format!("{}/", harness.server.base_url))],
+    );
+    let transport = oauth::http_transport(Arc::new(http));
+    let prompts = Mutex::new(Vec::new());
+    let options = LoginOptions {
+        min_poll_interval: std::time::Duration::ZERO,
+        ..LoginOptions::default()
+    };
+    oauth_login::login("kimi", config, &store, &transport, &|p| prompts.lock().expect("prompts").push(p), &options)
+        .expect("login");
+    assert!(matches!(
+        &prompts.lock().expect("prompts")[0],
+        LoginPrompt::DeviceCode { user_code, .. } if user_code == "KIMI-1234"
+    ));
+
+    let service = oauth::login_service("kimi");
+    harness
+        .keychain
+        .insert(service.clone(), store.read(&service).expect("stored tokens"));
+    let output = harness.run("kimi");
+
+    assert_eq!(progress(&output, "Weekly"), (5.0, 100.0));
+    assert_eq!(
+        harness.server.requests_to(KIMI_USAGE)[0].header("authorization"),
+        Some("Bearer login-access")
+    );
+}
+
+// --- Copilot ----------------------------------------------------------------
+
+const COPILOT_USAGE: &str = "https://api.github.com/copilot_internal/user";
+const COPILOT_USAGE_BODY: &str = r#"{
+    "copilot_plan": "individual",
+    "quota_reset_date": "2030-02-01",
+    "quota_snapshots": {
+        "premium_interactions": {"percent_remaining": 70},
+        "chat": {"percent_remaining": 100}
+    }
+}"#;
+
+#[test]
+fn copilot_uses_token_from_state_file() {
+    let harness = Harness::new("copilot-state");
+    let data_dir = harness.plugin_data_dir("copilot");
+    std::fs::create_dir_all(&data_dir).expect("plugin data dir");
+    std::fs::write(data_dir.join("auth.json"), r#"{"token": "state-token"}"#).expect("auth");
+    harness
+        .server
+        .route("GET", COPILOT_USAGE, vec![FakeResponse::json(200, COPILOT_USAGE_BODY)]);
+
+    let output = harness.run("copilot");
+
+    assert_eq!(output.plan.as_deref(), Some("Individual"));
+    assert_eq!(progress(&output, "Premium"), (30.0, 100.0));
+    assert_eq!(progress(&output, "Chat"), (0.0, 100.0));
+    let usage = harness.server.requests_to(COPILOT_USAGE);
+    assert_eq!(usage[0].header("authorization"), Some("token state-token"));
+}
+
+#[test]
+fn copilot_falls_back_to_gh_token_when_cached_token_is_rejected() {
+    let mut harness = Harness::new("copilot-fallback");
+    harness.keychain.insert(
+        "OpenUsage-copilot".to_string(),
+        r#"{"token": "stale-token"}"#.to_string(),
+    );
+    harness
+        .keychain
+        .insert("gh:github.com".to_string(), "gh-token".to_string());
+    harness.server.route(
+        "GET",
+        COPILOT_USAGE,
+        vec![
+            FakeResponse::json(401, r#"{"message":"Bad credentials"}"#),
+            FakeResponse::json(200, COPILOT_USAGE_BODY),
+        ],
+    );
+
+    let output = harness.run("copilot");
+
+    assert_eq!(progress(&output, "Premium"), (30.0, 100.0));
+    let usage = harness.server.requests_to(COPILOT_USAGE);
+    assert_eq!(usage[0].header("authorization"), Some("token stale-token"));
+    assert_eq!(usage[1].header("authorization"), Some("token gh-token"));
+}
+
+// --- Amp --------------------------------------------------------------------
+
+const AMP_API: &str = "https://ampcode.com/api/internal";
+const AMP_SECRETS: &str = ".local/share/amp/secrets.json";
+
+fn amp_harness(label: &str) -> Harness {
+    let harness = Harness::new(label);
+    harness.write_home_file(AMP_SECRETS, r#"{"apiKey@https://ampcode.com/": "amp-key"}"#);
+    harness
+}
+
+#[test]
+fn amp_parses_free_balance_and_credits() {
+    let harness = amp_harness("amp-ok");
+    harness.server.route(
+        "POST",
+        AMP_API,
+        vec![FakeResponse::json(
+            200,
+            r#"{"ok": true, "result": {"displayText": "Amp Free: $7.50/$10 remaining (replenishes +$0.42/hour). Individual credits: $3.25 remaining"}}"#,
+        )],
+    );
+
+    let output = harness.run("amp");
+
+    assert_eq!(output.plan.as_deref(), Some("Free"));
+    assert_eq!(progress(&output, "Free"), (2.5, 10.0));
+    assert_eq!(text(&output, "Credits"), "$3.25");
+    let requests = harness.server.requests_to(AMP_API);
+    assert_eq!(requests[0].header("authorization"), Some("Bearer amp-key"));
+    assert!(requests[0].body.contains("userDisplayBalanceInfo"));
+}
+
+#[test]
+fn amp_reports_expired_session_on_401() {
+    let harness = amp_harness("amp-401");
+    harness
+        .server
+        .route("POST", AMP_API, vec![FakeResponse::json(401, "{}")]);
+
+    assert_eq!(
+        error_text(&harness.run("amp")),
+        Some("Session expired. Re-authenticate in Amp Code.")
+    );
+}
+
+// --- Cursor -----------------------------------------------------------------
+
+const CURSOR_USAGE: &str =
+    "https://api2.cursor.sh/aiserver.v1.DashboardService/GetCurrentPeriodUsage";
+const CURSOR_PLAN: &str = "https://api2.cursor.sh/aiserver.v1.DashboardService/GetPlanInfo";
+const CURSOR_CREDITS: &str =
+    "https://api2.cursor.sh/aiserver.v1.DashboardService/GetCreditGrantsBalance";
+
+fn cursor_state_db() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "Library/Application Support/Cursor/User/globalStorage/state.vscdb",
+        "windows" => "AppData/Roaming/Cursor/User/globalStorage/state.vscdb",
+        _ => ".config/Cursor/User/globalStorage/state.vscdb",
+    }
+}
+
+fn cursor_harness(label: &str) -> Harness {
+    let harness = Harness::new(label);
+    let db_path = harness.write_home_file(cursor_state_db(), "");
+    std::fs::remove_file(&db_path).expect("reset db file");
+    let conn = rusqlite::Connection::open(&db_path).expect("open state db");
+    let access = unsigned_jwt(&format!(
+        r#"{{"sub": "auth0|user_1", "exp": {}}}"#,
+        far_future_ms() / 1000
+    ));
+    conn.execute_batch("CREATE TABLE ItemTable (key TEXT PRIMARY KEY, value TEXT);")
+        .expect("create table");
+    conn.execute(
+        "INSERT INTO ItemTable (key, value) VALUES ('cursorAuth/accessToken', ?1), ('cursorAuth/refreshToken', 'refresh')",
+        [&access],
+    )
+    .expect("seed tokens");
+    harness
+}
+
+#[test]
+fn cursor_reports_credits_plan_usage_and_on_demand() {
+    let harness = cursor_harness("cursor-ok");
+    harness.server.route(
+        "POST",
+        CURSOR_USAGE,
+        vec![FakeResponse::json(
+            200,
+            r#"{
+                "enabled": true,
+                "billingCycleStart": "1767225600000",
+                "billingCycleEnd": "1769904000000",
+                "planUsage": {"limit": 2000, "totalSpend": 500},
+                "spendLimitUsage": {"individualLimit": 10000, "individualRemaining": 7500}
+            }"#,
+        )],
+    );
+    harness.server.route(
+        "POST",
+        CURSOR_PLAN,
+        vec![FakeResponse::json(200, r#"{"planInfo": {"planName": "pro"}}"#)],
+    );
+    harness.server.route(
+        "POST",
+        CURSOR_CREDITS,
+        vec![FakeResponse::json(
+            200,
+            r#"{"hasCreditGrants": true, "totalCents": "5000", "usedCents": "1250"}"#,
+        )],
+    );
+
+    let output = harness.run("cursor");
+
+    assert_eq!(output.plan.as_deref(), Some("Pro"));
+    assert_eq!(progress(&output, "Credits"), (12.5, 50.0));
+    assert_eq!(progress(&output, "Plan usage"), (5.0, 20.0));
+    assert_eq!(progress(&output, "On-demand"), (25.0, 100.0));
+    let usage = harness.server.requests_to(CURSOR_USAGE);
+    assert_eq!(usage[0].header("connect-protocol-version"), Some("1"));
+}
+
+#[test]
+fn cursor_refresh_writes_new_token_back_to_state_db() {
+    let harness = cursor_harness("cursor-refresh");
+    let db_path = harness.home.join(cursor_state_db());
+    let expired = unsigned_jwt(r#"{"sub": "auth0|user_1", "exp": 1}"#);
+    let fresh = unsigned_jwt(&format!(r#"{{"exp": {}}}"#, far_future_ms() / 1000));
+    rusqlite::Connection::open(&db_path)
+        .expect("open state db")
+        .execute(
+            "UPDATE ItemTable SET value = ?1 WHERE key = 'cursorAuth/accessToken'",
+            [&expired],
+        )
+        .expect("expire token");
+    harness.server.route(
+        "POST",
+        "https://api2.cursor.sh/oauth/token",
+        vec![FakeResponse::json(200, &format!(r#"{{"access_token": "{}"}}"#, fresh))],
+    );
+    harness.server.route(
+        "POST",
+        CURSOR_USAGE,
+        vec![FakeResponse::json(
+            200,
+            r#"{"enabled": true, "planUsage": {"limit": 2000, "totalSpend": 500}}"#,
+        )],
+    );
+
+    let output = harness.run("cursor");
+
+    assert_eq!(progress(&output, "Plan usage"), (5.0, 20.0));
+    let stored: String = rusqlite::Connection::open(&db_path)
+        .expect("open state db")
+        .query_row(
+            "SELECT value FROM ItemTable WHERE key = 'cursorAuth/accessToken'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("stored token");
+    assert_eq!(stored, fresh);
+}
+
+#[test]
+fn cursor_surfaces_rate_limit_and_malformed_json() {
+    let harness = cursor_harness("cursor-429");
+    harness
+        .server
+        .route("POST", CURSOR_USAGE, vec![FakeResponse::json(429, "{}")]);
+    assert_eq!(
+        error_text(&harness.run("cursor")),
+        Some("Usage request failed (HTTP 429). Try again later.")
+    );
+
+    let harness = cursor_harness("cursor-malformed");
+    harness
+        .server
+        .route("POST", CURSOR_USAGE, vec![FakeResponse::json(200, "<html>")]);
+    assert_eq!(
+        error_text(&harness.run("cursor")),
+        Some("Usage response invalid. Try again later.")
+    );
+}
+
+// --- Declarative ------------------------------------------------------------
+
+const GATEWAY_USAGE: &str = "https://llm.example.internal/v1/usage";
+
+/// Writes a manifest-only plugin next to the harness dirs and loads it.
+fn declarative_plugin(harness: &Harness) -> LoadedPlugin {
+    let dir = harness.root.join("plugins").join("gateway");
+    std::fs::create_dir_all(&dir).expect("plugin dir");
+    std::fs::write(dir.join("icon.svg"), "<svg/>").expect("icon");
+    std::fs::write(
+        dir.join("plugin.json"),
+        r#"{
+          "schemaVersion": 1,
+          "id": "gateway",
+          "name": "LLM Gateway",
+          "version": "0.0.1",
+          "icon": "icon.svg",
+          "lines": [{ "type": "progress", "label": "Budget", "scope": "overview" }],
+          "permissions": { "env": ["GATEWAY_ORG"] },
+          "settings": [{ "key": "apiKey", "label": "API key", "type": "secret", "required": true }],
+          "declarative": {
+            "requests": [{
+              "name": "usage",
+              "url": "https://llm.example.internal/v1/usage",
+              "headers": { "Authorization": "Bearer {{config.apiKey}}", "X-Org": "{{env.GATEWAY_ORG}}" }
+            }],
+            "plan": "$.usage.plan",
+            "lines": [{ "type": "progress", "label": "Budget", "used": "$.usage.spent", "limit": "$.usage.budget",
+                        "format": { "kind": "dollars" } }]
+          }
+        }"#,
+    )
+    .expect("manifest");
+    manifest::load_plugins_from_dir(&harness.root.join("plugins"))
+        .into_iter()
+        .next()
+        .expect("declarative plugin loads")
+}
+
+#[test]
+fn declarative_plugin_maps_response_without_js() {
+    let mut harness = Harness::new("declarative");
+    harness.env.insert("GATEWAY_ORG".to_string(), "platform".to_string());
+    harness
+        .keychain
+        .insert("openusage:gateway:setting:apiKey".to_string(), "gw-key".to_string());
+    harness.server.route(
+        "GET",
+        GATEWAY_USAGE,
+        vec![FakeResponse::json(200, r#"{"plan": "Team", "spent": 42.5, "budget": 100}"#)],
+    );
+
+    let plugin = declarative_plugin(&harness);
+    let output = harness.run_plugin(&plugin);
+
+    assert_eq!(output.plan.as_deref(), Some("Team"));
+    assert_eq!(progress(&output, "Budget"), (42.5, 100.0));
+    let requests = harness.server.requests_to(GATEWAY_USAGE);
+    assert_eq!(requests[0].header("authorization"), Some("Bearer gw-key"));
+    assert_eq!(requests[0].header("x-org"), Some("platform"));
+}
+
+#[test]
+fn declarative_plugin_reports_auth_failure() {
+    let mut harness = Harness::new("declarative-401");
+    harness.env.insert("GATEWAY_ORG".to_string(), "platform".to_string());
+    harness
+        .keychain
+        .insert("openusage:gateway:setting:apiKey".to_string(), "stale".to_string());
+    harness
+        .server
+        .route("GET", GATEWAY_USAGE, vec![FakeResponse::json(401, "{}")]);
+
+    let plugin = declarative_plugin(&harness);
+    assert_eq!(
+        error_text(&harness.run_plugin(&plugin)),
+        Some("Authentication failed (HTTP 401). Check the plugin's settings.")
+    );
+}
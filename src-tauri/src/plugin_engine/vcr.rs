@@ -0,0 +1,320 @@
+use crate::plugin_engine::host_api::{redact_body, redact_url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+#[cfg(any(test, debug_assertions))]
+const VCR_MODE_ENV: &str = "OPENUSAGE_HTTP_VCR";
+#[cfg(any(test, debug_assertions))]
+const VCR_DIR_ENV: &str = "OPENUSAGE_HTTP_VCR_DIR";
+
+/// Request headers whose values never make it into a fixture.
+const SENSITIVE_HEADERS: [&str; 6] = [
+    "authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "proxy-authorization",
+    "x-goog-api-key",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VcrMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+impl VcrMode {
+    /// `OPENUSAGE_HTTP_VCR=record|replay` plus `OPENUSAGE_HTTP_VCR_DIR=<dir>`.
+    /// Debug builds only: a release build must never write real traffic to
+    /// disk or answer probes from a cassette.
+    #[cfg(any(test, debug_assertions))]
+    pub fn from_env() -> Option<Self> {
+        let mode = std::env::var(VCR_MODE_ENV).ok()?;
+        let dir = PathBuf::from(std::env::var(VCR_DIR_ENV).ok()?);
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "record" => Some(VcrMode::Record(dir)),
+            "replay" => Some(VcrMode::Replay(dir)),
+            other => {
+                log::warn!("ignoring unknown {} value '{}'", VCR_MODE_ENV, other);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VcrRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VcrResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body_text: String,
+}
+
+/// One request and its outcome: a response, or the network error it raised.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VcrInteraction {
+    pub request: VcrRequest,
+    pub response: Option<VcrResponse>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<VcrInteraction>,
+}
+
+struct VcrState {
+    path: PathBuf,
+    record: bool,
+    interactions: Vec<VcrInteraction>,
+    used: Vec<bool>,
+}
+
+/// Recording state per cassette path, shared by every probe in this process.
+static RECORDINGS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<VcrState>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Record/replay ("VCR") handle for `host.http`, one per probe.
+///
+/// In record mode every request a plugin makes is written, redacted, to
+/// `{dir}/{pluginId}.json`. In replay mode those fixtures are served instead
+/// of touching the network, so bundled plugins can run end-to-end in tests.
+///
+/// Each request is one interaction holding its final outcome: when `host.http`
+/// retries, only the last attempt is recorded, and replay serves it without
+/// retrying.
+#[derive(Clone)]
+pub struct HttpVcr {
+    state: Arc<Mutex<VcrState>>,
+}
+
+impl HttpVcr {
+    /// Record mode starts a fresh cassette the first time a plugin probes in
+    /// this process, and later probes append to it. Replay mode loads the
+    /// existing one (a missing cassette replays as empty, so every request
+    /// fails loudly).
+    pub fn new(mode: &VcrMode, plugin_id: &str) -> Self {
+        let (dir, record) = match mode {
+            VcrMode::Record(dir) => (dir, true),
+            VcrMode::Replay(dir) => (dir, false),
+        };
+        let path = cassette_path(dir, plugin_id);
+        let fresh = |path: PathBuf, interactions: Vec<VcrInteraction>| {
+            let used = vec![false; interactions.len()];
+            Arc::new(Mutex::new(VcrState {
+                path,
+                record,
+                interactions,
+                used,
+            }))
+        };
+        if !record {
+            let interactions = load_cassette(&path);
+            return Self {
+                state: fresh(path, interactions),
+            };
+        }
+        let mut recordings = RECORDINGS.lock().unwrap_or_else(|e| e.into_inner());
+        let state = recordings
+            .entry(path.clone())
+            .or_insert_with(|| fresh(path, Vec::new()));
+        Self {
+            state: Arc::clone(state),
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.state.lock().map(|s| !s.record).unwrap_or(false)
+    }
+
+    /// Serve the first unused interaction matching method and (redacted) URL,
+    /// in recording order, so "401 then 200" sequences replay faithfully.
+    pub fn replay(&self, method: &str, url: &str) -> Result<VcrResponse, String> {
+        let mut state = self.state.lock().map_err(|_| "vcr state poisoned".to_string())?;
+        let wanted_url = redact_url(url);
+        let found = state.interactions.iter().enumerate().position(|(idx, it)| {
+            !state.used[idx]
+                && it.request.method.eq_ignore_ascii_case(method)
+                && it.request.url == wanted_url
+        });
+        let Some(idx) = found else {
+            return Err(format!(
+                "vcr: no recorded interaction for {} {}",
+                method, wanted_url
+            ));
+        };
+        state.used[idx] = true;
+        let interaction = &state.interactions[idx];
+        match (&interaction.response, &interaction.error) {
+            (Some(response), _) => Ok(response.clone()),
+            (None, Some(error)) => Err(error.clone()),
+            (None, None) => Err(format!("vcr: empty interaction for {} {}", method, wanted_url)),
+        }
+    }
+
+    /// Append a redacted interaction and rewrite the cassette file.
+    pub fn record(
+        &self,
+        request: VcrRequest,
+        response: Result<&VcrResponse, &str>,
+    ) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let interaction = match response {
+            Ok(response) => VcrInteraction {
+                request: redact_request(request),
+                response: Some(VcrResponse {
+                    status: response.status,
+                    headers: redact_headers(&response.headers),
+                    body_text: redact_body(&response.body_text),
+                }),
+                error: None,
+            },
+            Err(error) => VcrInteraction {
+                request: redact_request(request),
+                response: None,
+                error: Some(error.to_string()),
+            },
+        };
+        state.interactions.push(interaction);
+        state.used.push(true);
+
+        let cassette = Cassette {
+            interactions: state.interactions.clone(),
+        };
+        if let Some(parent) = state.path.parent()
+            && let Err(err) = std::fs::create_dir_all(parent)
+        {
+            log::warn!("vcr dir {} unavailable: {}", parent.display(), err);
+            return;
+        }
+        match serde_json::to_string_pretty(&cassette) {
+            Ok(text) => {
+                if let Err(err) = std::fs::write(&state.path, text) {
+                    log::warn!("vcr write {} failed: {}", state.path.display(), err);
+                }
+            }
+            Err(err) => log::warn!("vcr encode failed: {}", err),
+        }
+    }
+}
+
+pub fn cassette_path(dir: &Path, plugin_id: &str) -> PathBuf {
+    dir.join(format!("{}.json", plugin_id))
+}
+
+fn load_cassette(path: &Path) -> Vec<VcrInteraction> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            log::warn!("vcr cassette {} unreadable: {}", path.display(), err);
+            return Vec::new();
+        }
+    };
+    match serde_json::from_str::<Cassette>(&text) {
+        Ok(cassette) => cassette.interactions,
+        Err(err) => {
+            log::warn!("vcr cassette {} invalid: {}", path.display(), err);
+            Vec::new()
+        }
+    }
+}
+
+fn redact_request(request: VcrRequest) -> VcrRequest {
+    VcrRequest {
+        method: request.method.to_ascii_uppercase(),
+        url: redact_url(&request.url),
+        headers: redact_headers(&request.headers),
+        body_text: request.body_text.as_deref().map(redact_body),
+    }
+}
+
+fn redact_headers(headers: &HashMap<String, String>) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(key, value)| {
+            if SENSITIVE_HEADERS.contains(&key.to_ascii_lowercase().as_str()) {
+                (key.clone(), "[REDACTED]".to_string())
+            } else {
+                (key.clone(), value.clone())
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    fn request(method: &str, url: &str, auth: &str) -> VcrRequest {
+        let mut headers = HashMap::new();
+        headers.insert("Authorization".to_string(), auth.to_string());
+        VcrRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers,
+            body_text: Some(r#"{"refresh_token":"rt-secret-value-123456"}"#.to_string()),
+        }
+    }
+
+    #[test]
+    fn record_redacts_secrets_and_replay_serves_in_order() {
+        let dir = TempDir::new("vcr-roundtrip");
+        let recorder = HttpVcr::new(&VcrMode::Record(dir.to_path_buf()), "demo");
+        let url = "https://api.example.com/usage?key=sk-1234567890abcdef";
+        let unauthorized = VcrResponse {
+            status: 401,
+            headers: HashMap::new(),
+            body_text: String::new(),
+        };
+        let ok = VcrResponse {
+            status: 200,
+            headers: HashMap::new(),
+            body_text: r#"{"used": 5}"#.to_string(),
+        };
+        recorder.record(request("GET", url, "Bearer secret-token"), Ok(&unauthorized));
+        recorder.record(request("GET", url, "Bearer secret-token"), Ok(&ok));
+        // A later probe in the same session appends instead of starting over.
+        let next_probe = HttpVcr::new(&VcrMode::Record(dir.to_path_buf()), "demo");
+        next_probe.record(request("POST", "https://down.example.com", ""), Err("connection refused"));
+
+        let text = std::fs::read_to_string(cassette_path(&dir, "demo")).expect("cassette");
+        assert!(!text.contains("secret-token"));
+        assert!(!text.contains("sk-1234567890abcdef"));
+        assert!(!text.contains("rt-secret-value-123456"));
+
+        let player = HttpVcr::new(&VcrMode::Replay(dir.to_path_buf()), "demo");
+        assert!(player.is_replay());
+        assert_eq!(player.replay("GET", url).expect("first").status, 401);
+        assert_eq!(player.replay("get", url).expect("second").status, 200);
+        assert!(player.replay("GET", url).is_err(), "interactions are consumed");
+        assert_eq!(
+            player.replay("POST", "https://down.example.com").unwrap_err(),
+            "connection refused"
+        );
+    }
+
+    #[test]
+    fn replay_without_cassette_fails_every_request() {
+        let dir = TempDir::new("vcr-missing");
+        let player = HttpVcr::new(&VcrMode::Replay(dir.to_path_buf()), "none");
+        let err = player.replay("GET", "https://example.com").unwrap_err();
+        assert!(err.contains("no recorded interaction"), "got: {}", err);
+    }
+}
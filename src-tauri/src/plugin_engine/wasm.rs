@@ -0,0 +1,423 @@
+//! Runs plugins whose `entry` is a WebAssembly module.
+//!
+//! The module gets no WASI and no other imports than `openusage.host_call`,
+//! which dispatches to the same host functions as `ctx.host` and enforces the
+//! same manifest permissions. Values cross the boundary as UTF-8 JSON in guest
+//! memory, allocated with the module's own `openusage_alloc`. CPU is bounded
+//! with fuel and memory with a store limit, so a runaway module fails its
+//! probe instead of hanging the app.
+
+use crate::plugin_engine::credential_store::{CredentialStore, PluginKeychain};
+use crate::plugin_engine::fs_atomic::{self, WriteOpts};
+use crate::plugin_engine::fs_read;
+use crate::plugin_engine::host_api::{self, HostOptions, HttpHost};
+use crate::plugin_engine::manifest::PluginPermissions;
+use crate::plugin_engine::vcr::HttpVcr;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wasmi::core::TrapCode;
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+/// Roughly one unit per instruction; host calls (HTTP waits, file reads) are free.
+const FUEL: u64 = 2_000_000_000;
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// What the module can reach through `host_call`.
+struct Host {
+    plugin_id: String,
+    home: Option<PathBuf>,
+    plugin_data_dir: PathBuf,
+    http: HttpHost,
+    credentials: Arc<dyn CredentialStore>,
+    sqlite_write: Vec<String>,
+    env: Vec<String>,
+    env_overrides: Option<HashMap<String, String>>,
+    limits: StoreLimits,
+}
+
+/// Instantiates `module`, calls `openusage_probe` with the probe context and
+/// returns the JSON it produced, which has the shape of a script plugin's
+/// `probe()` result.
+pub fn probe(
+    module: &[u8],
+    plugin_id: &str,
+    permissions: &PluginPermissions,
+    config: &Map<String, Value>,
+    app_data_dir: &Path,
+    app_version: &str,
+    options: &HostOptions,
+) -> Result<String, String> {
+    let plugin_data_dir = app_data_dir.join("plugins_data").join(plugin_id);
+    std::fs::create_dir_all(&plugin_data_dir).map_err(|e| e.to_string())?;
+
+    let mut engine_config = Config::default();
+    engine_config.consume_fuel(true);
+    let engine = Engine::new(&engine_config);
+    let module = Module::new(&engine, module).map_err(|e| format!("invalid wasm module: {}", e))?;
+
+    let vcr = options
+        .vcr
+        .as_ref()
+        .map(|mode| HttpVcr::new(mode, plugin_id));
+    let host = Host {
+        plugin_id: plugin_id.to_string(),
+        home: options.home_dir.clone(),
+        plugin_data_dir: plugin_data_dir.clone(),
+        http: HttpHost::new(
+            plugin_id,
+            &plugin_data_dir,
+            vcr,
+            options.url_overrides.clone(),
+        ),
+        credentials: Arc::new(PluginKeychain(options.credential_store(app_data_dir))),
+        sqlite_write: permissions.sqlite_write.clone(),
+        env: permissions.env.clone(),
+        env_overrides: options.env.clone(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .build(),
+    };
+    let mut store = Store::new(&engine, host);
+    store.limiter(|host| &mut host.limits);
+    store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+
+    let mut linker = <Linker<Host>>::new(&engine);
+    linker
+        .func_wrap("openusage", "host_call", host_call)
+        .map_err(|e| e.to_string())?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(|e| format!("wasm instantiation failed: {}", e))?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or("wasm module must export `memory`")?;
+    let probe = instance
+        .get_typed_func::<(i32, i32), i64>(&store, "openusage_probe")
+        .map_err(|_| "wasm module must export `openusage_probe(ptr, len) -> i64`")?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&store, "openusage_alloc")
+        .map_err(|_| "wasm module must export `openusage_alloc(len) -> ptr`")?;
+
+    let probe_ctx = json!({
+        "nowIso": host_api::iso_now(),
+        "app": {
+            "version": app_version,
+            "platform": std::env::consts::OS,
+            "appDataDir": app_data_dir.to_string_lossy(),
+            "pluginDataDir": plugin_data_dir.to_string_lossy(),
+        },
+        "config": config,
+    });
+    let (ptr, len) = write_guest(&mut store, memory, alloc, probe_ctx.to_string().as_bytes())
+        .map_err(trap_message)?;
+    let packed = probe.call(&mut store, (ptr, len)).map_err(trap_message)?;
+    read_guest(&store, memory, packed).map_err(trap_message)
+}
+
+/// `host_call(name_ptr, name_len, args_ptr, args_len) -> i64`. `args` is a
+/// JSON array of the arguments the `ctx.host` function takes; the reply is
+/// `{"ok": value}` or `{"error": message}`, returned as a packed pointer.
+fn host_call(
+    mut caller: Caller<'_, Host>,
+    name_ptr: i32,
+    name_len: i32,
+    args_ptr: i32,
+    args_len: i32,
+) -> Result<i64, wasmi::Error> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("wasm module must export `memory`"))?;
+    let name = read_guest(&caller, memory, pack(name_ptr, name_len))?;
+    let args = read_guest(&caller, memory, pack(args_ptr, args_len))?;
+    let reply = match serde_json::from_str::<Vec<Value>>(&args) {
+        Ok(args) => match caller.data().call(&name, &args) {
+            Ok(value) => json!({ "ok": value }),
+            Err(message) => json!({ "error": message }),
+        },
+        Err(e) => json!({ "error": format!("invalid arguments for {}: {}", name, e) }),
+    };
+    let alloc = caller
+        .get_export("openusage_alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmi::Error::new("wasm module must export `openusage_alloc`"))?
+        .typed::<i32, i32>(&caller)?;
+    let (ptr, len) = write_guest(&mut caller, memory, alloc, reply.to_string().as_bytes())?;
+    Ok(pack(ptr, len))
+}
+
+impl Host {
+    fn call(&self, name: &str, args: &[Value]) -> Result<Value, String> {
+        let home = self.home.as_deref();
+        let path = || str_arg(args, 0, name).map(|path| host_api::expand_path(path, home));
+        match name {
+            "log.info" | "log.warn" | "log.error" => {
+                let msg = host_api::redact_log_message(str_arg(args, 0, name)?);
+                match name {
+                    "log.info" => log::info!("[plugin:{}] {}", self.plugin_id, msg),
+                    "log.warn" => log::warn!("[plugin:{}] {}", self.plugin_id, msg),
+                    _ => log::error!("[plugin:{}] {}", self.plugin_id, msg),
+                }
+                Ok(Value::Null)
+            }
+            "env.get" => Ok(host_api::read_env(
+                str_arg(args, 0, name)?,
+                &self.env,
+                self.env_overrides.as_ref(),
+            )
+            .map_or(Value::Null, Value::String)),
+            "fs.exists" => Ok(Value::Bool(Path::new(&path()?).exists())),
+            "fs.readText" => std::fs::read_to_string(path()?)
+                .map(Value::String)
+                .map_err(|e| e.to_string()),
+            "fs.readBytes" => fs_read::read_bytes(Path::new(&path()?)).map(Value::String),
+            "fs.listDir" => fs_read::list_dir(Path::new(&path()?)).map(|names| json!(names)),
+            "fs.glob" => fs_read::glob(&path()?).map(|paths| json!(paths)),
+            "fs.stat" => fs_read::stat(Path::new(&path()?)).map(|stat| {
+                json!({ "size": stat.size, "mtimeMs": stat.mtime_ms, "mode": stat.mode, "isDir": stat.is_dir })
+            }),
+            "fs.writeText" => {
+                let content = str_arg(args, 1, name)?;
+                let opts: WriteOpts = match args.get(2).filter(|opts| !opts.is_null()) {
+                    Some(opts) => serde_json::from_value(opts.clone())
+                        .map_err(|e| format!("invalid writeText opts: {}", e))?,
+                    None => WriteOpts::default(),
+                };
+                fs_atomic::write(
+                    Path::new(&path()?),
+                    content.as_bytes(),
+                    opts,
+                    &self.plugin_data_dir,
+                )
+                .map(|_| Value::Null)
+            }
+            "http.request" => {
+                let mut req = args.first().cloned().unwrap_or(Value::Null);
+                // Like the JS wrapper, `cache: true` means "cache with the server's TTL".
+                if let Some(cache) = req
+                    .get_mut("cache")
+                    .filter(|cache| cache.as_bool() == Some(true))
+                {
+                    *cache = json!({});
+                }
+                let resp = self.http.request(&req.to_string())?;
+                serde_json::from_str(&resp).map_err(|e| e.to_string())
+            }
+            "sqlite.query" => host_api::sqlite_query(
+                str_arg(args, 0, name)?,
+                str_arg(args, 1, name)?,
+                sql_params(args)?,
+                home,
+            )
+            .map(Value::Array),
+            "sqlite.exec" => host_api::sqlite_exec(
+                str_arg(args, 0, name)?,
+                str_arg(args, 1, name)?,
+                sql_params(args)?,
+                home,
+                &self.sqlite_write,
+            )
+            .map(|_| Value::Null),
+            "keychain.readGenericPassword" => self
+                .credentials
+                .read(str_arg(args, 0, name)?)
+                .map(Value::String)
+                .map_err(String::from),
+            "keychain.writeGenericPassword" => self
+                .credentials
+                .write(str_arg(args, 0, name)?, str_arg(args, 1, name)?)
+                .map(|_| Value::Null)
+                .map_err(String::from),
+            "keychain.deleteGenericPassword" => self
+                .credentials
+                .delete(str_arg(args, 0, name)?)
+                .map(|_| Value::Null)
+                .map_err(String::from),
+            "keychain.listGenericPasswords" => self
+                .credentials
+                .list(str_arg(args, 0, name)?)
+                .map(|items| json!(items))
+                .map_err(String::from),
+            _ => Err(format!("unknown host function: {}", name)),
+        }
+    }
+}
+
+fn str_arg<'a>(args: &'a [Value], index: usize, name: &str) -> Result<&'a str, String> {
+    args.get(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("{}: argument {} must be a string", name, index + 1))
+}
+
+/// The optional third `sqlite.*` argument; `null` means no params.
+fn sql_params(args: &[Value]) -> Result<Option<&Value>, String> {
+    match args.get(2) {
+        None | Some(Value::Null) => Ok(None),
+        Some(params @ (Value::Array(_) | Value::Object(_))) => Ok(Some(params)),
+        Some(_) => Err("sqlite params must be an array or an object".to_string()),
+    }
+}
+
+fn pack(ptr: i32, len: i32) -> i64 {
+    (((ptr as u32 as u64) << 32) | len as u32 as u64) as i64
+}
+
+fn read_guest<T>(
+    store: impl wasmi::AsContext<Data = T>,
+    memory: Memory,
+    packed: i64,
+) -> Result<String, wasmi::Error> {
+    let ptr = (packed as u64 >> 32) as usize;
+    let len = (packed as u64 & 0xffff_ffff) as usize;
+    let bytes = memory
+        .data(&store)
+        .get(ptr..ptr.saturating_add(len))
+        .ok_or_else(|| wasmi::Error::new("wasm module returned an out-of-bounds string"))?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| wasmi::Error::new("wasm module returned invalid UTF-8"))
+}
+
+/// Copies `bytes` into memory from the module's `openusage_alloc(len) -> ptr`.
+fn write_guest<T>(
+    mut store: impl wasmi::AsContextMut<Data = T>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> Result<(i32, i32), wasmi::Error> {
+    let len =
+        i32::try_from(bytes.len()).map_err(|_| wasmi::Error::new("value too large for wasm"))?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory
+        .write(&mut store, ptr as u32 as usize, bytes)
+        .map_err(|_| wasmi::Error::new("openusage_alloc returned an out-of-bounds pointer"))?;
+    Ok((ptr, len))
+}
+
+/// Turns a trap into the message shown on the provider card.
+fn trap_message(err: wasmi::Error) -> String {
+    match err.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => "The plugin ran too long and was stopped.".to_string(),
+        Some(code) => format!("wasm trap: {}", code),
+        None => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::test_util::TempDir;
+
+    /// A guest that makes one host call, then returns `result`, or the host's
+    /// reply when `result` is `None`.
+    fn guest(name: &str, args: &str, result: Option<&str>) -> Vec<u8> {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let body = match result {
+            Some(result) => format!(
+                "(drop (call $host_call (i32.const 0) (i32.const {name_len}) (i32.const 256) (i32.const {args_len}))) (i64.const {packed})",
+                name_len = name.len(),
+                args_len = args.len(),
+                packed = pack(1024, result.len() as i32)
+            ),
+            None => format!(
+                "(call $host_call (i32.const 0) (i32.const {}) (i32.const 256) (i32.const {}))",
+                name.len(),
+                args.len()
+            ),
+        };
+        let text = format!(
+            r#"(module
+                (import "openusage" "host_call" (func $host_call (param i32 i32 i32 i32) (result i64)))
+                (memory (export "memory") 1)
+                (global $heap (mut i32) (i32.const 4096))
+                (func (export "openusage_alloc") (param $len i32) (result i32)
+                  (local $ptr i32)
+                  (local.set $ptr (global.get $heap))
+                  (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+                  (local.get $ptr))
+                (data (i32.const 0) "{}")
+                (data (i32.const 256) "{}")
+                (data (i32.const 1024) "{}")
+                (func (export "openusage_probe") (param i32 i32) (result i64) {}))"#,
+            escape(name),
+            escape(args),
+            escape(result.unwrap_or_default()),
+            body
+        );
+        wat::parse_str(text).expect("wat")
+    }
+
+    fn run(module: &[u8], permissions: &PluginPermissions, root: &Path) -> Result<String, String> {
+        let options = HostOptions {
+            home_dir: Some(root.join("home")),
+            keychain: Some(HashMap::new()),
+            ..HostOptions::default()
+        };
+        probe(
+            module,
+            "demo",
+            permissions,
+            &Map::new(),
+            &root.join("app_data"),
+            "0.0.0",
+            &options,
+        )
+    }
+
+    #[test]
+    fn guest_calls_host_and_returns_lines() {
+        let root = TempDir::new("wasm-probe");
+        std::fs::create_dir_all(root.join("home")).unwrap();
+        let result = r#"{"plan":"Pro","lines":[{"type":"text","label":"Status","value":"ok"}]}"#;
+        let module = guest("fs.writeText", r#"["~/out.txt","hello"]"#, Some(result));
+
+        assert_eq!(
+            run(&module, &PluginPermissions::default(), &root).unwrap(),
+            result
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("home/out.txt")).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn host_calls_keep_manifest_permissions() {
+        let root = TempDir::new("wasm-permissions");
+        let module = guest("sqlite.exec", r#"["~/state.db","DELETE FROM t"]"#, None);
+        assert_eq!(
+            run(&module, &PluginPermissions::default(), &root).unwrap(),
+            r#"{"error":"sqlite write not allowed by plugin manifest: ~/state.db"}"#
+        );
+
+        let module = guest("env.get", r#"["HOME"]"#, None);
+        assert_eq!(
+            run(&module, &PluginPermissions::default(), &root).unwrap(),
+            r#"{"ok":null}"#
+        );
+
+        let module = guest(
+            "keychain.readGenericPassword",
+            r#"["openusage:other:setting:apiKey"]"#,
+            None,
+        );
+        assert_eq!(
+            run(&module, &PluginPermissions::default(), &root).unwrap(),
+            r#"{"error":"keychain service names starting with \"openusage:\" are reserved"}"#
+        );
+
+        let module = guest("process.exit", "[]", None);
+        assert_eq!(
+            run(&module, &PluginPermissions::default(), &root).unwrap(),
+            r#"{"error":"unknown host function: process.exit"}"#
+        );
+
+        let err = run(b"not wasm", &PluginPermissions::default(), &root).unwrap_err();
+        assert!(err.starts_with("invalid wasm module"), "{}", err);
+    }
+}
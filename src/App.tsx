@@ -10,7 +10,7 @@ import { PanelFooter } from "@/components/panel-footer"
 import { OverviewPage } from "@/pages/overview"
 import { ProviderDetailPage } from "@/pages/provider-detail"
 import { SettingsPage } from "@/pages/settings"
-import type { PluginMeta, PluginOutput } from "@/lib/plugin-types"
+import type { PluginLoginPrompt, PluginMeta, PluginOutput, PluginSettingsView } from "@/lib/plugin-types"
 import { getTrayIconSizePx, renderTrayBarsIcon } from "@/lib/tray-bars-icon"
 import { getTrayPrimaryBars } from "@/lib/tray-primary-progress"
 import { useProbeEvents } from "@/hooks/use-probe-events"
@@ -86,6 +86,8 @@ function App() {
   const [canScrollDown, setCanScrollDown] = useState(false);
   const [pluginStates, setPluginStates] = useState<Record<string, PluginState>>({})
   const [pluginsMeta, setPluginsMeta] = useState<PluginMeta[]>([])
+  const [loginStatus, setLoginStatus] = useState<Record<string, string>>({})
+  const [pluginConfigView, setPluginConfigView] = useState<PluginSettingsView | null>(null)
   const [pluginSettings, setPluginSettings] = useState<PluginSettings | null>(null)
   const [autoUpdateInterval, setAutoUpdateInterval] = useState<AutoUpdateIntervalMinutes>(
     DEFAULT_AUTO_UPDATE_INTERVAL
@@ -312,6 +314,24 @@ function App() {
     return displayPlugins.find((p) => p.meta.id === activeView) ?? null
   }, [activeView, displayPlugins])
 
+  const selectedPluginId = selectedPlugin?.meta.id ?? null
+  const selectedHasSettings = (selectedPlugin?.meta.settings?.length ?? 0) > 0
+
+  // Load the selected plugin's user configuration for its settings form
+  useEffect(() => {
+    setPluginConfigView(null)
+    if (!selectedPluginId || !selectedHasSettings) return
+    let cancelled = false
+    invoke<PluginSettingsView>("get_plugin_settings", { pluginId: selectedPluginId })
+      .then((view) => {
+        if (!cancelled) setPluginConfigView(view)
+      })
+      .catch((error) => console.error("Failed to load plugin settings:", error))
+    return () => {
+      cancelled = true
+    }
+  }, [selectedPluginId, selectedHasSettings])
+
 
   // Initialize panel on mount
   useEffect(() => {
@@ -365,6 +385,17 @@ function App() {
       })
       if (cancelled) { u2(); return }
       unlisteners.push(u2)
+
+      const u3 = await listen<PluginLoginPrompt>("plugin-login:prompt", (event) => {
+        const { pluginId, prompt } = event.payload
+        const status =
+          prompt.type === "deviceCode"
+            ? `Enter code \`${prompt.userCode}\` at ${prompt.verificationUri}`
+            : "Finish signing in in your browser"
+        setLoginStatus((prev) => ({ ...prev, [pluginId]: status }))
+      })
+      if (cancelled) { u3(); return }
+      unlisteners.push(u3)
     }
     void setup()
 
@@ -686,6 +717,74 @@ function App() {
     [resetAutoUpdateSchedule, setLoadingForPlugins, setErrorForPlugins, startBatch]
   )
 
+  const handleLoginPlugin = useCallback(
+    (id: string) => {
+      setLoginStatus((prev) => ({ ...prev, [id]: "Opening sign-in…" }))
+      invoke("start_plugin_login", { pluginId: id })
+        .then(() => handleRetryPlugin(id))
+        .catch((error) => {
+          console.error("Plugin login failed:", error)
+          setErrorForPlugins([id], `Sign-in failed: ${String(error)}`)
+        })
+        .finally(() => {
+          setLoginStatus((prev) => {
+            const next = { ...prev }
+            delete next[id]
+            return next
+          })
+        })
+    },
+    [handleRetryPlugin, setErrorForPlugins]
+  )
+
+  const handleAddPluginInstance = useCallback(
+    async (pluginId: string, name: string) => {
+      if (!pluginSettings) return
+      const meta = await invoke<PluginMeta>("create_plugin_instance", { pluginId, name })
+      setPluginsMeta((prev) => [...prev, meta])
+      // Place the new account right after the plugin it copies, enabled.
+      const order = pluginSettings.order.filter((id) => id !== meta.id)
+      order.splice(order.indexOf(pluginId) + 1, 0, meta.id)
+      const nextSettings: PluginSettings = { ...pluginSettings, order }
+      setPluginSettings(nextSettings)
+      scheduleTrayIconUpdate("settings", TRAY_SETTINGS_DEBOUNCE_MS)
+      void savePluginSettings(nextSettings).catch((error) => {
+        console.error("Failed to save plugin settings:", error)
+      })
+      setActiveView(meta.id)
+      handleRetryPlugin(meta.id)
+    },
+    [pluginSettings, scheduleTrayIconUpdate, handleRetryPlugin]
+  )
+
+  const handleRemovePluginInstance = useCallback(
+    async (id: string) => {
+      if (!pluginSettings) return
+      await invoke("remove_plugin_instance", { pluginId: id })
+      setActiveView("home")
+      setPluginsMeta((prev) => prev.filter((meta) => meta.id !== id))
+      const nextSettings: PluginSettings = {
+        order: pluginSettings.order.filter((entry) => entry !== id),
+        disabled: pluginSettings.disabled.filter((entry) => entry !== id),
+      }
+      setPluginSettings(nextSettings)
+      scheduleTrayIconUpdate("settings", TRAY_SETTINGS_DEBOUNCE_MS)
+      void savePluginSettings(nextSettings).catch((error) => {
+        console.error("Failed to save plugin settings:", error)
+      })
+    },
+    [pluginSettings, scheduleTrayIconUpdate]
+  )
+
+  const handleSavePluginConfig = useCallback(
+    async (id: string, changes: Record<string, string>) => {
+      await invoke("set_plugin_settings", { pluginId: id, values: changes })
+      setPluginConfigView(await invoke<PluginSettingsView>("get_plugin_settings", { pluginId: id }))
+      handleRetryPlugin(id)
+    },
+    [handleRetryPlugin]
+  )
+
   const handleThemeModeChange = useCallback((mode: ThemeMode) => {
     setThemeMode(mode)
     void saveThemeMode(mode).catch((error) => {
@@ -846,6 +945,8 @@ function App() {
         <OverviewPage
           plugins={displayPlugins}
           onRetryPlugin={handleRetryPlugin}
+          onLoginPlugin={handleLoginPlugin}
+          loginStatus={loginStatus}
           displayMode={displayMode}
         />
       )
@@ -880,6 +981,22 @@ function App() {
       <ProviderDetailPage
         plugin={selectedPlugin}
         onRetry={handleRetry}
+        onLogin={selectedPlugin ? () => handleLoginPlugin(selectedPlugin.meta.id) : undefined}
+        loginStatus={selectedPlugin ? loginStatus[selectedPlugin.meta.id] : null}
+        settingsView={pluginConfigView}
+        onSaveSettings={
+          selectedPlugin
+            ? (changes) => handleSavePluginConfig(selectedPlugin.meta.id, changes)
+            : undefined
+        }
+        onAddAccount={
+          selectedPlugin
+            ? (name) => handleAddPluginInstance(selectedPlugin.meta.id, name)
+            : undefined
+        }
+        onRemoveAccount={
+          selectedPlugin ? () => handleRemovePluginInstance(selectedPlugin.meta.id) : undefined
+        }
         displayMode={displayMode}
       />
     )
@@ -0,0 +1,34 @@
+import { render, screen } from "@testing-library/react"
+import userEvent from "@testing-library/user-event"
+import { describe, expect, it, vi } from "vitest"
+import { PluginAccounts } from "@/components/plugin-accounts"
+
+describe("PluginAccounts", () => {
+  it("adds an account with the entered name", async () => {
+    const onAdd = vi.fn().mockResolvedValue(undefined)
+    render(<PluginAccounts onAdd={onAdd} />)
+    await userEvent.click(screen.getByRole("button", { name: "Add account" }))
+    await userEvent.type(screen.getByLabelText("Account name"), " Work ")
+    await userEvent.click(screen.getByRole("button", { name: "Add" }))
+    expect(onAdd).toHaveBeenCalledWith("Work")
+    expect(await screen.findByRole("button", { name: "Add account" })).toBeInTheDocument()
+  })
+
+  it("asks before removing an account", async () => {
+    const onRemove = vi.fn().mockResolvedValue(undefined)
+    render(<PluginAccounts onRemove={onRemove} />)
+    await userEvent.click(screen.getByRole("button", { name: "Remove account" }))
+    expect(onRemove).not.toHaveBeenCalled()
+    await userEvent.click(screen.getByRole("button", { name: "Remove" }))
+    expect(onRemove).toHaveBeenCalledTimes(1)
+  })
+
+  it("shows errors from the backend", async () => {
+    const onAdd = vi.fn().mockRejectedValue("account name cannot be empty")
+    render(<PluginAccounts onAdd={onAdd} />)
+    await userEvent.click(screen.getByRole("button", { name: "Add account" }))
+    await userEvent.type(screen.getByLabelText("Account name"), "x")
+    await userEvent.click(screen.getByRole("button", { name: "Add" }))
+    expect(await screen.findByText("account name cannot be empty")).toBeInTheDocument()
+  })
+})
@@ -0,0 +1,85 @@
+import { useState, type FormEvent } from "react"
+import { Button } from "@/components/ui/button"
+
+type PluginAccountsProps = {
+  /** Adds another account of this plugin. Omitted on added accounts. */
+  onAdd?: (name: string) => Promise<void>
+  /** Removes this added account. Omitted on the original plugin. */
+  onRemove?: () => Promise<void>
+}
+
+const INPUT_CLASS =
+  "h-6 flex-1 rounded-md border bg-transparent px-2 text-xs outline-none focus-visible:ring-[3px] focus-visible:ring-ring/50"
+
+export function PluginAccounts({ onAdd, onRemove }: PluginAccountsProps) {
+  const [adding, setAdding] = useState(false)
+  const [confirming, setConfirming] = useState(false)
+  const [name, setName] = useState("")
+  const [busy, setBusy] = useState(false)
+  const [error, setError] = useState<string | null>(null)
+
+  const run = (action: () => Promise<void>) => {
+    setBusy(true)
+    setError(null)
+    action()
+      .then(() => {
+        setAdding(false)
+        setName("")
+      })
+      .catch((err) => setError(String(err)))
+      .finally(() => {
+        setBusy(false)
+        setConfirming(false)
+      })
+  }
+
+  const handleAdd = (event: FormEvent) => {
+    event.preventDefault()
+    if (onAdd && name.trim()) run(() => onAdd(name.trim()))
+  }
+
+  return (
+    <div className="space-y-1">
+      {onRemove &&
+        (confirming ? (
+          <div className="flex items-center gap-2">
+            <span className="text-xs text-muted-foreground">Remove this account and its settings?</span>
+            <Button variant="destructive" size="xs" disabled={busy} onClick={() => run(onRemove)}>
+              Remove
+            </Button>
+            <Button variant="ghost" size="xs" onClick={() => setConfirming(false)}>
+              Cancel
+            </Button>
+          </div>
+        ) : (
+          <Button variant="outline" size="xs" onClick={() => setConfirming(true)}>
+            Remove account
+          </Button>
+        ))}
+      {onAdd &&
+        (adding ? (
+          <form onSubmit={handleAdd} className="flex items-center gap-2">
+            <input
+              aria-label="Account name"
+              className={INPUT_CLASS}
+              placeholder="e.g. Work"
+              value={name}
+              autoFocus
+              onChange={(event) => setName(event.target.value)}
+            />
+            <Button type="submit" size="xs" disabled={busy || !name.trim()}>
+              Add
+            </Button>
+            <Button type="button" variant="ghost" size="xs" onClick={() => setAdding(false)}>
+              Cancel
+            </Button>
+          </form>
+        ) : (
+          <Button variant="outline" size="xs" onClick={() => setAdding(true)}>
+            Add account
+          </Button>
+        ))}
+      {error && <p className="text-xs text-destructive">{error}</p>}
+    </div>
+  )
+}
@@ -1,5 +1,6 @@
 import { render, screen } from "@testing-library/react"
-import { describe, expect, it } from "vitest"
+import userEvent from "@testing-library/user-event"
+import { describe, expect, it, vi } from "vitest"
 import { PluginError } from "@/components/plugin-error"
 
 describe("PluginError", () => {
@@ -12,4 +13,17 @@ describe("PluginError", () => {
     render(<PluginError message="Check `config.json` file" />)
     expect(screen.getByText("config.json")).toBeInTheDocument()
   })
+
+  it("offers sign in when a login handler is given", async () => {
+    const onLogin = vi.fn()
+    render(<PluginError message="Not logged in" onLogin={onLogin} />)
+    await userEvent.click(screen.getByRole("button", { name: "Sign in" }))
+    expect(onLogin).toHaveBeenCalledTimes(1)
+  })
+
+  it("shows login progress instead of the button", () => {
+    render(<PluginError message="Not logged in" onLogin={vi.fn()} loginStatus="Enter code `ABCD-1234`" />)
+    expect(screen.queryByRole("button", { name: "Sign in" })).not.toBeInTheDocument()
+    expect(screen.getByText("ABCD-1234")).toBeInTheDocument()
+  })
 })
@@ -1,8 +1,13 @@
 import { AlertCircle } from "lucide-react"
 import { Alert, AlertDescription } from "@/components/ui/alert"
+import { Button } from "@/components/ui/button"
 
 type PluginErrorProps = {
   message: string
+  /** Offers an in-app sign-in when the plugin declares one. */
+  onLogin?: () => void
+  /** Shown instead of the sign-in button while a login is running. */
+  loginStatus?: string | null
 }
 
 function formatMessage(message: string) {
@@ -21,14 +26,25 @@ function formatMessage(message: string) {
   )
 }
 
-export function PluginError({ message }: PluginErrorProps) {
+export function PluginError({ message, onLogin, loginStatus }: PluginErrorProps) {
   return (
     <Alert
       variant="destructive"
       className="flex items-center gap-2 [&>svg]:static [&>svg]:translate-y-0 [&>svg~*]:pl-0 [&>svg+div]:translate-y-0"
     >
       <AlertCircle className="h-4 w-4" />
-      <AlertDescription>{formatMessage(message)}</AlertDescription>
+      <AlertDescription>
+        {formatMessage(message)}
+        {loginStatus ? (
+          <p className="mt-1 text-muted-foreground">{formatMessage(loginStatus)}</p>
+        ) : (
+          onLogin && (
+            <Button variant="outline" size="xs" className="mt-1" onClick={onLogin}>
+              Sign in
+            </Button>
+          )
+        )}
+      </AlertDescription>
     </Alert>
   )
 }
@@ -0,0 +1,47 @@
+import { render, screen } from "@testing-library/react"
+import userEvent from "@testing-library/user-event"
+import { describe, expect, it, vi } from "vitest"
+import { PluginSettingsForm } from "@/components/plugin-settings-form"
+import type { SettingField } from "@/lib/plugin-types"
+
+const fields: SettingField[] = [
+  { key: "apiKey", label: "API key", type: "secret", required: true },
+  { key: "org", label: "Organization", type: "string", description: "Leave empty for personal" },
+  { key: "region", label: "Region", type: "enum", options: ["us", "eu"], default: "us", required: true },
+]
+
+describe("PluginSettingsForm", () => {
+  it("shows stored values and defaults without revealing secrets", () => {
+    render(
+      <PluginSettingsForm
+        fields={fields}
+        view={{ values: { org: "acme" }, secretsSet: ["apiKey"] }}
+        onSave={vi.fn()}
+      />
+    )
+    expect(screen.getByLabelText(/API key/)).toHaveValue("")
+    expect(screen.getByLabelText(/API key/)).toHaveAttribute("placeholder", "Saved — type to replace")
+    expect(screen.getByLabelText("Organization")).toHaveValue("acme")
+    expect(screen.getByLabelText(/Region/)).toHaveValue("us")
+    expect(screen.getByText("Leave empty for personal")).toBeInTheDocument()
+    expect(screen.getByRole("button", { name: "Save" })).toBeDisabled()
+  })
+
+  it("saves only edited fields", async () => {
+    const onSave = vi.fn().mockResolvedValue(undefined)
+    render(<PluginSettingsForm fields={fields} view={{ values: {}, secretsSet: [] }} onSave={onSave} />)
+    await userEvent.type(screen.getByLabelText(/API key/), "sk-123")
+    await userEvent.selectOptions(screen.getByLabelText(/Region/), "eu")
+    await userEvent.click(screen.getByRole("button", { name: "Save" }))
+    expect(onSave).toHaveBeenCalledWith({ apiKey: "sk-123", region: "eu" })
+    expect(await screen.findByText("Saved")).toBeInTheDocument()
+  })
+
+  it("shows the error when saving fails", async () => {
+    const onSave = vi.fn().mockRejectedValue("API key has an invalid format")
+    render(<PluginSettingsForm fields={fields} view={null} onSave={onSave} />)
+    await userEvent.type(screen.getByLabelText(/API key/), "nope")
+    await userEvent.click(screen.getByRole("button", { name: "Save" }))
+    expect(await screen.findByText("API key has an invalid format")).toBeInTheDocument()
+  })
+})
@@ -0,0 +1,104 @@
+import { useEffect, useState, type FormEvent } from "react"
+import { Button } from "@/components/ui/button"
+import type { PluginSettingsView, SettingField } from "@/lib/plugin-types"
+
+type PluginSettingsFormProps = {
+  fields: SettingField[]
+  view: PluginSettingsView | null
+  /** Receives only the edited keys; `""` clears a value. Rejects with the backend's error. */
+  onSave: (changes: Record<string, string>) => Promise<void>
+}
+
+const INPUT_CLASS =
+  "h-7 w-full rounded-md border bg-transparent px-2 text-xs outline-none focus-visible:ring-[3px] focus-visible:ring-ring/50"
+
+function initialValue(field: SettingField, view: PluginSettingsView | null) {
+  if (field.type === "secret") return ""
+  const value = view?.values[field.key] ?? field.default
+  return value === undefined ? "" : String(value)
+}
+
+export function PluginSettingsForm({ fields, view, onSave }: PluginSettingsFormProps) {
+  const [draft, setDraft] = useState<Record<string, string>>({})
+  const [saving, setSaving] = useState(false)
+  const [status, setStatus] = useState<string | null>(null)
+
+  useEffect(() => {
+    setDraft({})
+  }, [view])
+
+  const valueOf = (field: SettingField) => draft[field.key] ?? initialValue(field, view)
+  const dirty = Object.keys(draft).length > 0
+
+  const handleSubmit = (event: FormEvent) => {
+    event.preventDefault()
+    setSaving(true)
+    setStatus(null)
+    // An emptied secret box means "keep the saved one", not "clear it".
+    const changes = Object.fromEntries(
+      Object.entries(draft).filter(
+        ([key, value]) => value !== "" || fields.find((f) => f.key === key)?.type !== "secret"
+      )
+    )
+    onSave(changes)
+      .then(() => setStatus("Saved"))
+      .catch((error) => setStatus(String(error)))
+      .finally(() => setSaving(false))
+  }
+
+  return (
+    <form onSubmit={handleSubmit} className="space-y-2" aria-label="Plugin settings">
+      {fields.map((field) => {
+        const id = `setting-${field.key}`
+        const secretSet = field.type === "secret" && view?.secretsSet.includes(field.key)
+        const onChange = (value: string) =>
+          setDraft((prev) => ({ ...prev, [field.key]: value }))
+        return (
+          <div key={field.key} className="space-y-1">
+            <label htmlFor={id} className="text-xs font-medium">
+              {field.label}
+              {field.required && <span className="text-muted-foreground"> *</span>}
+            </label>
+            {field.type === "enum" ? (
+              <select
+                id={id}
+                className={INPUT_CLASS}
+                value={valueOf(field)}
+                onChange={(event) => onChange(event.target.value)}
+              >
+                {!field.required && <option value="">Not set</option>}
+                {(field.options ?? []).map((option) => (
+                  <option key={option} value={option}>
+                    {option}
+                  </option>
+                ))}
+              </select>
+            ) : (
+              <input
+                id={id}
+                className={INPUT_CLASS}
+                type={field.type === "secret" ? "password" : field.type === "number" ? "number" : "text"}
+                min={field.min}
+                max={field.max}
+                value={valueOf(field)}
+                placeholder={secretSet ? "Saved — type to replace" : undefined}
+                autoComplete="off"
+                spellCheck={false}
+                onChange={(event) => onChange(event.target.value)}
+              />
+            )}
+            {field.description && (
+              <p className="text-xs text-muted-foreground">{field.description}</p>
+            )}
+          </div>
+        )
+      })}
+      <div className="flex items-center gap-2">
+        <Button type="submit" size="xs" disabled={!dirty || saving}>
+          {saving ? "Saving…" : "Save"}
+        </Button>
+        {status && <span className="text-xs text-muted-foreground">{status}</span>}
+      </div>
+    </form>
+  )
+}
@@ -24,6 +24,8 @@ interface ProviderCardProps {
   skeletonLines?: ManifestLine[]
   lastManualRefreshAt?: number | null
   onRetry?: () => void
+  onLogin?: () => void
+  loginStatus?: string | null
   scopeFilter?: "overview" | "all"
   displayMode: DisplayMode
 }
@@ -106,6 +108,8 @@ export function ProviderCard({
   skeletonLines = [],
   lastManualRefreshAt,
   onRetry,
+  onLogin,
+  loginStatus,
   scopeFilter = "all",
   displayMode,
 }: ProviderCardProps) {
@@ -222,7 +226,7 @@ export function ProviderCard({
             </Badge>
           )}
         </div>
-        {error && <PluginError message={error} />}
+        {error && <PluginError message={error} onLogin={onLogin} loginStatus={loginStatus} />}
 
         {loading && !error && (
           <SkeletonLines lines={filteredSkeletonLines} />
@@ -39,6 +39,45 @@ export type PluginMeta = {
   lines: ManifestLine[]
   /** Ordered list of primary metric candidates. Frontend picks first available. */
   primaryCandidates: string[]
+  /** In-app OAuth login declared in the manifest, if any. */
+  login?: "pkce" | "deviceCode" | null
+  /** User-entered configuration the plugin declares; passed to probes as `ctx.config`. */
+  settings?: SettingField[]
+  /** Set on added accounts: id of the plugin this instance copies. */
+  instanceOf?: string | null
+}
+
+export type SettingField = {
+  key: string
+  label: string
+  type: "string" | "secret" | "number" | "enum" | "path"
+  description?: string
+  default?: string | number
+  required?: boolean
+  options?: string[]
+  min?: number
+  max?: number
+  pattern?: string
+}
+
+/** Stored setting values. Secrets are only reported as set, never returned. */
+export type PluginSettingsView = {
+  values: Record<string, string | number>
+  secretsSet: string[]
+}
+
+export type LoginPrompt =
+  | { type: "openBrowser"; url: string }
+  | {
+      type: "deviceCode"
+      userCode: string
+      verificationUri: string
+      verificationUriComplete: string | null
+    }
+
+export type PluginLoginPrompt = {
+  pluginId: string
+  prompt: LoginPrompt
 }
 
 export type PluginDisplayState = {
@@ -5,10 +5,19 @@ import type { DisplayMode } from "@/lib/settings"
 interface OverviewPageProps {
   plugins: PluginDisplayState[]
   onRetryPlugin?: (pluginId: string) => void
+  onLoginPlugin?: (pluginId: string) => void
+  /** Progress text for logins in flight, by plugin id. */
+  loginStatus?: Record<string, string>
   displayMode: DisplayMode
 }
 
-export function OverviewPage({ plugins, onRetryPlugin, displayMode }: OverviewPageProps) {
+export function OverviewPage({
+  plugins,
+  onRetryPlugin,
+  onLoginPlugin,
+  loginStatus,
+  displayMode,
+}: OverviewPageProps) {
   if (plugins.length === 0) {
     return (
       <div className="text-center text-muted-foreground py-8">
@@ -31,6 +40,10 @@ export function OverviewPage({ plugins, onRetryPlugin, displayMode }: OverviewPa
           skeletonLines={plugin.meta.lines}
           lastManualRefreshAt={plugin.lastManualRefreshAt}
           onRetry={onRetryPlugin ? () => onRetryPlugin(plugin.meta.id) : undefined}
+          onLogin={
+            onLoginPlugin && plugin.meta.login ? () => onLoginPlugin(plugin.meta.id) : undefined
+          }
+          loginStatus={loginStatus?.[plugin.meta.id]}
           scopeFilter="overview"
           displayMode={displayMode}
         />
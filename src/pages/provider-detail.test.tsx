@@ -40,5 +40,29 @@ describe("ProviderDetailPage", () => {
     )
     expect(screen.getAllByText("Alpha").length).toBeGreaterThan(0)
   })
-})
 
+  it("renders the settings form when the plugin declares settings", () => {
+    render(
+      <ProviderDetailPage
+        displayMode="used"
+        onSaveSettings={async () => {}}
+        settingsView={{ values: {}, secretsSet: [] }}
+        plugin={{
+          meta: {
+            id: "a",
+            name: "Alpha",
+            iconUrl: "",
+            lines: [],
+            settings: [{ key: "apiKey", label: "API key", type: "secret" }],
+          },
+          data: null,
+          loading: false,
+          error: null,
+          lastManualRefreshAt: null,
+        }}
+      />
+    )
+    expect(screen.getByText("Settings")).toBeInTheDocument()
+    expect(screen.getByLabelText("API key")).toBeInTheDocument()
+  })
+})
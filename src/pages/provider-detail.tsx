@@ -1,14 +1,32 @@
+import { PluginAccounts } from "@/components/plugin-accounts"
+import { PluginSettingsForm } from "@/components/plugin-settings-form"
 import { ProviderCard } from "@/components/provider-card"
-import type { PluginDisplayState } from "@/lib/plugin-types"
+import type { PluginDisplayState, PluginSettingsView } from "@/lib/plugin-types"
 import type { DisplayMode } from "@/lib/settings"
 
 interface ProviderDetailPageProps {
   plugin: PluginDisplayState | null
   onRetry?: () => void
+  onLogin?: () => void
+  loginStatus?: string | null
+  settingsView?: PluginSettingsView | null
+  onSaveSettings?: (changes: Record<string, string>) => Promise<void>
+  onAddAccount?: (name: string) => Promise<void>
+  onRemoveAccount?: () => Promise<void>
   displayMode: DisplayMode
 }
 
-export function ProviderDetailPage({ plugin, onRetry, displayMode }: ProviderDetailPageProps) {
+export function ProviderDetailPage({
+  plugin,
+  onRetry,
+  onLogin,
+  loginStatus,
+  settingsView,
+  onSaveSettings,
+  onAddAccount,
+  onRemoveAccount,
+  displayMode,
+}: ProviderDetailPageProps) {
   if (!plugin) {
     return (
       <div className="text-center text-muted-foreground py-8">
@@ -17,19 +35,42 @@ export function ProviderDetailPage({ plugin, onRetry, displayMode }: ProviderDet
     )
   }
 
+  const settings = plugin.meta.settings ?? []
+  const isInstance = Boolean(plugin.meta.instanceOf)
+
   return (
-    <ProviderCard
-      name={plugin.meta.name}
-      plan={plugin.data?.plan}
-      showSeparator={false}
-      loading={plugin.loading}
-      error={plugin.error}
-      lines={plugin.data?.lines ?? []}
-      skeletonLines={plugin.meta.lines}
-      lastManualRefreshAt={plugin.lastManualRefreshAt}
-      onRetry={onRetry}
-      scopeFilter="all"
-      displayMode={displayMode}
-    />
+    <>
+      <ProviderCard
+        name={plugin.meta.name}
+        plan={plugin.data?.plan}
+        showSeparator={false}
+        loading={plugin.loading}
+        error={plugin.error}
+        lines={plugin.data?.lines ?? []}
+        skeletonLines={plugin.meta.lines}
+        lastManualRefreshAt={plugin.lastManualRefreshAt}
+        onRetry={onRetry}
+        onLogin={plugin.meta.login ? onLogin : undefined}
+        loginStatus={loginStatus}
+        scopeFilter="all"
+        displayMode={displayMode}
+      />
+      {settings.length > 0 && onSaveSettings && (
+        <section className="pt-3">
+          <h3 className="mb-2 text-sm font-semibold">Settings</h3>
+          <PluginSettingsForm
+            fields={settings}
+            view={settingsView ?? null}
+            onSave={onSaveSettings}
+          />
+          <div className="pt-3">
+            <PluginAccounts
+              onAdd={isInstance ? undefined : onAddAccount}
+              onRemove={isInstance ? onRemoveAccount : undefined}
+            />
+          </div>
+        </section>
+      )}
+    </>
   )
 }